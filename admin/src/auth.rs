@@ -0,0 +1,170 @@
+use crate::context::{AuthConfig, Context};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, Method},
+    middleware::Next,
+    web::Data,
+    Error, HttpMessage, HttpResponse,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use secrecy::ExposeSecret;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use types::operator::{Operator, Role};
+use uuid::Uuid;
+
+const ISSUER: &str = "pontoon-admin";
+
+/// Routes of the admin API answered without an access token.
+const PUBLIC_PATHS: [&str; 1] = ["/admin/login"];
+
+/// Claims of operator access tokens, available to handlers as `ReqData<Claims>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Operator id.
+    pub sub: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub iss: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// Issues an access token for `operator`, valid for `token_ttl` seconds.
+pub fn issue_token(config: &AuthConfig, operator: &Operator) -> anyhow::Result<String> {
+    let (iat, exp) = token_lifetime(config)?;
+    encode_token(
+        config,
+        &Claims {
+            sub: operator.id,
+            username: operator.username.clone(),
+            role: operator.role,
+            iss: ISSUER.to_string(),
+            iat,
+            exp,
+        },
+    )
+}
+
+/// Issue and expiry times of a token issued now, in Unix seconds.
+pub(crate) fn token_lifetime(config: &AuthConfig) -> anyhow::Result<(u64, u64)> {
+    let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    Ok((iat, iat + config.token_ttl))
+}
+
+pub(crate) fn encode_token(config: &AuthConfig, claims: &impl Serialize) -> anyhow::Result<String> {
+    let key = EncodingKey::from_secret(config.jwt_secret.expose_secret().as_bytes());
+    Ok(jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        claims,
+        &key,
+    )?)
+}
+
+/// Validates a token and its issuer, so that tokens of operators and of dashboard accounts are
+/// not mistaken for one another.
+pub(crate) fn decode_token<T: DeserializeOwned>(
+    config: &AuthConfig,
+    issuer: &str,
+    token: &str,
+) -> anyhow::Result<T> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[issuer]);
+    validation.leeway = 0;
+    let key = DecodingKey::from_secret(config.jwt_secret.expose_secret().as_bytes());
+    Ok(jsonwebtoken::decode::<T>(token, &key, &validation)?.claims)
+}
+
+/// Token of the `Authorization: Bearer` header.
+pub(crate) fn bearer_token(req: &ServiceRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Role the admin route matching `pattern` requires.
+///
+/// Routes missing from this table are left to superadmins.
+fn required_role(method: &Method, pattern: &str) -> Role {
+    match (method, pattern) {
+        (&Method::GET, "/admin/client")
+        | (&Method::GET, "/admin/client/credentials")
+        | (&Method::GET, "/admin/client/certificates")
+        | (&Method::GET, "/admin/client/networks")
+        | (&Method::GET, "/admin/client/lockouts")
+        | (&Method::GET, "/admin/keys/rewrap") => Role::Viewer,
+        (&Method::POST, "/admin/client")
+        | (&Method::POST, "/admin/client/accounts")
+        | (&Method::POST, "/admin/client/credentials")
+        | (&Method::PATCH, "/admin/client/credentials/{api_key}")
+        | (&Method::DELETE, "/admin/client/credentials/{api_key}")
+        | (&Method::POST, "/admin/client/certificates")
+        | (&Method::DELETE, "/admin/client/certificates/{fingerprint}")
+        | (&Method::PUT, "/admin/client/networks")
+        | (&Method::DELETE, "/admin/client/lockouts/{api_key}") => Role::Operator,
+        _ => Role::Superadmin,
+    }
+}
+
+/// Validates the `Authorization: Bearer` access token and the role it grants for the route.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if PUBLIC_PATHS.contains(&req.path()) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let Some(ctx) = req.app_data::<Data<Context>>().cloned() else {
+        tracing::error!("Failed to extract context");
+        return Ok(req
+            .into_response(HttpResponse::InternalServerError().body("No context found"))
+            .map_into_right_body());
+    };
+
+    let token = bearer_token(&req);
+    let claims = match token.map(|token| decode_token::<Claims>(&ctx.config.auth, ISSUER, token)) {
+        Some(Ok(claims)) => claims,
+        Some(Err(err)) => {
+            tracing::error!("Invalid access token: {}", err);
+            return Ok(req.into_response(unauthorized()).map_into_right_body());
+        }
+        None => {
+            tracing::debug!("Missing access token");
+            return Ok(req.into_response(unauthorized()).map_into_right_body());
+        }
+    };
+
+    // Unknown routes are answered by the default service
+    if let Some(pattern) = req.match_pattern() {
+        let role = required_role(req.method(), &pattern);
+        if claims.role < role {
+            tracing::error!(
+                "Operator '{}' with role {} denied {} {}, requires {}",
+                claims.username,
+                claims.role,
+                req.method(),
+                pattern,
+                role
+            );
+            return Ok(req
+                .into_response(HttpResponse::Forbidden().body("Forbidden"))
+                .map_into_right_body());
+        }
+    }
+
+    req.extensions_mut().insert(claims);
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+pub(crate) fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+        .body("Unauthorized")
+}
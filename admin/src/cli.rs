@@ -0,0 +1,135 @@
+use crate::context::Config;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use clap::{Parser, Subcommand};
+use kms::shamir;
+use postgres_database::PostgresPool;
+use repositories::operator::OperatorRepository;
+use std::{io::BufRead, str::FromStr};
+use types::{
+    config::ConfigReader,
+    encrypt::Aes256Key,
+    env,
+    operator::{Operator, Role},
+};
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(about = "Pontoon admin service")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Runs the admin service, the default.
+    Serve,
+    /// Generates a master key and splits it into shares, one per operator.
+    Ceremony {
+        /// Number of shares to hand out.
+        #[arg(long, default_value_t = 5)]
+        shares: u8,
+        /// Number of shares needed to unseal.
+        #[arg(long, default_value_t = 3)]
+        threshold: u8,
+        /// Splits an existing master key file instead of generating a key.
+        #[arg(long)]
+        key_file: Option<String>,
+    },
+    /// Creates an operator of the admin API, e.g. the first superadmin.
+    ///
+    /// The password is read from stdin, the database from the service configuration.
+    CreateOperator {
+        #[arg(long)]
+        username: String,
+        /// viewer, operator or superadmin.
+        #[arg(long, value_parser = Role::from_str)]
+        role: Role,
+    },
+    /// Submits a key share read from stdin to a running service.
+    ///
+    /// The unseal token is read from `UNSEAL__TOKEN`.
+    Unseal {
+        /// Base URL of the admin or wallet service.
+        #[arg(long, default_value = "http://localhost:3001")]
+        url: String,
+        /// Master key the share belongs to, defaults to the one being unsealed.
+        #[arg(long)]
+        key_id: Option<String>,
+    },
+}
+
+pub fn ceremony(shares: u8, threshold: u8, key_file: Option<&str>) -> anyhow::Result<()> {
+    let key = match key_file {
+        Some(path) => {
+            let bytes = Zeroizing::new(URL_SAFE_NO_PAD.decode(env::read_file(path)?.trim())?);
+            Aes256Key::try_from(bytes.as_slice())?
+        }
+        None => Aes256Key::generate(),
+    };
+    let key_shares = shamir::split(&key, shares, threshold)?;
+
+    println!("Master key split into {shares} shares, {threshold} of them unseal it.");
+    println!("Hand out one share per operator and keep none of them with the service.\n");
+    for (index, share) in key_shares.shares.iter().enumerate() {
+        println!("Share {}: {}", index + 1, share.as_str());
+    }
+    println!("\nConfigure the services with:\n");
+    println!("KEY_PROVIDER__TYPE=shamir");
+    println!("KEY_PROVIDER__KEY_CHECK={}", key_shares.key_check);
+    Ok(())
+}
+
+pub async fn create_operator(username: String, role: Role) -> anyhow::Result<()> {
+    let config = Config::read_config()?;
+    let database = PostgresPool::new(&config.database).await?;
+
+    eprintln!("Password:");
+    let mut password = Zeroizing::new(String::new());
+    std::io::stdin().lock().read_line(&mut password)?;
+    let operator = Operator::new(username, password.trim_end_matches(['\r', '\n']), role)?;
+
+    let username = operator.username.clone();
+    if !OperatorRepository::create_operator(&database, operator).await? {
+        anyhow::bail!("Operator '{username}' already exists");
+    }
+    println!("Operator '{username}' created with role {role}");
+    Ok(())
+}
+
+pub async fn unseal(url: &str, key_id: Option<String>) -> anyhow::Result<()> {
+    let token = std::env::var("UNSEAL__TOKEN")
+        .map_err(|_| anyhow::anyhow!("Set UNSEAL__TOKEN to submit a key share"))?;
+
+    eprintln!("Key share:");
+    let mut share = Zeroizing::new(String::new());
+    std::io::stdin().lock().read_line(&mut share)?;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/unseal", url.trim_end_matches('/')))
+        .header("x-unseal-token", token)
+        .json(&serde_json::json!({ "share": share.trim(), "key_id": key_id }))
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("Unseal failed with {status}: {body}");
+    }
+    println!("{body}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ceremony_rejects_key_file_of_wrong_length() {
+        let path = std::env::temp_dir().join(format!("short-key-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, URL_SAFE_NO_PAD.encode([0u8; 16])).expect("write key file");
+        let result = ceremony(3, 2, path.to_str());
+        std::fs::remove_file(&path).expect("remove key file");
+        assert!(result.is_err());
+    }
+}
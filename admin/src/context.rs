@@ -1,19 +1,74 @@
-use postgres_database::PostgresPool;
+use crate::{
+    mail::{MailConfig, Mailer},
+    rotation::RewrapJob,
+};
+use kms::{
+    config::{KeyProviderConfig, MasterKeysConfig, UnsealConfig},
+    master_keys::MasterKeys,
+    unseal::UnsealContext,
+};
+use postgres_database::{KeyStore, PostgresPool};
+use secrecy::{ExposeSecret, SecretBox};
 use serde::Deserialize;
 use std::fmt::Debug;
-use types::{
-    config::ConfigReader,
-    db::postgres::PostgresConnection,
-    encrypt::master_key::{from_file, MasterKey},
-};
+use tls::config::TlsConfig;
+use types::{config::ConfigReader, db::postgres::PostgresConnection};
 
 #[derive(Deserialize)]
 pub struct Config {
     pub rust_log: String,
     pub port: u16,
-    #[serde(deserialize_with = "from_file")]
-    pub master_key: MasterKey,
-    database: PostgresConnection,
+    /// Path to the master key file, used when no key provider is configured.
+    pub master_key: Option<String>,
+    pub key_provider: Option<KeyProviderConfig>,
+    /// Several master keys during rotation, taking precedence over `key_provider`.
+    pub master_keys: Option<MasterKeysConfig>,
+    /// Required when a master key is split into shares.
+    pub unseal: Option<UnsealConfig>,
+    pub auth: AuthConfig,
+    pub dashboard: DashboardConfig,
+    pub mail: MailConfig,
+    /// Serves HTTPS, only to clients presenting a certificate issued by `client_ca` if set.
+    pub tls: Option<TlsConfig>,
+    pub(crate) database: PostgresConnection,
+    /// Database of the tenant keys, kept out of the backups of `database`.
+    pub(crate) key_store: PostgresConnection,
+}
+
+/// Access tokens of operators, e.g. `AUTH__JWT_SECRET`.
+#[derive(Deserialize)]
+pub struct AuthConfig {
+    /// HMAC key signing access tokens, at least 32 bytes long.
+    pub jwt_secret: SecretBox<String>,
+    /// Lifetime of access tokens in seconds.
+    #[serde(default = "default_token_ttl")]
+    pub token_ttl: u64,
+}
+
+fn default_token_ttl() -> u64 {
+    900
+}
+
+/// Dashboard of clients, e.g. `DASHBOARD__RESET_URL`.
+#[derive(Debug, Deserialize)]
+pub struct DashboardConfig {
+    /// Dashboard page setting a new password, mailed with the reset token appended as `?token=`.
+    pub reset_url: String,
+    /// Lifetime of password reset tokens in seconds.
+    #[serde(default = "default_reset_token_ttl")]
+    pub reset_token_ttl: u64,
+}
+
+fn default_reset_token_ttl() -> u64 {
+    3600
+}
+
+impl Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("token_ttl", &self.token_ttl)
+            .finish()
+    }
 }
 
 impl Debug for Config {
@@ -21,6 +76,13 @@ impl Debug for Config {
         f.debug_struct("Config")
             .field("port", &self.port)
             .field("rust_log", &self.rust_log)
+            .field("key_provider", &self.key_provider)
+            .field("master_keys", &self.master_keys)
+            .field("unseal", &self.unseal)
+            .field("auth", &self.auth)
+            .field("dashboard", &self.dashboard)
+            .field("mail", &self.mail)
+            .field("tls", &self.tls)
             .finish()
     }
 }
@@ -28,12 +90,46 @@ impl Debug for Config {
 pub struct Context {
     pub config: Config,
     pub database: PostgresPool,
+    pub key_store: KeyStore,
+    pub master_keys: MasterKeys,
+    pub mailer: Mailer,
+    pub rewrap: RewrapJob,
 }
 
 impl Context {
     pub async fn build() -> anyhow::Result<Self> {
         let config = Config::read_config()?;
+        if config.auth.jwt_secret.expose_secret().len() < 32 {
+            anyhow::bail!("AUTH__JWT_SECRET must be at least 32 bytes long");
+        }
+        let master_keys = MasterKeys::from_config(
+            config.master_keys.as_ref(),
+            config.key_provider.as_ref(),
+            config.master_key.as_deref(),
+        )?;
+        if master_keys.is_sealed() && config.unseal.is_none() {
+            anyhow::bail!("Set UNSEAL__TOKEN to unseal the master keys");
+        }
+        let mailer = Mailer::new(&config.mail)?;
         let database = PostgresPool::new(&config.database).await?;
-        Ok(Self { config, database })
+        let key_store = KeyStore::new(&config.key_store).await?;
+        Ok(Self {
+            config,
+            database,
+            key_store,
+            master_keys,
+            mailer,
+            rewrap: RewrapJob::default(),
+        })
+    }
+}
+
+impl UnsealContext for Context {
+    fn master_keys(&self) -> &MasterKeys {
+        &self.master_keys
+    }
+
+    fn unseal_config(&self) -> Option<&UnsealConfig> {
+        self.config.unseal.as_ref()
     }
 }
@@ -0,0 +1,357 @@
+use crate::{
+    auth,
+    context::Context,
+    mail::{Mail, MailSender},
+    routes::{self, CredentialsExpiryRequest, LoginResponse},
+};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web::{self, Bytes, Data, Json, Path, Query, ReqData},
+    Error, HttpMessage, HttpResponse,
+};
+use repositories::{client::ClientRepository, dashboard::DashboardRepository};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use types::{
+    client::ClientId,
+    dashboard::{self, DashboardAccount, ResetToken},
+    password,
+    secret::redact::Redacted,
+};
+use uuid::Uuid;
+
+const ISSUER: &str = "pontoon-dashboard";
+
+/// Routes of the dashboard answered without an access token.
+const PUBLIC_PATHS: [&str; 3] = [
+    "/dashboard/login",
+    "/dashboard/password/forgot",
+    "/dashboard/password/reset",
+];
+
+/// Users listed per page when no limit is requested, and at most.
+const DEFAULT_USERS_LIMIT: i64 = 100;
+const MAX_USERS_LIMIT: i64 = 1000;
+
+/// Claims of dashboard access tokens, available to handlers as `ReqData<DashboardClaims>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardClaims {
+    /// Dashboard account id.
+    pub sub: Uuid,
+    pub email: String,
+    /// Name of the client the account belongs to, the only one it is granted.
+    pub client: String,
+    pub iss: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// Validates the `Authorization: Bearer` access token of a dashboard account.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if PUBLIC_PATHS.contains(&req.path()) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let Some(ctx) = req.app_data::<Data<Context>>().cloned() else {
+        tracing::error!("Failed to extract context");
+        return Ok(req
+            .into_response(HttpResponse::InternalServerError().body("No context found"))
+            .map_into_right_body());
+    };
+
+    let token = auth::bearer_token(&req);
+    let claims = match token
+        .map(|token| auth::decode_token::<DashboardClaims>(&ctx.config.auth, ISSUER, token))
+    {
+        Some(Ok(claims)) => claims,
+        Some(Err(err)) => {
+            tracing::error!("Invalid dashboard access token: {}", err);
+            return Ok(req
+                .into_response(auth::unauthorized())
+                .map_into_right_body());
+        }
+        None => {
+            tracing::debug!("Missing dashboard access token");
+            return Ok(req
+                .into_response(auth::unauthorized())
+                .map_into_right_body());
+        }
+    };
+
+    req.extensions_mut().insert(claims);
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DashboardLoginRequest {
+    pub email: String,
+    pub password: Redacted<String>,
+}
+
+pub(crate) async fn login(
+    ctx: Data<Context>,
+    body: Json<DashboardLoginRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let DashboardLoginRequest { email, password } = body.into_inner();
+    let account = match dashboard::normalize_email(&email) {
+        Ok(email) => match DashboardRepository::find_account(&ctx.database, &email).await {
+            Ok(account) => account,
+            Err(err) => {
+                tracing::error!("Failed to retrieve dashboard account: {}", err);
+                return Ok(HttpResponse::InternalServerError().finish());
+            }
+        },
+        Err(_) => None,
+    };
+
+    // Password hashing is slow on purpose, keep it off the workers
+    let result = web::block(move || {
+        DashboardAccount::login(account.as_ref(), password.expose()).map(|_| account)
+    })
+    .await?;
+    let account = match result {
+        Ok(Some(account)) => account,
+        Ok(None) | Err(_) => {
+            tracing::error!("Failed login of dashboard account '{}'", email);
+            return Ok(HttpResponse::Unauthorized().body("Invalid email or password"));
+        }
+    };
+
+    let client = match ClientRepository::find(&ctx.database, account.client_id.clone()).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            tracing::error!("Client of dashboard account '{}' not found", account.email);
+            return Ok(HttpResponse::Unauthorized().body("Invalid email or password"));
+        }
+        Err(err) => {
+            tracing::error!("Failed to retrieve client: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+
+    let token = auth::token_lifetime(&ctx.config.auth).and_then(|(iat, exp)| {
+        auth::encode_token(
+            &ctx.config.auth,
+            &DashboardClaims {
+                sub: account.id,
+                email: account.email.clone(),
+                client: client.name,
+                iss: ISSUER.to_string(),
+                iat,
+                exp,
+            },
+        )
+    });
+    match token {
+        Ok(access_token) => {
+            tracing::info!("Dashboard account '{}' logged in", account.email);
+            Ok(HttpResponse::Ok().json(LoginResponse {
+                access_token,
+                token_type: "Bearer",
+                expires_in: ctx.config.auth.token_ttl,
+            }))
+        }
+        Err(err) => {
+            tracing::error!("Failed to issue access token: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+/// Mails a password reset token to the account owner.
+///
+/// Answers `202 Accepted` whether or not an account has the email address, so that addresses
+/// cannot be probed.
+pub(crate) async fn forgot_password(
+    ctx: Data<Context>,
+    body: Json<ForgotPasswordRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let account = match dashboard::normalize_email(&body.email) {
+        Ok(email) => match DashboardRepository::find_account(&ctx.database, &email).await {
+            Ok(account) => account,
+            Err(err) => {
+                tracing::error!("Failed to retrieve dashboard account: {}", err);
+                return Ok(HttpResponse::InternalServerError().finish());
+            }
+        },
+        Err(_) => None,
+    };
+    let Some(account) = account else {
+        tracing::debug!("Password reset requested for an unknown account");
+        return Ok(HttpResponse::Accepted().finish());
+    };
+
+    let reset_token = ResetToken::generate();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let expires_at = (now + ctx.config.dashboard.reset_token_ttl) as i64;
+    if let Err(err) = DashboardRepository::create_password_reset(
+        &ctx.database,
+        account.id,
+        &reset_token.hash,
+        expires_at,
+    )
+    .await
+    {
+        tracing::error!("Failed to store password reset token: {}", err);
+        return Ok(HttpResponse::InternalServerError().finish());
+    }
+
+    let mail = Mail {
+        to: account.email.clone(),
+        subject: "Reset your Pontoon dashboard password".to_string(),
+        body: format!(
+            "A password reset was requested for your Pontoon dashboard account.\n\n\
+             Set a new password within {} minutes at:\n{}?token={}\n\n\
+             If you did not request it, ignore this mail.\n",
+            ctx.config.dashboard.reset_token_ttl / 60,
+            ctx.config.dashboard.reset_url,
+            reset_token.token.expose()
+        ),
+    };
+    // Sent in the background, so that the response time does not reveal the account exists
+    let ctx = ctx.clone();
+    actix_web::rt::spawn(async move {
+        match ctx.mailer.send(mail).await {
+            Ok(()) => tracing::info!("Password reset mailed to '{}'", account.email),
+            Err(err) => tracing::error!(
+                "Failed to mail password reset to '{}': {}",
+                account.email,
+                err
+            ),
+        }
+    });
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: Redacted<String>,
+    pub password: Redacted<String>,
+}
+
+pub(crate) async fn reset_password(
+    ctx: Data<Context>,
+    body: Json<ResetPasswordRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let ResetPasswordRequest { token, password } = body.into_inner();
+    let password_hash = web::block(move || password::hash_password(password.expose()))
+        .await?
+        .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))?;
+
+    match DashboardRepository::reset_password(
+        &ctx.database,
+        &ResetToken::hash(token.expose()),
+        &password_hash,
+    )
+    .await
+    {
+        Ok(true) => {
+            tracing::info!("Dashboard account password reset");
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Ok(false) => {
+            tracing::error!("Password reset with an invalid token");
+            Ok(HttpResponse::BadRequest().body(types::error::Error::InvalidResetToken.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to reset password: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn get_client(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+) -> actix_web::Result<HttpResponse> {
+    routes::client_response(&ctx, &claims.client).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsersQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub(crate) async fn list_users(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+    query: Query<UsersQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_USERS_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_USERS_LIMIT).contains(&limit) || offset < 0 {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "limit must be between 1 and {MAX_USERS_LIMIT}, offset not negative"
+        )));
+    }
+
+    match ClientRepository::list_users(
+        &ctx.database,
+        ClientId::from(claims.client.as_str()),
+        limit,
+        offset,
+    )
+    .await
+    {
+        Ok(users) => Ok(HttpResponse::Ok().json(users)),
+        Err(err) => {
+            tracing::error!("Failed to list users: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn list_credentials(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+) -> actix_web::Result<HttpResponse> {
+    routes::list_client_credentials(&ctx, &claims.client).await
+}
+
+pub(crate) async fn issue_credentials(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+    body: Bytes,
+) -> actix_web::Result<HttpResponse> {
+    routes::issue_client_credentials(&ctx, &claims.client, &body).await
+}
+
+pub(crate) async fn set_credentials_expiry(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+    api_key: Path<Uuid>,
+    body: Json<CredentialsExpiryRequest>,
+) -> actix_web::Result<HttpResponse> {
+    routes::set_client_credentials_expiry(
+        &ctx,
+        &claims.client,
+        api_key.into_inner(),
+        body.expires_at,
+    )
+    .await
+}
+
+pub(crate) async fn revoke_credentials(
+    ctx: Data<Context>,
+    claims: ReqData<DashboardClaims>,
+    api_key: Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    routes::revoke_client_credentials(&ctx, &claims.client, api_key.into_inner()).await
+}
@@ -1,16 +1,39 @@
+mod auth;
+mod cli;
 mod context;
+mod dashboard;
+mod mail;
+mod rotation;
 mod routes;
 mod server;
 
-use crate::{context::Context, server::make_server};
+use crate::{
+    cli::{Cli, Command},
+    context::Context,
+    server::make_server,
+};
+use clap::Parser;
 use std::str::FromStr;
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Some(Command::Ceremony {
+            shares,
+            threshold,
+            key_file,
+        }) => return cli::ceremony(shares, threshold, key_file.as_deref()),
+        Some(Command::CreateOperator { username, role }) => {
+            return cli::create_operator(username, role).await
+        }
+        Some(Command::Unseal { url, key_id }) => return cli::unseal(&url, key_id).await,
+        Some(Command::Serve) | None => {}
+    }
+
     let ctx = Context::build().await?;
 
-    let tracing_level = tracing::Level::from_str(&ctx.config.rust_log)
-        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    let tracing_level =
+        tracing::Level::from_str(&ctx.config.rust_log).map_err(std::io::Error::other)?;
 
     tracing::subscriber::set_global_default(
         tracing_subscriber::FmtSubscriber::builder()
@@ -18,13 +41,18 @@ async fn main() -> anyhow::Result<()> {
             .finish(),
     )
     .map_err(|err| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to initialize tracing subscriber: {}", err),
-        )
+        std::io::Error::other(format!("Failed to initialize tracing subscriber: {}", err))
     })?;
 
     tracing::info!("Starting middleware service with config: {:?}", ctx.config);
+    if ctx.master_keys.is_sealed() {
+        tracing::warn!("Master keys are sealed, submit key shares to POST /unseal");
+    }
+    // Tenant keys were stored in the main database before the key store
+    let moved = ctx.database.move_tenant_keys(&ctx.key_store).await?;
+    if moved > 0 {
+        tracing::info!("Moved {} tenant keys to the key store", moved);
+    }
 
     let server = make_server(ctx)?;
 
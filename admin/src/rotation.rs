@@ -0,0 +1,273 @@
+use crate::context::Context;
+use actix_web::web::Data;
+use kms::master_keys::MasterKeys;
+use repositories::{
+    master_key::MasterKeyRepository, tenant_key::TenantKeyRepository, wallet::WalletRepository,
+};
+use serde::Serialize;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Mutex,
+};
+use types::{
+    client::ClientId,
+    encrypt::{
+        provider::KeyRing,
+        rotation::{DataKeyOwner, WrappedDataKey},
+    },
+    tenant::{TenantKey, TenantKeyRing, TENANT_KEY_ID},
+    user::encrypt::EncryptedUser,
+};
+use uuid::Uuid;
+
+/// Number of data keys read from the database at once.
+const BATCH_SIZE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RewrapState {
+    #[default]
+    Idle,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RewrapStatus {
+    pub state: RewrapState,
+    /// Master key the data keys are re-wrapped under.
+    pub master_key_id: String,
+    pub rewrapped: u64,
+    /// Data keys that could not be unwrapped, e.g. wrapped by a master key no longer configured.
+    pub failed: u64,
+    /// Public keys stored for users registered before public keys were kept in clear.
+    pub public_keys: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Progress of the re-wrap job, which runs at most once at a time.
+///
+/// Only data keys not yet wrapped by the active master key are visited, so a job
+/// interrupted by a failure or a restart resumes where it stopped when started again.
+#[derive(Default)]
+pub struct RewrapJob {
+    status: Mutex<RewrapStatus>,
+}
+
+impl RewrapJob {
+    pub fn status(&self) -> RewrapStatus {
+        self.status.lock().expect("rewrap status").clone()
+    }
+
+    /// Returns `false` if the job is already running.
+    pub fn start(&self, master_key_id: &str) -> bool {
+        let mut status = self.status.lock().expect("rewrap status");
+        if status.state == RewrapState::Running {
+            return false;
+        }
+        *status = RewrapStatus {
+            state: RewrapState::Running,
+            master_key_id: master_key_id.to_string(),
+            ..RewrapStatus::default()
+        };
+        true
+    }
+
+    fn update(&self, update: impl FnOnce(&mut RewrapStatus)) {
+        update(&mut self.status.lock().expect("rewrap status"));
+    }
+}
+
+/// Keys of each table, tenant keys first so that data keys end up under re-wrapped tenant keys.
+pub const OWNERS: [DataKeyOwner; 3] = [
+    DataKeyOwner::TenantKeys,
+    DataKeyOwner::Credentials,
+    DataKeyOwner::Users,
+];
+
+/// Key the keys of `owner` end up wrapped by: the active master key for tenant keys, the
+/// client's tenant key for data keys.
+pub fn target_key_id(master_keys: &MasterKeys, owner: DataKeyOwner) -> &str {
+    match owner {
+        DataKeyOwner::TenantKeys => master_keys.active_key_id(),
+        DataKeyOwner::Credentials | DataKeyOwner::Users => TENANT_KEY_ID,
+    }
+}
+
+/// Re-wraps every tenant key under the active master key, and every data key under its
+/// client's tenant key, then stores the public keys of users registered without one.
+pub async fn rewrap_data_keys(ctx: Data<Context>) {
+    let result = rewrap_all(&ctx).await;
+    ctx.rewrap.update(|status| match result {
+        Ok(()) => status.state = RewrapState::Completed,
+        Err(err) => {
+            status.state = RewrapState::Failed;
+            status.error = Some(err.to_string());
+        }
+    });
+    let status = ctx.rewrap.status();
+    tracing::info!(
+        "Re-wrap job {:?}: {} data keys re-wrapped, {} failed",
+        status.state,
+        status.rewrapped,
+        status.failed
+    );
+}
+
+async fn rewrap_all(ctx: &Context) -> anyhow::Result<()> {
+    let mut tenant_keys = HashMap::new();
+    for owner in OWNERS {
+        match owner {
+            DataKeyOwner::TenantKeys => {
+                rewrap_owner(ctx, &ctx.key_store, owner, &mut tenant_keys).await?
+            }
+            DataKeyOwner::Credentials | DataKeyOwner::Users => {
+                rewrap_owner(ctx, &ctx.database, owner, &mut tenant_keys).await?
+            }
+        }
+    }
+    store_public_keys(ctx, &mut tenant_keys).await
+}
+
+/// Re-wraps the keys of `owner`, read from `store`.
+async fn rewrap_owner(
+    ctx: &Context,
+    store: &(impl MasterKeyRepository + Sync),
+    owner: DataKeyOwner,
+    tenant_keys: &mut HashMap<Uuid, TenantKey>,
+) -> anyhow::Result<()> {
+    let key_id = target_key_id(&ctx.master_keys, owner);
+    let mut after = None;
+    loop {
+        let data_keys = store
+            .find_data_keys_to_rewrap(owner, key_id, after, BATCH_SIZE)
+            .await?;
+        let Some(last) = data_keys.last() else {
+            break;
+        };
+        after = Some(last.id);
+
+        for data_key in data_keys {
+            match rewrap(ctx, store, tenant_keys, &data_key).await {
+                Ok(true) => ctx.rewrap.update(|status| status.rewrapped += 1),
+                Ok(false) => tracing::debug!(
+                    "Data key of {:?} {} changed while re-wrapping",
+                    data_key.owner,
+                    data_key.id
+                ),
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to re-wrap data key of {:?} {}: {}",
+                        data_key.owner,
+                        data_key.id,
+                        err
+                    );
+                    ctx.rewrap.update(|status| status.failed += 1);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Stores the public keys of users registered before they were kept next to the private
+/// key, so that verifying their signatures does not decrypt it.
+async fn store_public_keys(
+    ctx: &Context,
+    tenant_keys: &mut HashMap<Uuid, TenantKey>,
+) -> anyhow::Result<()> {
+    let mut after = None;
+    loop {
+        let users =
+            WalletRepository::find_users_without_public_key(&ctx.database, after, BATCH_SIZE)
+                .await?;
+        let Some((_, last)) = users.last() else {
+            break;
+        };
+        after = Some(last.id.clone());
+
+        for (client_id, encrypted_user) in users {
+            let user_id = encrypted_user.id.clone();
+            match store_public_key(ctx, tenant_keys, client_id, encrypted_user).await {
+                Ok(true) => ctx.rewrap.update(|status| status.public_keys += 1),
+                Ok(false) => {
+                    tracing::debug!("User {:?} deleted while storing its public key", user_id)
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to store the public key of user {:?}: {}",
+                        user_id,
+                        err
+                    );
+                    ctx.rewrap.update(|status| status.failed += 1);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn store_public_key(
+    ctx: &Context,
+    tenant_keys: &mut HashMap<Uuid, TenantKey>,
+    client_id: ClientId,
+    encrypted_user: EncryptedUser,
+) -> anyhow::Result<bool> {
+    let tenant_key = match tenant_keys.entry(client_id.clone().into()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(tenant_key(ctx, client_id.clone()).await?),
+    };
+    let key_ring = TenantKeyRing::new(&ctx.master_keys, Some(&*tenant_key));
+    let user_id = encrypted_user.id.clone();
+    let user = encrypted_user.decrypt(&key_ring).await?;
+    let public_key = user.signing_key.public_key_pem()?;
+    WalletRepository::set_public_key(&ctx.database, client_id, user_id, public_key).await
+}
+
+/// Returns `false` if the data key changed since it was read, e.g. the row was deleted.
+async fn rewrap(
+    ctx: &Context,
+    store: &(impl MasterKeyRepository + Sync),
+    tenant_keys: &mut HashMap<Uuid, TenantKey>,
+    data_key: &WrappedDataKey,
+) -> anyhow::Result<bool> {
+    let tenant_key = match (data_key.owner, data_key.client_id) {
+        (DataKeyOwner::TenantKeys, _) => None,
+        (_, Some(client_id)) => Some(match tenant_keys.entry(client_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(tenant_key(ctx, client_id.into()).await?),
+        }),
+        (_, None) => anyhow::bail!("data key without a client"),
+    };
+    let key_ring = TenantKeyRing::new(&ctx.master_keys, tenant_key.map(|key| &*key));
+
+    let key = key_ring
+        .unwrap_data_key(&data_key.master_key_id, &data_key.encrypted_data_key)
+        .await?;
+    let encrypted_data_key = key_ring.wrap_data_key(&key).await?;
+    let replaced = store
+        .replace_data_key(data_key, key_ring.active_key_id(), encrypted_data_key)
+        .await?;
+    Ok(replaced)
+}
+
+/// Loads the tenant key of the client, created for clients created before tenant keys.
+async fn tenant_key(ctx: &Context, client_id: ClientId) -> anyhow::Result<TenantKey> {
+    if let Some(encrypted_tenant_key) =
+        TenantKeyRepository::find_tenant_key(&ctx.key_store, client_id.clone()).await?
+    {
+        return Ok(encrypted_tenant_key.decrypt(&ctx.master_keys).await?);
+    }
+
+    let tenant_key = TenantKey::generate(client_id.clone());
+    let encrypted_tenant_key = tenant_key.encrypt(&ctx.master_keys).await?;
+    anyhow::ensure!(
+        TenantKeyRepository::create_tenant_key(&ctx.key_store, encrypted_tenant_key).await?,
+        "tenant key of client {:?} created concurrently",
+        client_id
+    );
+    tracing::info!("Created the tenant key of client {:?}", client_id);
+    Ok(tenant_key)
+}
@@ -1,15 +1,188 @@
-use crate::context::Context;
+use crate::{
+    auth::{self, Claims},
+    context::Context,
+    rotation::{self, RewrapStatus},
+};
 use actix_web::{
-    web::{Data, Json, Query},
+    web::{self, Bytes, Data, Json, Path, Query, ReqData},
     HttpResponse,
 };
-use repositories::client::ClientRepository;
+use repositories::{
+    certificate::CertificateRepository,
+    client::{ClientRepository, CreateClientResult},
+    credentials::CredentialsRepository,
+    dashboard::DashboardRepository,
+    lockout::LockoutRepository,
+    master_key::MasterKeyRepository,
+    network::NetworkRepository,
+    operator::OperatorRepository,
+    tenant_key::TenantKeyRepository,
+};
 use serde::{Deserialize, Serialize};
-use types::{api_key::ApiKey, client::Client, secret::mask::Masked};
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use types::{
+    api_key::ApiKey,
+    certificate::CertificateFingerprint,
+    client::{Client, ClientId, Credentials, CredentialsInfo, CredentialsState},
+    client_key::PublicKeyCredentials,
+    dashboard::DashboardAccount,
+    encrypt::{provider::KeyRing, rotation::DataKeyOwner},
+    network::IpNetwork,
+    operator::{Operator, Role},
+    scope::Scope,
+    secret::{mask::Masked, redact::Redacted},
+    tenant::TenantKeyRing,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: Redacted<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    /// Lifetime of the access token in seconds.
+    pub expires_in: u64,
+}
+
+pub(crate) async fn login(
+    ctx: Data<Context>,
+    body: Json<LoginRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let LoginRequest { username, password } = body.into_inner();
+    let operator = match OperatorRepository::find_operator(&ctx.database, &username).await {
+        Ok(operator) => operator,
+        Err(err) => {
+            tracing::error!("Failed to retrieve operator: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+
+    // Password hashing is slow on purpose, keep it off the workers
+    let result =
+        web::block(move || Operator::login(operator.as_ref(), password.expose()).map(|_| operator))
+            .await?;
+    let operator = match result {
+        Ok(Some(operator)) => operator,
+        Ok(None) | Err(_) => {
+            tracing::error!("Failed login of operator '{}'", username);
+            return Ok(HttpResponse::Unauthorized().body("Invalid username or password"));
+        }
+    };
+
+    match auth::issue_token(&ctx.config.auth, &operator) {
+        Ok(access_token) => {
+            tracing::info!("Operator '{}' logged in", operator.username);
+            Ok(HttpResponse::Ok().json(LoginResponse {
+                access_token,
+                token_type: "Bearer",
+                expires_in: ctx.config.auth.token_ttl,
+            }))
+        }
+        Err(err) => {
+            tracing::error!("Failed to issue access token: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOperatorRequest {
+    pub username: String,
+    pub password: Redacted<String>,
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperatorResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub role: Role,
+}
+
+impl From<Operator> for OperatorResponse {
+    fn from(operator: Operator) -> Self {
+        OperatorResponse {
+            id: operator.id,
+            username: operator.username,
+            role: operator.role,
+        }
+    }
+}
+
+pub(crate) async fn create_operator(
+    ctx: Data<Context>,
+    claims: ReqData<Claims>,
+    body: Json<CreateOperatorRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let CreateOperatorRequest {
+        username,
+        password,
+        role,
+    } = body.into_inner();
+    if username.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "username must not be empty",
+        ));
+    }
+
+    let operator = web::block(move || Operator::new(username, password.expose(), role))
+        .await?
+        .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))?;
+    let response = OperatorResponse {
+        id: operator.id,
+        username: operator.username.clone(),
+        role: operator.role,
+    };
+    match OperatorRepository::create_operator(&ctx.database, operator).await {
+        Ok(true) => {
+            tracing::info!(
+                "Operator '{}' created with role {} by '{}'",
+                response.username,
+                response.role,
+                claims.username
+            );
+            Ok(HttpResponse::Created().json(response))
+        }
+        Ok(false) => {
+            tracing::debug!("Operator '{}' already exists", response.username);
+            Ok(HttpResponse::Conflict().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to store operator: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn list_operators(ctx: Data<Context>) -> actix_web::Result<HttpResponse> {
+    match OperatorRepository::list_operators(&ctx.database).await {
+        Ok(operators) => Ok(HttpResponse::Ok().json(
+            operators
+                .into_iter()
+                .map(OperatorResponse::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(err) => {
+            tracing::error!("Failed to list operators: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct CreateClientRequest {
     pub name: String,
+    /// Dashboard account created along with the client, when set with `password`.
+    pub email: Option<String>,
+    pub password: Option<Redacted<String>>,
 }
 
 pub(crate) async fn create_client(
@@ -17,22 +190,150 @@ pub(crate) async fn create_client(
     body: Json<CreateClientRequest>,
 ) -> actix_web::Result<HttpResponse> {
     tracing::debug!("Creating client: {:?}", body);
-    let client = Client::new(body.name.clone());
-    match client.encrypt(&ctx.config.master_key) {
-        Ok(encrypted_client) => {
-            match ClientRepository::create(&ctx.database, encrypted_client).await {
-                Ok(_) => Ok(HttpResponse::Created().json(client)),
-                Err(err) => {
-                    tracing::error!("Failed to store client: {}", err);
-                    Ok(HttpResponse::InternalServerError().finish())
-                }
+    let CreateClientRequest {
+        name,
+        email,
+        password,
+    } = body.into_inner();
+    let client = Client::new(name);
+    let account = match (email, password) {
+        (Some(email), Some(password)) => {
+            Some(new_dashboard_account(client.id().clone(), email, password).await?)
+        }
+        (None, None) => None,
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "email and password go together",
+            ))
+        }
+    };
+    let email = account.as_ref().map(|account| account.email.clone());
+
+    let (encrypted_client, tenant_key) =
+        match client.encrypt_with_tenant_key(&ctx.master_keys).await {
+            Ok(encrypted_client) => encrypted_client,
+            Err(err) => {
+                tracing::error!("Failed to encrypt client: {}", err);
+                return Ok(HttpResponse::InternalServerError().finish());
             }
+        };
+    match ClientRepository::create_with_tenant_key(
+        &ctx.database,
+        encrypted_client,
+        tenant_key,
+        account,
+        &ctx.key_store,
+    )
+    .await
+    {
+        Ok(CreateClientResult::Created) => {}
+        Ok(CreateClientResult::NameTaken) => {
+            tracing::debug!("Client '{}' already exists", client.name);
+            return Ok(HttpResponse::Conflict().body("client already exists"));
+        }
+        Ok(CreateClientResult::EmailTaken) => {
+            tracing::debug!(
+                "Dashboard account '{}' already exists",
+                email.unwrap_or_default()
+            );
+            return Ok(HttpResponse::Conflict().body("email is already taken"));
+        }
+        Err(err) => {
+            tracing::error!("Failed to store client: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    }
+    if let Some(email) = email {
+        tracing::info!(
+            "Dashboard account '{}' created for client '{}'",
+            email,
+            client.name
+        );
+    }
+    Ok(HttpResponse::Created().json(client))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDashboardAccountRequest {
+    pub email: String,
+    pub password: Redacted<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DashboardAccountResponse {
+    pub id: Uuid,
+    pub email: String,
+}
+
+/// Creates a dashboard account of an existing client.
+pub(crate) async fn create_dashboard_account(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    body: Json<CreateDashboardAccountRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let CreateDashboardAccountRequest { email, password } = body.into_inner();
+    match ClientRepository::find_by_name(&ctx.database, &query.name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            tracing::debug!("Client not found");
+            return Ok(HttpResponse::NotFound().finish());
         }
         Err(err) => {
-            tracing::error!("Failed to encrypt client: {}", err);
+            tracing::error!("Failed to retrieve client: {}", err);
             return Ok(HttpResponse::InternalServerError().finish());
         }
     }
+
+    let account =
+        new_dashboard_account(ClientId::from(query.name.as_str()), email, password).await?;
+    let response = DashboardAccountResponse {
+        id: account.id,
+        email: account.email.clone(),
+    };
+    if !store_dashboard_account(&ctx, account, &query.name).await? {
+        return Ok(HttpResponse::Conflict().body("email is already taken"));
+    }
+    Ok(HttpResponse::Created().json(response))
+}
+
+async fn new_dashboard_account(
+    client_id: ClientId,
+    email: String,
+    password: Redacted<String>,
+) -> actix_web::Result<DashboardAccount> {
+    // Password hashing is slow on purpose, keep it off the workers
+    web::block(move || DashboardAccount::new(client_id, &email, password.expose()))
+        .await?
+        .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))
+}
+
+/// Returns `false` if the email address is already taken.
+async fn store_dashboard_account(
+    ctx: &Context,
+    account: DashboardAccount,
+    client_name: &str,
+) -> actix_web::Result<bool> {
+    let email = account.email.clone();
+    match DashboardRepository::create_account(&ctx.database, account).await {
+        Ok(true) => {
+            tracing::info!(
+                "Dashboard account '{}' created for client '{}'",
+                email,
+                client_name
+            );
+            Ok(true)
+        }
+        Ok(false) => {
+            tracing::debug!("Dashboard account '{}' already exists", email);
+            Ok(false)
+        }
+        Err(err) => {
+            tracing::error!("Failed to store dashboard account: {}", err);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to store dashboard account",
+            ))
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,7 +351,12 @@ pub(crate) async fn get_client(
     ctx: Data<Context>,
     query: Query<ClientQuery>,
 ) -> actix_web::Result<HttpResponse> {
-    match ClientRepository::find_by_name(&ctx.database, &query.name).await {
+    client_response(&ctx, &query.name).await
+}
+
+/// Answers the client named `name` with its oldest active API key, masked.
+pub(crate) async fn client_response(ctx: &Context, name: &str) -> actix_web::Result<HttpResponse> {
+    match ClientRepository::find_by_name(&ctx.database, name).await {
         Ok(Some(client)) => {
             tracing::debug!("Retrieved client: {:?}", client);
             let response = GetClientResponse {
@@ -69,3 +375,603 @@ pub(crate) async fn get_client(
         }
     }
 }
+
+/// Deletes the client with its tenant key, leaving its secrets unrecoverable.
+pub(crate) async fn delete_client(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let client_id = ClientId::from(query.name.as_str());
+    // The tenant key goes first: without it the secrets of the client are unrecoverable
+    if let Err(err) =
+        TenantKeyRepository::delete_tenant_key(&ctx.key_store, client_id.clone()).await
+    {
+        tracing::error!("Failed to delete tenant key: {}", err);
+        return Ok(HttpResponse::InternalServerError().finish());
+    }
+    match ClientRepository::delete(&ctx.database, client_id).await {
+        Ok(true) => {
+            tracing::info!("Client '{}' deleted", query.name);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Ok(false) => {
+            tracing::debug!("Client not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to delete client: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CredentialsExpiryRequest {
+    /// Unix timestamp in seconds, `None` for credentials that do not expire.
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IssueCredentialsRequest {
+    /// Unix timestamp in seconds, `None` for credentials that do not expire.
+    pub expires_at: Option<i64>,
+    /// Every scope when not set.
+    pub scopes: Option<Vec<Scope>>,
+    /// PEM encoded Ed25519 or P-256 public key the client signs requests with, instead of a
+    /// secret generated by the server.
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssueCredentialsResponse<C> {
+    #[serde(flatten)]
+    pub credentials: C,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialsResponse {
+    #[serde(flatten)]
+    pub info: CredentialsInfo,
+    pub state: CredentialsState,
+}
+
+impl From<CredentialsInfo> for CredentialsResponse {
+    fn from(info: CredentialsInfo) -> Self {
+        let state = info.state(now());
+        CredentialsResponse { info, state }
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+fn check_expiry(expires_at: Option<i64>) -> actix_web::Result<()> {
+    match expires_at {
+        Some(expires_at) if expires_at <= now() => Err(actix_web::error::ErrorBadRequest(
+            "expires_at must be in the future",
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) async fn issue_credentials(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    body: Bytes,
+) -> actix_web::Result<HttpResponse> {
+    issue_client_credentials(&ctx, &query.name, &body).await
+}
+
+/// Issues additional credentials for the client named `name`, next to the ones it already has.
+///
+/// The request body is optional, credentials do not expire and have every scope by default.
+/// Credentials registering a public key get no secret.
+pub(crate) async fn issue_client_credentials(
+    ctx: &Context,
+    name: &str,
+    body: &[u8],
+) -> actix_web::Result<HttpResponse> {
+    let request: IssueCredentialsRequest = if body.is_empty() {
+        IssueCredentialsRequest::default()
+    } else {
+        serde_json::from_slice(body).map_err(actix_web::error::ErrorBadRequest)?
+    };
+    check_expiry(request.expires_at)?;
+    let scopes = request.scopes.unwrap_or_else(|| Scope::ALL.to_vec());
+    if scopes.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "credentials need at least one scope",
+        ));
+    }
+
+    let client = match ClientRepository::find_by_name(&ctx.database, name).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            tracing::debug!("Client not found");
+            return Ok(HttpResponse::NotFound().finish());
+        }
+        Err(err) => {
+            tracing::error!("Failed to retrieve client: {}", err);
+            return Ok(HttpResponse::InternalServerError().finish());
+        }
+    };
+    if let Some(public_key) = request.public_key {
+        let credentials = PublicKeyCredentials::new(client.id, &public_key, scopes)
+            .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))?;
+        return issue_public_key_credentials(ctx, name, credentials, request.expires_at).await;
+    }
+
+    let tenant_key =
+        match TenantKeyRepository::find_tenant_key(&ctx.key_store, client.id.clone()).await {
+            Ok(Some(tenant_key)) => match tenant_key.decrypt(&ctx.master_keys).await {
+                Ok(tenant_key) => Some(tenant_key),
+                Err(err) => {
+                    tracing::error!("Failed to decrypt tenant key: {}", err);
+                    return Ok(HttpResponse::InternalServerError().finish());
+                }
+            },
+            Ok(None) => None,
+            Err(err) => {
+                tracing::error!("Failed to retrieve tenant key: {}", err);
+                return Ok(HttpResponse::InternalServerError().finish());
+            }
+        };
+
+    let credentials = Credentials::generate_with_scopes(client.id, scopes);
+    let key_ring = TenantKeyRing::new(&ctx.master_keys, tenant_key.as_ref());
+    match credentials.encrypt(&key_ring).await {
+        Ok(encrypted_credentials) => {
+            match CredentialsRepository::create_credentials(
+                &ctx.database,
+                encrypted_credentials,
+                request.expires_at,
+            )
+            .await
+            {
+                Ok(_) => {
+                    tracing::info!(
+                        "Issued credentials {} for client '{}'",
+                        credentials.api_key,
+                        name
+                    );
+                    Ok(HttpResponse::Created().json(IssueCredentialsResponse {
+                        credentials,
+                        expires_at: request.expires_at,
+                    }))
+                }
+                Err(err) => {
+                    tracing::error!("Failed to store credentials: {}", err);
+                    Ok(HttpResponse::InternalServerError().finish())
+                }
+            }
+        }
+        Err(err) => {
+            tracing::error!("Failed to encrypt credentials: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+async fn issue_public_key_credentials(
+    ctx: &Context,
+    name: &str,
+    credentials: PublicKeyCredentials,
+    expires_at: Option<i64>,
+) -> actix_web::Result<HttpResponse> {
+    match CredentialsRepository::create_public_key_credentials(
+        &ctx.database,
+        &credentials,
+        expires_at,
+    )
+    .await
+    {
+        Ok(_) => {
+            tracing::info!(
+                "Issued {} credentials {} for client '{}'",
+                credentials.credential_type,
+                credentials.api_key,
+                name
+            );
+            Ok(HttpResponse::Created().json(IssueCredentialsResponse {
+                credentials,
+                expires_at,
+            }))
+        }
+        Err(err) => {
+            tracing::error!("Failed to store credentials: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn list_credentials(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+) -> actix_web::Result<HttpResponse> {
+    list_client_credentials(&ctx, &query.name).await
+}
+
+pub(crate) async fn list_client_credentials(
+    ctx: &Context,
+    name: &str,
+) -> actix_web::Result<HttpResponse> {
+    match CredentialsRepository::list_credentials(&ctx.database, ClientId::from(name)).await {
+        // Every client has credentials from its creation on
+        Ok(credentials) if credentials.is_empty() => {
+            tracing::debug!("Client not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Ok(credentials) => Ok(HttpResponse::Ok().json(
+            credentials
+                .into_iter()
+                .map(CredentialsResponse::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(err) => {
+            tracing::error!("Failed to list credentials: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn set_credentials_expiry(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    api_key: Path<Uuid>,
+    body: Json<CredentialsExpiryRequest>,
+) -> actix_web::Result<HttpResponse> {
+    set_client_credentials_expiry(&ctx, &query.name, api_key.into_inner(), body.expires_at).await
+}
+
+pub(crate) async fn set_client_credentials_expiry(
+    ctx: &Context,
+    name: &str,
+    api_key: Uuid,
+    expires_at: Option<i64>,
+) -> actix_web::Result<HttpResponse> {
+    check_expiry(expires_at)?;
+    match CredentialsRepository::set_credentials_expiry(
+        &ctx.database,
+        ClientId::from(name),
+        &Masked::from(ApiKey::from(api_key)),
+        expires_at,
+    )
+    .await
+    {
+        Ok(Some(info)) => {
+            tracing::info!(
+                "Credentials {} of client '{}' expire at {:?}",
+                info.api_key,
+                name,
+                info.expires_at
+            );
+            Ok(HttpResponse::Ok().json(CredentialsResponse::from(info)))
+        }
+        Ok(None) => {
+            tracing::debug!("Active credentials not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to set credentials expiry: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn revoke_credentials(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    api_key: Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    revoke_client_credentials(&ctx, &query.name, api_key.into_inner()).await
+}
+
+pub(crate) async fn revoke_client_credentials(
+    ctx: &Context,
+    name: &str,
+    api_key: Uuid,
+) -> actix_web::Result<HttpResponse> {
+    let api_key = Masked::from(ApiKey::from(api_key));
+    match CredentialsRepository::revoke_credentials(&ctx.database, ClientId::from(name), &api_key)
+        .await
+    {
+        Ok(true) => {
+            tracing::info!("Credentials {} of client '{}' revoked", api_key, name);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Ok(false) => {
+            tracing::debug!("Active credentials not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to revoke credentials: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Client certificate to register, or its SHA-256 fingerprint when only that is at hand.
+#[derive(Debug, Deserialize)]
+pub struct RegisterCertificateRequest {
+    /// PEM encoded X.509 certificate.
+    pub certificate: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+/// Returns `None` with the response to send if the client named `name` does not exist.
+async fn check_client_exists(ctx: &Context, name: &str) -> Option<HttpResponse> {
+    match ClientRepository::find_by_name(&ctx.database, name).await {
+        Ok(Some(_)) => None,
+        Ok(None) => {
+            tracing::debug!("Client not found");
+            Some(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to retrieve client: {}", err);
+            Some(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Registers a client certificate, which the wallet then requires of the client when it
+/// verifies client certificates.
+pub(crate) async fn register_certificate(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    body: Json<RegisterCertificateRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let fingerprint = match body.into_inner() {
+        RegisterCertificateRequest {
+            certificate: Some(certificate),
+            fingerprint: None,
+        } => CertificateFingerprint::from_pem(&certificate),
+        RegisterCertificateRequest {
+            certificate: None,
+            fingerprint: Some(fingerprint),
+        } => CertificateFingerprint::from_str(&fingerprint),
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "expected either a certificate or a fingerprint",
+            ))
+        }
+    }
+    .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))?;
+    if let Some(response) = check_client_exists(&ctx, &query.name).await {
+        return Ok(response);
+    }
+
+    match CertificateRepository::create_certificate(
+        &ctx.database,
+        ClientId::from(query.name.as_str()),
+        &fingerprint,
+    )
+    .await
+    {
+        Ok(Some(certificate)) => {
+            tracing::info!(
+                "Certificate {} registered for client '{}'",
+                fingerprint,
+                query.name
+            );
+            Ok(HttpResponse::Created().json(certificate))
+        }
+        Ok(None) => Ok(HttpResponse::Conflict().body("certificate is already registered")),
+        Err(err) => {
+            tracing::error!("Failed to store certificate: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn list_certificates(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(response) = check_client_exists(&ctx, &query.name).await {
+        return Ok(response);
+    }
+    match CertificateRepository::list_certificates(
+        &ctx.database,
+        ClientId::from(query.name.as_str()),
+    )
+    .await
+    {
+        Ok(certificates) => Ok(HttpResponse::Ok().json(certificates)),
+        Err(err) => {
+            tracing::error!("Failed to list certificates: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn delete_certificate(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    fingerprint: Path<String>,
+) -> actix_web::Result<HttpResponse> {
+    let fingerprint = CertificateFingerprint::from_str(&fingerprint)
+        .map_err(|err| actix_web::error::ErrorBadRequest(err.to_string()))?;
+    match CertificateRepository::delete_certificate(
+        &ctx.database,
+        ClientId::from(query.name.as_str()),
+        &fingerprint,
+    )
+    .await
+    {
+        Ok(true) => {
+            tracing::info!(
+                "Certificate {} of client '{}' deleted",
+                fingerprint,
+                query.name
+            );
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Ok(false) => {
+            tracing::debug!("Certificate not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to delete certificate: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Networks a client may send wallet requests from, in CIDR notation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkPolicy {
+    /// Any address is allowed when empty.
+    pub allowed_networks: Vec<IpNetwork>,
+}
+
+pub(crate) async fn get_network_policy(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(response) = check_client_exists(&ctx, &query.name).await {
+        return Ok(response);
+    }
+    match NetworkRepository::find_allowed_networks(
+        &ctx.database,
+        ClientId::from(query.name.as_str()),
+    )
+    .await
+    {
+        Ok(allowed_networks) => Ok(HttpResponse::Ok().json(NetworkPolicy { allowed_networks })),
+        Err(err) => {
+            tracing::error!("Failed to retrieve allowed networks: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Restricts the wallet requests of a client to the given networks, lifting the restriction
+/// when the list is empty.
+pub(crate) async fn set_network_policy(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    body: Json<NetworkPolicy>,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(response) = check_client_exists(&ctx, &query.name).await {
+        return Ok(response);
+    }
+    let policy = body.into_inner();
+    match NetworkRepository::set_allowed_networks(
+        &ctx.database,
+        ClientId::from(query.name.as_str()),
+        &policy.allowed_networks,
+    )
+    .await
+    {
+        Ok(()) => {
+            tracing::info!(
+                "Allowed networks of client '{}' set to {:?}",
+                query.name,
+                policy.allowed_networks
+            );
+            Ok(HttpResponse::Ok().json(policy))
+        }
+        Err(err) => {
+            tracing::error!("Failed to store allowed networks: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+pub(crate) async fn list_lockouts(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(response) = check_client_exists(&ctx, &query.name).await {
+        return Ok(response);
+    }
+    match LockoutRepository::list_lockouts(&ctx.database, ClientId::from(query.name.as_str())).await
+    {
+        Ok(lockouts) => Ok(HttpResponse::Ok().json(lockouts)),
+        Err(err) => {
+            tracing::error!("Failed to list lockouts: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Lifts the lockout of credentials before it expires, and forgets their failures.
+pub(crate) async fn unlock_credentials(
+    ctx: Data<Context>,
+    query: Query<ClientQuery>,
+    api_key: Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    let api_key = Masked::from(ApiKey::from(api_key.into_inner()));
+    match LockoutRepository::unlock(&ctx.database, ClientId::from(query.name.as_str()), &api_key)
+        .await
+    {
+        Ok(true) => {
+            tracing::info!(
+                "Credentials {} of client '{}' unlocked",
+                api_key,
+                query.name
+            );
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Ok(false) => {
+            tracing::debug!("Lockout not found");
+            Ok(HttpResponse::NotFound().finish())
+        }
+        Err(err) => {
+            tracing::error!("Failed to unlock credentials: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RewrapResponse {
+    #[serde(flatten)]
+    pub status: RewrapStatus,
+    /// Data keys not wrapped by the active master key yet.
+    pub remaining: u64,
+}
+
+async fn rewrap_response(ctx: &Context) -> actix_web::Result<RewrapResponse> {
+    let mut remaining = 0;
+    for owner in rotation::OWNERS {
+        let key_id = rotation::target_key_id(&ctx.master_keys, owner);
+        let count = match owner {
+            DataKeyOwner::TenantKeys => {
+                MasterKeyRepository::count_data_keys_to_rewrap(&ctx.key_store, owner, key_id).await
+            }
+            DataKeyOwner::Credentials | DataKeyOwner::Users => {
+                MasterKeyRepository::count_data_keys_to_rewrap(&ctx.database, owner, key_id).await
+            }
+        };
+        remaining += count.map_err(|err| {
+            tracing::error!("Failed to count data keys: {}", err);
+            actix_web::error::ErrorInternalServerError("Failed to count data keys")
+        })?;
+    }
+    Ok(RewrapResponse {
+        status: ctx.rewrap.status(),
+        remaining,
+    })
+}
+
+pub(crate) async fn start_rewrap(ctx: Data<Context>) -> actix_web::Result<HttpResponse> {
+    if !ctx.rewrap.start(ctx.master_keys.active_key_id()) {
+        tracing::debug!("Re-wrap job already running");
+        return Ok(HttpResponse::Conflict().json(rewrap_response(&ctx).await?));
+    }
+    tracing::info!(
+        "Re-wrapping data keys under master key '{}'",
+        ctx.master_keys.active_key_id()
+    );
+    actix_web::rt::spawn(rotation::rewrap_data_keys(ctx.clone()));
+    Ok(HttpResponse::Accepted().json(rewrap_response(&ctx).await?))
+}
+
+pub(crate) async fn get_rewrap(ctx: Data<Context>) -> actix_web::Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(rewrap_response(&ctx).await?))
+}
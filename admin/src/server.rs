@@ -1,33 +1,345 @@
-use crate::{context::Context, routes};
+use crate::{auth, context::Context, dashboard, routes};
 use actix_web::{
     dev::Server,
+    middleware,
     web::{self, Data},
     App, HttpResponse, HttpServer,
 };
+use kms::unseal;
 use std::net::Ipv4Addr;
 use tracing_actix_web::TracingLogger;
 
 pub fn make_server(ctx: Context) -> anyhow::Result<Server> {
     let port = ctx.config.port;
+    let tls_config = ctx
+        .config
+        .tls
+        .as_ref()
+        .map(tls::server::server_config)
+        .transpose()?;
     let data = Data::new(ctx);
 
     let server = HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
-            // TODO: add authentication middleware
+            .wrap(middleware::from_fn(unseal::reject_sealed::<Context>))
             .wrap(TracingLogger::default())
-            .service(
-                web::resource("/admin/client")
-                    .route(web::get().to(routes::get_client))
-                    .route(web::post().to(routes::create_client)),
-            )
+            .configure(services)
             .default_service(web::to(|| {
                 tracing::error!("Route not found");
                 HttpResponse::NotFound()
             }))
-    })
-    .bind((Ipv4Addr::UNSPECIFIED, port))?
+    });
+
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((Ipv4Addr::UNSPECIFIED, port), tls_config)?,
+        None => server.bind((Ipv4Addr::UNSPECIFIED, port))?,
+    }
     .run();
 
     Ok(server)
 }
+
+/// Routes of the service with their authentication middlewares.
+pub(crate) fn services(config: &mut web::ServiceConfig) {
+    config
+        .service(
+            web::scope("/admin")
+                .wrap(middleware::from_fn(auth::authenticate))
+                .service(web::resource("/login").route(web::post().to(routes::login)))
+                .service(
+                    web::resource("/operators")
+                        .route(web::get().to(routes::list_operators))
+                        .route(web::post().to(routes::create_operator)),
+                )
+                .service(
+                    web::resource("/client")
+                        .route(web::get().to(routes::get_client))
+                        .route(web::post().to(routes::create_client))
+                        .route(web::delete().to(routes::delete_client)),
+                )
+                .service(
+                    web::resource("/client/accounts")
+                        .route(web::post().to(routes::create_dashboard_account)),
+                )
+                .service(
+                    web::resource("/client/credentials")
+                        .route(web::get().to(routes::list_credentials))
+                        .route(web::post().to(routes::issue_credentials)),
+                )
+                .service(
+                    web::resource("/client/credentials/{api_key}")
+                        .route(web::patch().to(routes::set_credentials_expiry))
+                        .route(web::delete().to(routes::revoke_credentials)),
+                )
+                .service(
+                    web::resource("/client/certificates")
+                        .route(web::get().to(routes::list_certificates))
+                        .route(web::post().to(routes::register_certificate)),
+                )
+                .service(
+                    web::resource("/client/certificates/{fingerprint}")
+                        .route(web::delete().to(routes::delete_certificate)),
+                )
+                .service(
+                    web::resource("/client/networks")
+                        .route(web::get().to(routes::get_network_policy))
+                        .route(web::put().to(routes::set_network_policy)),
+                )
+                .service(
+                    web::resource("/client/lockouts").route(web::get().to(routes::list_lockouts)),
+                )
+                .service(
+                    web::resource("/client/lockouts/{api_key}")
+                        .route(web::delete().to(routes::unlock_credentials)),
+                )
+                .service(
+                    web::resource("/keys/rewrap")
+                        .route(web::get().to(routes::get_rewrap))
+                        .route(web::post().to(routes::start_rewrap)),
+                ),
+        )
+        .service(
+            web::scope("/dashboard")
+                .wrap(middleware::from_fn(dashboard::authenticate))
+                .service(web::resource("/login").route(web::post().to(dashboard::login)))
+                .service(
+                    web::resource("/password/forgot")
+                        .route(web::post().to(dashboard::forgot_password)),
+                )
+                .service(
+                    web::resource("/password/reset")
+                        .route(web::post().to(dashboard::reset_password)),
+                )
+                .service(web::resource("/client").route(web::get().to(dashboard::get_client)))
+                .service(web::resource("/users").route(web::get().to(dashboard::list_users)))
+                .service(
+                    web::resource("/credentials")
+                        .route(web::get().to(dashboard::list_credentials))
+                        .route(web::post().to(dashboard::issue_credentials)),
+                )
+                .service(
+                    web::resource("/credentials/{api_key}")
+                        .route(web::patch().to(dashboard::set_credentials_expiry))
+                        .route(web::delete().to(dashboard::revoke_credentials)),
+                ),
+        )
+        .service(
+            web::resource("/unseal")
+                .route(web::get().to(unseal::get_unseal::<Context>))
+                .route(web::post().to(unseal::unseal::<Context>)),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::Config, mail::Mailer, rotation::RewrapJob};
+    use actix_web::{http::StatusCode, test};
+    use kms::master_keys::MasterKeys;
+    use postgres_database::{KeyStore, PostgresPool};
+    use repositories::{client::ClientRepository, dashboard::DashboardRepository};
+    use sqlx::PgPool;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use types::{
+        client::Client,
+        dashboard::{DashboardAccount, ResetToken},
+        operator::{Operator, Role},
+    };
+    use uuid::Uuid;
+
+    const EMAIL: &str = "owner@example.com";
+    const PASSWORD: &str = "long enough password";
+
+    fn context(pg_pool: PgPool) -> Context {
+        let connection = serde_json::json!({
+            "user": "admin",
+            "dbname": "pontoon",
+            "port": 5432,
+            "password": "",
+            "host": "localhost",
+        });
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "rust_log": "info",
+            "port": 3000,
+            "master_key": "../.local/master_key.txt",
+            "auth": { "jwt_secret": "0123456789abcdef0123456789abcdef" },
+            "dashboard": { "reset_url": "http://localhost/reset" },
+            "mail": { "type": "log" },
+            "database": connection,
+            "key_store": connection,
+        }))
+        .expect("config");
+        Context {
+            master_keys: MasterKeys::from_config(None, None, config.master_key.as_deref())
+                .expect("master keys"),
+            mailer: Mailer::new(&config.mail).expect("mailer"),
+            config,
+            database: PostgresPool {
+                pg_pool: pg_pool.clone(),
+            },
+            key_store: KeyStore { pg_pool },
+            rewrap: RewrapJob::default(),
+        }
+    }
+
+    /// Context whose databases cannot be reached, for requests rejected before any query.
+    fn unreachable_context() -> Context {
+        context(PgPool::connect_lazy("postgres://localhost:1/unused").expect("pool"))
+    }
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time")
+            .as_secs() as i64
+    }
+
+    /// Creates a client with a dashboard account of `EMAIL` and `PASSWORD`, returning its id.
+    async fn dashboard_account(ctx: &Context) -> Uuid {
+        let client = Client::new("client".to_string());
+        let encrypted_client = client
+            .encrypt(&ctx.master_keys)
+            .await
+            .expect("encrypt client");
+        ClientRepository::create(&ctx.database, encrypted_client)
+            .await
+            .expect("create client");
+        let account =
+            DashboardAccount::new(client.id().clone(), EMAIL, PASSWORD).expect("new account");
+        let account_id = account.id;
+        assert!(DashboardRepository::create_account(&ctx.database, account)
+            .await
+            .expect("create account"));
+        account_id
+    }
+
+    fn post(path: &str, body: serde_json::Value) -> test::TestRequest {
+        test::TestRequest::post().uri(path).set_json(body)
+    }
+
+    #[actix_web::test]
+    async fn test_viewer_cannot_create_client() {
+        let ctx = unreachable_context();
+        let viewer = Operator::new("viewer".to_string(), "long enough password", Role::Viewer)
+            .expect("new operator");
+        let token = auth::issue_token(&ctx.config.auth, &viewer).expect("issue token");
+        let app = test::init_service(App::new().app_data(Data::new(ctx)).configure(services)).await;
+
+        let request = test::TestRequest::post()
+            .uri("/admin/client")
+            .insert_header(("authorization", format!("Bearer {token}")))
+            .set_json(serde_json::json!({ "name": "client" }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_dashboard_login(pg_pool: PgPool) {
+        let ctx = context(pg_pool);
+        dashboard_account(&ctx).await;
+        let app = test::init_service(App::new().app_data(Data::new(ctx)).configure(services)).await;
+
+        let request = post(
+            "/dashboard/login",
+            serde_json::json!({ "email": EMAIL, "password": PASSWORD }),
+        );
+        let response: serde_json::Value =
+            test::call_and_read_body_json(&app, request.to_request()).await;
+        let token = response["access_token"].as_str().expect("access token");
+
+        let request = test::TestRequest::get()
+            .uri("/dashboard/client")
+            .insert_header(("authorization", format!("Bearer {token}")))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, request).await.status(),
+            StatusCode::OK
+        );
+
+        let request = post(
+            "/dashboard/login",
+            serde_json::json!({ "email": EMAIL, "password": "wrong enough password" }),
+        );
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_forgot_password_of_unknown_email_is_accepted(pg_pool: PgPool) {
+        let ctx = context(pg_pool.clone());
+        let account_id = dashboard_account(&ctx).await;
+        let app = test::init_service(App::new().app_data(Data::new(ctx)).configure(services)).await;
+
+        // Reset mails are sent from a local task
+        let local = tokio::task::LocalSet::new();
+        for email in [EMAIL, "unknown@example.com"] {
+            let request = post(
+                "/dashboard/password/forgot",
+                serde_json::json!({ "email": email }),
+            );
+            let response = local
+                .run_until(test::call_service(&app, request.to_request()))
+                .await;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+        local.await;
+
+        let resets: Vec<(Uuid,)> = sqlx::query_as("SELECT account_id FROM password_resets")
+            .fetch_all(&pg_pool)
+            .await
+            .expect("password resets");
+        assert_eq!(resets, vec![(account_id,)]);
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_reset_token_expires_and_is_used_once(pg_pool: PgPool) {
+        let ctx = context(pg_pool);
+        let account_id = dashboard_account(&ctx).await;
+        let expired = ResetToken::generate();
+        DashboardRepository::create_password_reset(
+            &ctx.database,
+            account_id,
+            &expired.hash,
+            now() - 1,
+        )
+        .await
+        .expect("create expired reset");
+        let valid = ResetToken::generate();
+        DashboardRepository::create_password_reset(
+            &ctx.database,
+            account_id,
+            &valid.hash,
+            now() + 3600,
+        )
+        .await
+        .expect("create reset");
+        let app = test::init_service(App::new().app_data(Data::new(ctx)).configure(services)).await;
+        let new_password = "another long enough password";
+        let reset = |token: &ResetToken| {
+            post(
+                "/dashboard/password/reset",
+                serde_json::json!({ "token": token.token.expose(), "password": new_password }),
+            )
+            .to_request()
+        };
+
+        let response = test::call_service(&app, reset(&expired)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = test::call_service(&app, reset(&valid)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test::call_service(&app, reset(&valid)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = post(
+            "/dashboard/login",
+            serde_json::json!({ "email": EMAIL, "password": new_password }),
+        );
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,72 @@
+use secrecy::{ExposeSecret, SecretBox};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Master keys by identifier, e.g. `MASTER_KEYS__KEYS__V2__TYPE=transit` and `MASTER_KEYS__ACTIVE=v2`.
+///
+/// Identifiers are read from variable names and hence lowercase.
+#[derive(Debug, Deserialize)]
+pub struct MasterKeysConfig {
+    /// Identifier of the key wrapping new data keys, the others only unwrap.
+    pub active: String,
+    pub keys: HashMap<String, KeyProviderConfig>,
+}
+
+/// Selects the key encryption provider wrapping data keys, e.g. `KEY_PROVIDER__TYPE=transit`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum KeyProviderConfig {
+    /// Master key read from a local file.
+    File { path: String },
+    /// AES key held by a PKCS#11 token.
+    Pkcs11(Pkcs11Config),
+    /// Vault transit compatible HTTP KMS.
+    Transit(TransitConfig),
+    /// Master key split into Shamir shares, sealed until operators submit enough of them.
+    Shamir(ShamirConfig),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Pkcs11Config {
+    /// Path to the PKCS#11 module, e.g. `/usr/lib/softhsm/libsofthsm2.so`.
+    pub module: String,
+    pub token_label: String,
+    pub pin: SecretBox<String>,
+    /// Label of the AES key used to wrap data keys.
+    pub key_label: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransitConfig {
+    /// Base address of the KMS, e.g. `http://127.0.0.1:8200`.
+    pub address: String,
+    pub key_name: String,
+    pub token: SecretBox<String>,
+    /// Mount path of the transit engine.
+    #[serde(default = "default_mount")]
+    pub mount: String,
+}
+
+fn default_mount() -> String {
+    "transit".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShamirConfig {
+    /// Key check value printed by the key ceremony, identifying the reconstructed key.
+    pub key_check: String,
+}
+
+/// Authenticates operators submitting key shares, e.g. `UNSEAL__TOKEN`.
+#[derive(Debug, Deserialize)]
+pub struct UnsealConfig {
+    pub token: SecretBox<String>,
+}
+
+impl UnsealConfig {
+    /// Compares digests, so that the comparison time does not depend on the token.
+    pub fn is_authorized(&self, token: &str) -> bool {
+        Sha256::digest(token.as_bytes()) == Sha256::digest(self.token.expose_secret().as_bytes())
+    }
+}
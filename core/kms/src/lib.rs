@@ -0,0 +1,91 @@
+//! Key encryption providers wrapping the data keys that encrypt secrets at rest.
+
+pub mod config;
+pub mod master_keys;
+pub mod pkcs11;
+pub mod shamir;
+pub mod transit;
+#[cfg(feature = "actix")]
+pub mod unseal;
+
+use crate::{
+    config::KeyProviderConfig, pkcs11::Pkcs11Provider, shamir::ShamirProvider,
+    transit::TransitProvider,
+};
+use types::{
+    encrypt::{master_key::MasterKey, provider::KeyEncryptionProvider, Aes256Key, Encrypted},
+    error::Error,
+};
+
+pub enum KeyProvider {
+    File(MasterKey),
+    Pkcs11(Box<Pkcs11Provider>),
+    Transit(TransitProvider),
+    Shamir(ShamirProvider),
+}
+
+impl KeyProvider {
+    pub fn new(config: &KeyProviderConfig) -> Result<Self, Error> {
+        Ok(match config {
+            KeyProviderConfig::File { path } => {
+                KeyProvider::File(MasterKey::from_file(path.clone())?)
+            }
+            KeyProviderConfig::Pkcs11(config) => {
+                KeyProvider::Pkcs11(Box::new(Pkcs11Provider::new(config)?))
+            }
+            KeyProviderConfig::Transit(config) => {
+                KeyProvider::Transit(TransitProvider::new(config)?)
+            }
+            KeyProviderConfig::Shamir(config) => KeyProvider::Shamir(ShamirProvider::new(config)),
+        })
+    }
+}
+
+impl KeyEncryptionProvider for KeyProvider {
+    async fn wrap(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        match self {
+            KeyProvider::File(provider) => provider.wrap(data_key).await,
+            KeyProvider::Pkcs11(provider) => provider.wrap(data_key).await,
+            KeyProvider::Transit(provider) => provider.wrap(data_key).await,
+            KeyProvider::Shamir(provider) => provider.wrap(data_key).await,
+        }
+    }
+
+    async fn unwrap(&self, wrapped_key: &Encrypted) -> Result<Aes256Key, Error> {
+        match self {
+            KeyProvider::File(provider) => provider.unwrap(wrapped_key).await,
+            KeyProvider::Pkcs11(provider) => provider.unwrap(wrapped_key).await,
+            KeyProvider::Transit(provider) => provider.unwrap(wrapped_key).await,
+            KeyProvider::Shamir(provider) => provider.unwrap(wrapped_key).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyProviderConfig;
+
+    #[test]
+    fn test_key_provider_config() {
+        let config: KeyProviderConfig = serde_json::from_str(
+            r#"{"type": "transit", "address": "http://127.0.0.1:8200", "key_name": "pontoon", "token": "s.token"}"#,
+        )
+        .expect("transit config");
+        assert!(
+            matches!(config, KeyProviderConfig::Transit(ref transit) if transit.mount == "transit")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_provider_reads_existing_keys() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let data_key = Aes256Key::generate();
+        // Data keys wrapped before providers existed.
+        let wrapped = master_key.encrypt(&data_key.to_string()).expect("wrap");
+
+        let provider = KeyProvider::File(master_key);
+        let unwrapped = provider.unwrap(&wrapped).await.expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{
+    config::{KeyProviderConfig, MasterKeysConfig},
+    shamir::UnsealStatus,
+    KeyProvider,
+};
+use std::collections::{BTreeMap, HashMap};
+use types::{
+    encrypt::{
+        provider::{KeyEncryptionProvider, KeyRing, DEFAULT_KEY_ID},
+        Aes256Key, Encrypted,
+    },
+    error::Error,
+    tenant::TENANT_KEY_ID,
+};
+
+/// Master keys loaded by identifier, one of them active.
+pub struct MasterKeys {
+    active: String,
+    keys: HashMap<String, KeyProvider>,
+}
+
+impl MasterKeys {
+    pub fn new(active: String, keys: HashMap<String, KeyProvider>) -> Result<Self, Error> {
+        if let Some(key_id) = keys.keys().find(|key_id| !is_valid_key_id(key_id)) {
+            return Err(Error::KeyProvider(format!(
+                "invalid master key id '{key_id}', use letters, digits, '-' and '_'"
+            )));
+        }
+        if keys.contains_key(TENANT_KEY_ID) {
+            return Err(Error::KeyProvider(format!(
+                "master key id '{TENANT_KEY_ID}' is reserved for tenant keys"
+            )));
+        }
+        if !keys.contains_key(&active) {
+            return Err(Error::UnknownMasterKey(active));
+        }
+        Ok(Self { active, keys })
+    }
+
+    /// Reads `MASTER_KEYS`, then falls back to a single `KEY_PROVIDER` or `MASTER_KEY` file,
+    /// identified by [`DEFAULT_KEY_ID`].
+    pub fn from_config(
+        master_keys: Option<&MasterKeysConfig>,
+        key_provider: Option<&KeyProviderConfig>,
+        master_key: Option<&str>,
+    ) -> Result<Self, Error> {
+        let single = |provider: KeyProvider| {
+            Self::new(
+                DEFAULT_KEY_ID.to_string(),
+                HashMap::from([(DEFAULT_KEY_ID.to_string(), provider)]),
+            )
+        };
+        match (master_keys, key_provider, master_key) {
+            (Some(config), _, _) => {
+                if !config.keys.contains_key(&config.active) {
+                    return Err(Error::UnknownMasterKey(config.active.clone()));
+                }
+                let keys = config
+                    .keys
+                    .iter()
+                    .map(|(key_id, config)| Ok((key_id.clone(), KeyProvider::new(config)?)))
+                    .collect::<Result<_, Error>>()?;
+                Self::new(config.active.clone(), keys)
+            }
+            (None, Some(config), _) => single(KeyProvider::new(config)?),
+            (None, None, Some(path)) => single(KeyProvider::new(&KeyProviderConfig::File {
+                path: path.to_string(),
+            })?),
+            (None, None, None) => Err(Error::KeyProvider(
+                "set MASTER_KEYS, KEY_PROVIDER__TYPE or MASTER_KEY".to_string(),
+            )),
+        }
+    }
+
+    /// Whether a master key split into shares is still sealed.
+    pub fn is_sealed(&self) -> bool {
+        self.keys
+            .values()
+            .any(|key| matches!(key, KeyProvider::Shamir(provider) if provider.is_sealed()))
+    }
+
+    /// Unseal progress of the master keys split into shares, by identifier.
+    pub fn unseal_status(&self) -> BTreeMap<String, UnsealStatus> {
+        self.keys
+            .iter()
+            .filter_map(|(key_id, key)| match key {
+                KeyProvider::Shamir(provider) => Some((key_id.clone(), provider.status())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Master key shares are submitted for when none is named: the only sealed key, if a single
+    /// one is, otherwise the active key.
+    pub fn unseal_key_id(&self) -> &str {
+        let mut sealed = self.keys.iter().filter_map(|(key_id, key)| match key {
+            KeyProvider::Shamir(provider) if provider.is_sealed() => Some(key_id.as_str()),
+            _ => None,
+        });
+        match (sealed.next(), sealed.next()) {
+            (Some(key_id), None) => key_id,
+            _ => &self.active,
+        }
+    }
+
+    /// Submits a share of the master key `key_id`.
+    pub fn unseal(&self, key_id: &str, share: &str) -> Result<UnsealStatus, Error> {
+        match self.keys.get(key_id) {
+            Some(KeyProvider::Shamir(provider)) => provider.unseal(share),
+            _ => Err(Error::InvalidKeyShare(format!(
+                "master key '{key_id}' is not split into shares"
+            ))),
+        }
+    }
+
+    fn key(&self, key_id: &str) -> Result<&KeyProvider, Error> {
+        self.keys
+            .get(key_id)
+            .ok_or_else(|| Error::UnknownMasterKey(key_id.to_string()))
+    }
+}
+
+/// Key ids are stored in ciphertext envelopes, delimited by `:`.
+fn is_valid_key_id(key_id: &str) -> bool {
+    !key_id.is_empty()
+        && key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl KeyRing for MasterKeys {
+    fn active_key_id(&self) -> &str {
+        &self.active
+    }
+
+    async fn wrap_data_key(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        let wrapped_key = self.key(&self.active)?.wrap(data_key).await?;
+        Ok(wrapped_key.with_key_id(&self.active))
+    }
+
+    async fn unwrap_data_key(
+        &self,
+        key_id: &str,
+        wrapped_key: &Encrypted,
+    ) -> Result<Aes256Key, Error> {
+        self.key(key_id)?
+            .unwrap(&wrapped_key.for_key(key_id)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ShamirConfig,
+        shamir::{self, ShamirProvider},
+    };
+    use types::encrypt::master_key::MasterKey;
+
+    fn file_key() -> KeyProvider {
+        KeyProvider::File(MasterKey::from(Aes256Key::generate()))
+    }
+
+    #[tokio::test]
+    async fn test_rotated_keys_unwrap() {
+        let data_key = Aes256Key::generate();
+        let keys = MasterKeys::new(
+            "v1".to_string(),
+            HashMap::from([("v1".to_string(), file_key())]),
+        )
+        .expect("master keys");
+        let wrapped = keys.wrap_data_key(&data_key).await.expect("wrap");
+
+        // v2 becomes active, v1 is kept to unwrap existing data keys.
+        let v1 = keys.keys.into_values().next().expect("v1");
+        let keys = MasterKeys::new(
+            "v2".to_string(),
+            HashMap::from([("v1".to_string(), v1), ("v2".to_string(), file_key())]),
+        )
+        .expect("master keys");
+        assert_eq!(keys.active_key_id(), "v2");
+
+        let unwrapped = keys.unwrap_data_key("v1", &wrapped).await.expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+        assert!(keys.unwrap_data_key("v2", &wrapped).await.is_err());
+        assert_eq!(
+            keys.unwrap_data_key("v3", &wrapped).await.err(),
+            Some(Error::UnknownMasterKey("v3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_master_keys_config() {
+        let config: MasterKeysConfig = serde_json::from_str(
+            r#"{"active": "v3", "keys": {"v2": {"type": "file", "path": "master_key.txt"}}}"#,
+        )
+        .expect("master keys config");
+        assert_eq!(
+            MasterKeys::from_config(Some(&config), None, None).err(),
+            Some(Error::UnknownMasterKey("v3".to_string()))
+        );
+        assert!(matches!(
+            MasterKeys::from_config(None, None, None),
+            Err(Error::KeyProvider(_))
+        ));
+        assert!(matches!(
+            MasterKeys::new(
+                "tenant".to_string(),
+                HashMap::from([("tenant".to_string(), file_key())])
+            ),
+            Err(Error::KeyProvider(_))
+        ));
+    }
+
+    #[test]
+    fn test_unseal_key_id() {
+        let key = Aes256Key::generate();
+        let shares = shamir::split(&key, 3, 2).expect("split").shares;
+        let shamir_key = KeyProvider::Shamir(ShamirProvider::new(&ShamirConfig {
+            key_check: shamir::key_check(&key),
+        }));
+        let keys = MasterKeys::new(
+            "v1".to_string(),
+            HashMap::from([
+                ("v1".to_string(), file_key()),
+                ("v2".to_string(), shamir_key),
+            ]),
+        )
+        .expect("master keys");
+        assert!(keys.is_sealed());
+        assert_eq!(keys.unseal_key_id(), "v2");
+        assert!(matches!(
+            keys.unseal("v1", &shares[0]),
+            Err(Error::InvalidKeyShare(_))
+        ));
+
+        keys.unseal("v2", &shares[0]).expect("unseal");
+        keys.unseal("v2", &shares[2]).expect("unseal");
+        assert!(!keys.is_sealed());
+        assert_eq!(keys.unseal_key_id(), "v1");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::config::Pkcs11Config;
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::{aead::GcmParams, Mechanism},
+    object::{Attribute, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use rand::{rngs::OsRng, RngCore};
+use secrecy::ExposeSecret;
+use std::sync::Mutex;
+use types::{
+    encrypt::{provider::KeyEncryptionProvider, Aes256Key, Algorithm, Encrypted},
+    error::Error,
+};
+use zeroize::Zeroizing;
+
+const IV_LEN: usize = 12;
+const TAG_BITS: u64 = 128;
+
+/// Wraps data keys with an AES key that never leaves the token, using CKM_AES_GCM.
+pub struct Pkcs11Provider {
+    session: Mutex<Session>,
+    key: ObjectHandle,
+}
+
+impl Pkcs11Provider {
+    pub fn new(config: &Pkcs11Config) -> Result<Self, Error> {
+        let pkcs11 = Pkcs11::new(&config.module).map_err(pkcs11_error)?;
+        pkcs11
+            .initialize(CInitializeArgs::OsThreads)
+            .map_err(pkcs11_error)?;
+
+        let slot = pkcs11
+            .get_slots_with_token()
+            .map_err(pkcs11_error)?
+            .into_iter()
+            .find(|slot| {
+                pkcs11
+                    .get_token_info(*slot)
+                    .is_ok_and(|info| info.label() == config.token_label)
+            })
+            .ok_or_else(|| {
+                Error::KeyProvider(format!("token '{}' not found", config.token_label))
+            })?;
+
+        let session = pkcs11.open_rw_session(slot).map_err(pkcs11_error)?;
+        let pin = AuthPin::new(config.pin.expose_secret().clone());
+        session
+            .login(UserType::User, Some(&pin))
+            .map_err(pkcs11_error)?;
+
+        let key = session
+            .find_objects(&[
+                Attribute::Class(ObjectClass::SECRET_KEY),
+                Attribute::Label(config.key_label.as_bytes().to_vec()),
+            ])
+            .map_err(pkcs11_error)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::KeyProvider(format!("key '{}' not found", config.key_label)))?;
+
+        Ok(Self {
+            session: Mutex::new(session),
+            key,
+        })
+    }
+
+    fn session(&self) -> Result<std::sync::MutexGuard<'_, Session>, Error> {
+        self.session
+            .lock()
+            .map_err(|_| Error::KeyProvider("PKCS#11 session poisoned".to_string()))
+    }
+}
+
+impl KeyEncryptionProvider for Pkcs11Provider {
+    async fn wrap(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let nonce = iv.to_vec();
+
+        let params = GcmParams::new(&mut iv, &[], TAG_BITS.into()).map_err(pkcs11_error)?;
+        let ciphertext = self
+            .session()?
+            .encrypt(
+                &Mechanism::AesGcm(params),
+                self.key,
+                data_key.expose_secret(),
+            )
+            .map_err(pkcs11_error)?;
+
+        Ok(Encrypted::new(Algorithm::Aes256Gcm, nonce, ciphertext))
+    }
+
+    async fn unwrap(&self, wrapped_key: &Encrypted) -> Result<Aes256Key, Error> {
+        if wrapped_key.algorithm != Algorithm::Aes256Gcm {
+            return Err(Error::MalformedCiphertext(format!(
+                "expected {}, got {}",
+                Algorithm::Aes256Gcm,
+                wrapped_key.algorithm
+            )));
+        }
+        let mut iv = wrapped_key.nonce.clone();
+        let params = GcmParams::new(&mut iv, &[], TAG_BITS.into()).map_err(pkcs11_error)?;
+        let plaintext = Zeroizing::new(
+            self.session()?
+                .decrypt(
+                    &Mechanism::AesGcm(params),
+                    self.key,
+                    &wrapped_key.ciphertext,
+                )
+                .map_err(pkcs11_error)?,
+        );
+
+        Aes256Key::try_from(plaintext.as_slice())
+    }
+}
+
+fn pkcs11_error(err: impl std::fmt::Display) -> Error {
+    Error::KeyProvider(format!("PKCS#11: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cryptoki::object::KeyType;
+    use secrecy::SecretBox;
+
+    /// Expects a SoftHSM token, e.g.
+    /// `softhsm2-util --init-token --free --label pontoon --pin 1234 --so-pin 1234`.
+    fn config() -> Pkcs11Config {
+        Pkcs11Config {
+            module: std::env::var("PKCS11_MODULE")
+                .unwrap_or_else(|_| "/usr/lib/softhsm/libsofthsm2.so".to_string()),
+            token_label: std::env::var("PKCS11_TOKEN_LABEL").unwrap_or("pontoon".to_string()),
+            pin: SecretBox::new(Box::new(
+                std::env::var("PKCS11_PIN").unwrap_or("1234".to_string()),
+            )),
+            key_label: "pontoon-test-kek".to_string(),
+        }
+    }
+
+    fn generate_key(config: &Pkcs11Config) {
+        let pkcs11 = Pkcs11::new(&config.module).expect("load module");
+        pkcs11
+            .initialize(CInitializeArgs::OsThreads)
+            .expect("initialize");
+        let slot = pkcs11
+            .get_slots_with_token()
+            .expect("slots")
+            .into_iter()
+            .find(|slot| {
+                pkcs11
+                    .get_token_info(*slot)
+                    .is_ok_and(|info| info.label() == config.token_label)
+            })
+            .expect("token");
+        let session = pkcs11.open_rw_session(slot).expect("session");
+        let pin = AuthPin::new(config.pin.expose_secret().clone());
+        session.login(UserType::User, Some(&pin)).expect("login");
+        let label = Attribute::Label(config.key_label.as_bytes().to_vec());
+        if !session.find_objects(&[label]).expect("find key").is_empty() {
+            return;
+        }
+        session
+            .generate_key(
+                &Mechanism::AesKeyGen,
+                &[
+                    Attribute::Class(ObjectClass::SECRET_KEY),
+                    Attribute::KeyType(KeyType::AES),
+                    Attribute::ValueLen(32.into()),
+                    Attribute::Label(config.key_label.as_bytes().to_vec()),
+                    Attribute::Token(true),
+                    Attribute::Encrypt(true),
+                    Attribute::Decrypt(true),
+                ],
+            )
+            .expect("generate key");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires SoftHSM"]
+    async fn test_pkcs11_wrap_unwrap() {
+        let config = config();
+        generate_key(&config);
+        let provider = Pkcs11Provider::new(&config).expect("provider");
+
+        let data_key = Aes256Key::generate();
+        let wrapped = provider.wrap(&data_key).await.expect("wrap");
+        assert_eq!(wrapped.nonce.len(), IV_LEN);
+
+        let unwrapped = provider.unwrap(&wrapped).await.expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::config::ShamirConfig;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sharks::{Share, Sharks};
+use std::sync::{Arc, Mutex};
+use types::{
+    encrypt::{master_key::MasterKey, provider::KeyEncryptionProvider, Aes256Key, Encrypted},
+    error::Error,
+};
+use zeroize::Zeroizing;
+
+/// Message authenticated by the key check value, which identifies a key without revealing it.
+const KEY_CHECK_MESSAGE: &[u8] = b"pontoon master key check";
+
+/// Length of a decoded share: threshold, x coordinate and one byte per key byte.
+const SHARE_LEN: usize = 2 + 32;
+
+/// Key check value of `key`, compared once the key is reconstructed from shares.
+pub fn key_check(key: &Aes256Key) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.expose_secret()).expect("HMAC takes keys of any size");
+    mac.update(KEY_CHECK_MESSAGE);
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+/// Shares of a master key, handed out to operators during the key ceremony.
+pub struct KeyShares {
+    pub shares: Vec<Zeroizing<String>>,
+    pub key_check: String,
+}
+
+/// Splits `key` into `shares` shares, any `threshold` of which reconstruct it.
+///
+/// Shares are URL safe base64 encoded and carry the threshold.
+pub fn split(key: &Aes256Key, shares: u8, threshold: u8) -> Result<KeyShares, Error> {
+    if threshold < 2 || threshold > shares {
+        return Err(Error::InvalidKeyShare(format!(
+            "threshold must be between 2 and the number of shares, got {threshold} of {shares}"
+        )));
+    }
+    let shares = Sharks(threshold)
+        .dealer(key.expose_secret())
+        .take(shares.into())
+        .map(|share| {
+            let mut bytes = Zeroizing::new(vec![threshold]);
+            bytes.extend(Vec::from(&share));
+            Zeroizing::new(URL_SAFE_NO_PAD.encode(bytes.as_slice()))
+        })
+        .collect();
+    Ok(KeyShares {
+        shares,
+        key_check: key_check(key),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnsealStatus {
+    pub sealed: bool,
+    /// Shares needed, known once a first share is submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<u8>,
+    /// Distinct shares submitted so far.
+    pub progress: usize,
+}
+
+/// Master key split into Shamir shares, reconstructed in memory once enough shares are submitted.
+///
+/// Wrapping and unwrapping fail with [`Error::Sealed`] until then.
+pub struct ShamirProvider {
+    key_check: String,
+    state: Mutex<UnsealState>,
+}
+
+#[derive(Default)]
+struct UnsealState {
+    threshold: Option<u8>,
+    shares: Vec<Share>,
+    key: Option<Arc<MasterKey>>,
+}
+
+impl UnsealState {
+    fn status(&self) -> UnsealStatus {
+        UnsealStatus {
+            sealed: self.key.is_none(),
+            threshold: self.threshold,
+            progress: self.shares.len(),
+        }
+    }
+}
+
+impl ShamirProvider {
+    pub fn new(config: &ShamirConfig) -> Self {
+        ShamirProvider {
+            key_check: config.key_check.clone(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.status().sealed
+    }
+
+    pub fn status(&self) -> UnsealStatus {
+        self.state.lock().expect("unseal state").status()
+    }
+
+    /// Adds a share, unsealing the key once enough shares are submitted.
+    ///
+    /// Shares reconstructing another key are all discarded, so that operators start over.
+    pub fn unseal(&self, share: &str) -> Result<UnsealStatus, Error> {
+        let mut state = self.state.lock().expect("unseal state");
+        if state.key.is_some() {
+            return Ok(state.status());
+        }
+
+        let bytes = Zeroizing::new(
+            URL_SAFE_NO_PAD
+                .decode(share.trim())
+                .map_err(|_| Error::InvalidKeyShare("not URL safe base64".to_string()))?,
+        );
+        let (threshold, share) = match bytes.split_first() {
+            Some((&threshold, share)) if bytes.len() == SHARE_LEN && threshold >= 2 => (
+                threshold,
+                Share::try_from(share).map_err(|err| Error::InvalidKeyShare(err.to_string()))?,
+            ),
+            _ => return Err(Error::InvalidKeyShare("malformed share".to_string())),
+        };
+        if state
+            .threshold
+            .is_some_and(|expected| expected != threshold)
+        {
+            return Err(Error::InvalidKeyShare(
+                "threshold differs from the submitted shares".to_string(),
+            ));
+        }
+        // Submitting the same share twice does not bring the key closer
+        if state
+            .shares
+            .iter()
+            .all(|submitted| submitted.x.0 != share.x.0)
+        {
+            state.threshold = Some(threshold);
+            state.shares.push(share);
+        }
+        if state.shares.len() < usize::from(threshold) {
+            return Ok(state.status());
+        }
+
+        let secret = Zeroizing::new(
+            Sharks(threshold)
+                .recover(&state.shares)
+                .map_err(|err| Error::InvalidKeyShare(err.to_string()))?,
+        );
+        let key = Aes256Key::try_from(secret.as_slice())?;
+        if key_check(&key) != self.key_check {
+            *state = UnsealState::default();
+            return Err(Error::InvalidKeyShare(
+                "shares do not reconstruct the master key, submit them again".to_string(),
+            ));
+        }
+        *state = UnsealState {
+            threshold: Some(threshold),
+            shares: Vec::new(),
+            key: Some(Arc::new(MasterKey::from(key))),
+        };
+        Ok(state.status())
+    }
+
+    fn key(&self) -> Result<Arc<MasterKey>, Error> {
+        let state = self.state.lock().expect("unseal state");
+        state.key.clone().ok_or(Error::Sealed)
+    }
+}
+
+impl KeyEncryptionProvider for ShamirProvider {
+    async fn wrap(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        let key = self.key()?;
+        key.wrap(data_key).await
+    }
+
+    async fn unwrap(&self, wrapped_key: &Encrypted) -> Result<Aes256Key, Error> {
+        let key = self.key()?;
+        key.unwrap(wrapped_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(key: &Aes256Key) -> ShamirProvider {
+        ShamirProvider::new(&ShamirConfig {
+            key_check: key_check(key),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_unseal_with_threshold_shares() {
+        let key = Aes256Key::generate();
+        let key_shares = split(&key, 5, 3).expect("split");
+        assert_eq!(key_shares.shares.len(), 5);
+
+        let provider = provider(&key);
+        let data_key = Aes256Key::generate();
+        assert_eq!(provider.wrap(&data_key).await.err(), Some(Error::Sealed));
+
+        let status = provider.unseal(&key_shares.shares[4]).expect("unseal");
+        assert_eq!(
+            status,
+            UnsealStatus {
+                sealed: true,
+                threshold: Some(3),
+                progress: 1
+            }
+        );
+        // Duplicates are not counted
+        let status = provider.unseal(&key_shares.shares[4]).expect("unseal");
+        assert_eq!(status.progress, 1);
+        provider.unseal(&key_shares.shares[1]).expect("unseal");
+        assert!(provider.is_sealed());
+        let status = provider.unseal(&key_shares.shares[2]).expect("unseal");
+        assert!(!status.sealed);
+
+        // The reconstructed key unwraps keys wrapped by the original one
+        let wrapped =
+            MasterKey::from(Aes256Key::try_from(key.expose_secret().as_slice()).expect("key"))
+                .wrap(&data_key)
+                .await
+                .expect("wrap");
+        let unwrapped = provider.unwrap(&wrapped).await.expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+    }
+
+    #[test]
+    fn test_unseal_rejects_shares_of_another_key() {
+        let key = Aes256Key::generate();
+        let shares = split(&key, 3, 2).expect("split").shares;
+        let other_shares = split(&Aes256Key::generate(), 3, 2).expect("split").shares;
+
+        let provider = provider(&key);
+        provider.unseal(&shares[0]).expect("unseal");
+        assert!(matches!(
+            provider.unseal(&other_shares[1]),
+            Err(Error::InvalidKeyShare(_))
+        ));
+        // Submitted shares are discarded
+        assert_eq!(provider.status().progress, 0);
+
+        provider.unseal(&shares[1]).expect("unseal");
+        let status = provider.unseal(&shares[2]).expect("unseal");
+        assert!(!status.sealed);
+    }
+
+    #[test]
+    fn test_invalid_shares() {
+        let key = Aes256Key::generate();
+        assert!(matches!(split(&key, 3, 4), Err(Error::InvalidKeyShare(_))));
+        assert!(matches!(split(&key, 3, 1), Err(Error::InvalidKeyShare(_))));
+
+        let provider = provider(&key);
+        assert!(matches!(
+            provider.unseal("not a share!"),
+            Err(Error::InvalidKeyShare(_))
+        ));
+        assert!(matches!(
+            provider.unseal(&URL_SAFE_NO_PAD.encode([3u8; 8])),
+            Err(Error::InvalidKeyShare(_))
+        ));
+
+        provider
+            .unseal(&split(&key, 3, 2).expect("split").shares[0])
+            .expect("unseal");
+        assert!(matches!(
+            provider.unseal(&split(&key, 3, 3).expect("split").shares[0]),
+            Err(Error::InvalidKeyShare(_))
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::TransitConfig;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{Client, StatusCode};
+use secrecy::{ExposeSecret, SecretBox};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+use types::{
+    encrypt::{provider::KeyEncryptionProvider, Aes256Key, Algorithm, Encrypted, EnvelopeVersion},
+    error::Error,
+};
+use zeroize::Zeroizing;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wraps data keys with a Vault transit compatible HTTP KMS.
+///
+/// The KMS ciphertext (`vault:v1:...`) is stored as is; it carries its own nonce and key version.
+pub struct TransitProvider {
+    client: Client,
+    encrypt_url: String,
+    decrypt_url: String,
+    token: SecretBox<String>,
+}
+
+#[derive(Serialize)]
+struct EncryptRequest<'a> {
+    plaintext: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EncryptResponse {
+    ciphertext: String,
+}
+
+#[derive(Serialize)]
+struct DecryptRequest<'a> {
+    ciphertext: &'a str,
+}
+
+#[derive(Deserialize)]
+struct DecryptResponse {
+    plaintext: String,
+}
+
+#[derive(Deserialize)]
+struct TransitResponse<T> {
+    data: T,
+}
+
+impl TransitProvider {
+    pub fn new(config: &TransitConfig) -> Result<Self, Error> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(transit_error)?;
+        let base = format!(
+            "{}/v1/{}",
+            config.address.trim_end_matches('/'),
+            config.mount.trim_matches('/')
+        );
+
+        Ok(Self {
+            client,
+            encrypt_url: format!("{base}/encrypt/{}", config.key_name),
+            decrypt_url: format!("{base}/decrypt/{}", config.key_name),
+            token: SecretBox::new(Box::new(config.token.expose_secret().clone())),
+        })
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &impl Serialize,
+    ) -> Result<T, Error> {
+        let response = self
+            .client
+            .post(url)
+            .header("X-Vault-Token", self.token.expose_secret())
+            .json(body)
+            .send()
+            .await
+            .map_err(transit_error)?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let response: TransitResponse<T> = response.json().await.map_err(transit_error)?;
+                Ok(response.data)
+            }
+            status => Err(Error::KeyProvider(format!("transit: status {status}"))),
+        }
+    }
+}
+
+impl KeyEncryptionProvider for TransitProvider {
+    async fn wrap(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        let plaintext = Zeroizing::new(STANDARD.encode(data_key.expose_secret()));
+        let response: EncryptResponse = self
+            .post(
+                &self.encrypt_url,
+                &EncryptRequest {
+                    plaintext: &plaintext,
+                },
+            )
+            .await?;
+
+        Ok(Encrypted::new(
+            Algorithm::Transit,
+            Vec::new(),
+            response.ciphertext.into_bytes(),
+        ))
+    }
+
+    async fn unwrap(&self, wrapped_key: &Encrypted) -> Result<Aes256Key, Error> {
+        // Keys wrapped before envelopes were versioned only have an empty nonce.
+        let legacy = wrapped_key.version == EnvelopeVersion::Legacy && wrapped_key.nonce.is_empty();
+        if wrapped_key.algorithm != Algorithm::Transit && !legacy {
+            return Err(Error::MalformedCiphertext(format!(
+                "expected {}, got {}",
+                Algorithm::Transit,
+                wrapped_key.algorithm
+            )));
+        }
+        let ciphertext = std::str::from_utf8(&wrapped_key.ciphertext).map_err(transit_error)?;
+        let response: DecryptResponse = self
+            .post(&self.decrypt_url, &DecryptRequest { ciphertext })
+            .await?;
+
+        let plaintext = Zeroizing::new(
+            STANDARD
+                .decode(Zeroizing::new(response.plaintext).as_bytes())
+                .map_err(transit_error)?,
+        );
+        Aes256Key::try_from(plaintext.as_slice())
+    }
+}
+
+fn transit_error(err: impl std::fmt::Display) -> Error {
+    Error::KeyProvider(format!("transit: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, Request, ResponseTemplate,
+    };
+
+    const TOKEN: &str = "test-token";
+
+    /// Mimics transit by prefixing the plaintext instead of encrypting it.
+    async fn mock_transit() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/transit/encrypt/pontoon"))
+            .and(header("X-Vault-Token", TOKEN))
+            .respond_with(|request: &Request| {
+                let body: Value = request.body_json().expect("json body");
+                let plaintext = body["plaintext"].as_str().expect("plaintext");
+                ResponseTemplate::new(200).set_body_json(
+                    json!({ "data": { "ciphertext": format!("vault:v1:{plaintext}") } }),
+                )
+            })
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/transit/decrypt/pontoon"))
+            .and(header("X-Vault-Token", TOKEN))
+            .respond_with(|request: &Request| {
+                let body: Value = request.body_json().expect("json body");
+                let ciphertext = body["ciphertext"].as_str().expect("ciphertext");
+                match ciphertext.strip_prefix("vault:v1:") {
+                    Some(plaintext) => ResponseTemplate::new(200)
+                        .set_body_json(json!({ "data": { "plaintext": plaintext } })),
+                    None => ResponseTemplate::new(400)
+                        .set_body_json(json!({ "errors": ["invalid ciphertext"] })),
+                }
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn config(server: &MockServer, token: &str) -> TransitConfig {
+        TransitConfig {
+            address: server.uri(),
+            key_name: "pontoon".to_string(),
+            token: SecretBox::new(Box::new(token.to_string())),
+            mount: "transit".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transit_wrap_unwrap() {
+        let server = mock_transit().await;
+        let provider = TransitProvider::new(&config(&server, TOKEN)).expect("provider");
+
+        let data_key = Aes256Key::generate();
+        let wrapped = provider.wrap(&data_key).await.expect("wrap");
+        assert!(wrapped.ciphertext.starts_with(b"vault:v1:"));
+
+        // The wrapped key survives the `nonce:ciphertext` column encoding.
+        let stored: String = wrapped.into();
+        let wrapped: Encrypted = stored.try_into().expect("decode");
+        let unwrapped = provider.unwrap(&wrapped).await.expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+    }
+
+    #[tokio::test]
+    async fn test_transit_errors() {
+        let server = mock_transit().await;
+        let provider = TransitProvider::new(&config(&server, "wrong-token")).expect("provider");
+        let result = provider.wrap(&Aes256Key::generate()).await;
+        assert!(matches!(result, Err(Error::KeyProvider(_))));
+
+        let provider = TransitProvider::new(&config(&server, TOKEN)).expect("provider");
+        let wrapped = Encrypted::new(Algorithm::Transit, Vec::new(), b"tampered".to_vec());
+        let result = provider.unwrap(&wrapped).await;
+        assert!(matches!(result, Err(Error::KeyProvider(_))));
+    }
+}
@@ -0,0 +1,134 @@
+//! Unseal endpoints of the services, and the gate rejecting their other requests while sealed.
+
+use crate::{config::UnsealConfig, master_keys::MasterKeys, shamir::UnsealStatus};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    web::{Data, Json},
+    Error, HttpRequest, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use types::{error, secret::redact::Redacted};
+
+/// Context of a service holding master keys.
+pub trait UnsealContext {
+    fn master_keys(&self) -> &MasterKeys;
+    /// `None` when no master key is split into shares.
+    fn unseal_config(&self) -> Option<&UnsealConfig>;
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsealRequest {
+    pub share: Redacted<String>,
+    /// Master key the share belongs to, defaults to the one being unsealed.
+    pub key_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnsealResponse {
+    pub sealed: bool,
+    /// Progress of the master keys split into shares, only told to unseal token holders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys: Option<BTreeMap<String, UnsealStatus>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    code: &'static str,
+    message: String,
+}
+
+fn error_response(err: &error::Error) -> HttpResponse {
+    let status = StatusCode::from_u16(err.http_status().as_u16())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    // Details of internal failures are only logged
+    let message = if status.is_server_error() && *err != error::Error::Sealed {
+        "Internal server error".to_string()
+    } else {
+        err.to_string()
+    };
+    HttpResponse::build(status).json(ErrorResponse {
+        code: err.code(),
+        message,
+    })
+}
+
+fn unseal_response(master_keys: &MasterKeys, authorized: bool) -> UnsealResponse {
+    UnsealResponse {
+        sealed: master_keys.is_sealed(),
+        keys: authorized.then(|| master_keys.unseal_status()),
+    }
+}
+
+/// Whether the `x-unseal-token` header matches `UNSEAL__TOKEN`.
+fn is_unseal_authorized(ctx: &impl UnsealContext, req: &HttpRequest) -> bool {
+    let token = req
+        .headers()
+        .get("x-unseal-token")
+        .and_then(|value| value.to_str().ok());
+    match (ctx.unseal_config(), token) {
+        (Some(config), Some(token)) => config.is_authorized(token),
+        _ => false,
+    }
+}
+
+pub async fn get_unseal<C: UnsealContext + 'static>(
+    ctx: Data<C>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let authorized = is_unseal_authorized(ctx.get_ref(), &req);
+    HttpResponse::Ok().json(unseal_response(ctx.master_keys(), authorized))
+}
+
+pub async fn unseal<C: UnsealContext + 'static>(
+    ctx: Data<C>,
+    req: HttpRequest,
+    body: Json<UnsealRequest>,
+) -> HttpResponse {
+    if !is_unseal_authorized(ctx.get_ref(), &req) {
+        tracing::error!("Unseal attempt with an invalid token");
+        return HttpResponse::Unauthorized().body("Unauthorized");
+    }
+
+    let master_keys = ctx.master_keys();
+    let key_id = body
+        .key_id
+        .as_deref()
+        .unwrap_or(master_keys.unseal_key_id());
+    match master_keys.unseal(key_id, body.share.expose()) {
+        Ok(status) => {
+            tracing::info!(
+                "Key share of master key '{}' submitted: {:?}",
+                key_id,
+                status
+            );
+            HttpResponse::Ok().json(unseal_response(master_keys, true))
+        }
+        Err(err) => {
+            tracing::error!("Failed to unseal master key '{}': {}", key_id, err);
+            error_response(&err)
+        }
+    }
+}
+
+/// Answers `503 Service Unavailable` until the master keys are unsealed, except to unseal them.
+pub async fn reject_sealed<C: UnsealContext + 'static>(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let sealed = req
+        .app_data::<Data<C>>()
+        .is_some_and(|ctx| ctx.master_keys().is_sealed());
+    if sealed && req.path() != "/unseal" {
+        tracing::debug!("Rejecting request, the master keys are sealed");
+        // Not an internal failure, the client may retry once operators unseal
+        let response = error_response(&error::Error::Sealed);
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,36 @@
+use serde::Deserialize;
+
+/// TLS of a service, e.g. `TLS__CERT=/etc/pontoon/cert.pem` and `TLS__KEY=/etc/pontoon/key.pem`.
+///
+/// Services serve plain HTTP when it is not set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain, leaf first.
+    pub cert: String,
+    /// PEM encoded private key of the certificate: PKCS#8, PKCS#1 or SEC1.
+    pub key: String,
+    /// PEM encoded CA certificates issuing client certificates, enabling mutual TLS.
+    pub client_ca: Option<String>,
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+    /// Seconds between checks of the certificate and key files for changes, `0` to never
+    /// reload them.
+    #[serde(default = "default_reload_interval")]
+    pub reload_interval: u64,
+}
+
+fn default_reload_interval() -> u64 {
+    30
+}
+
+/// Whether clients must present a certificate when `client_ca` is set, e.g.
+/// `TLS__CLIENT_AUTH=optional`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// Handshakes without a client certificate issued by `client_ca` fail.
+    #[default]
+    Required,
+    /// Client certificates are verified when presented, handshakes without one succeed.
+    Optional,
+}
@@ -0,0 +1,110 @@
+use crate::error::Error;
+use rustls_pki_types::{pem::PemObject, CertificateDer};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{fmt::Display, str::FromStr};
+
+/// Hex encoded SHA-256 digest of a DER encoded X.509 certificate, lowercase and without
+/// separators, as printed by `openssl x509 -noout -fingerprint -sha256` once normalized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, sqlx::Type)]
+#[sqlx(transparent)]
+#[serde(transparent)]
+pub struct CertificateFingerprint(String);
+
+impl CertificateFingerprint {
+    pub fn from_der(der: &[u8]) -> Self {
+        CertificateFingerprint(hex::encode(Sha256::digest(der)))
+    }
+
+    /// Fingerprint of the first certificate of a PEM document.
+    pub fn from_pem(pem: &str) -> Result<Self, Error> {
+        let certificate = CertificateDer::from_pem_slice(pem.as_bytes())
+            .map_err(|err| Error::InvalidCertificate(err.to_string()))?;
+        Ok(Self::from_der(&certificate))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for CertificateFingerprint {
+    type Err = Error;
+
+    /// Accepts the `AB:CD:...` form of OpenSSL as well as bare hex digits.
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let hex: String = str
+            .trim()
+            .chars()
+            .filter(|char| *char != ':')
+            .map(|char| char.to_ascii_lowercase())
+            .collect();
+        if hex.len() != 64 || !hex.chars().all(|char| char.is_ascii_hexdigit()) {
+            return Err(Error::InvalidCertificate(format!(
+                "expected a SHA-256 fingerprint, got {}",
+                str
+            )));
+        }
+        Ok(CertificateFingerprint(hex))
+    }
+}
+
+/// Client certificate registered to a client, without the certificate itself.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CertificateInfo {
+    pub fingerprint: CertificateFingerprint,
+    pub created_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Self-signed certificate of `CN=client`
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBeDCCAR+gAwIBAgIUH289Zp0+464MnvVakSgg5RQbtKIwCgYIKoZIzj0EAwIw
+ETEPMA0GA1UEAwwGY2xpZW50MCAXDTI2MTAxNzAzMTIwNloYDzIxMjYwOTIzMDMx
+MjA2WjARMQ8wDQYDVQQDDAZjbGllbnQwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNC
+AAQdAhKNXpfLycniNBuPywok60L8pAlc6JUwvEioowLL6VZ+1hGZjftRGZdAHjk7
+qRFSxfjs+dVGoGfXTAzTC1Vho1MwUTAdBgNVHQ4EFgQU48jQlIISqCYDLhPGszRI
+XT611JIwHwYDVR0jBBgwFoAU48jQlIISqCYDLhPGszRIXT611JIwDwYDVR0TAQH/
+BAUwAwEB/zAKBggqhkjOPQQDAgNHADBEAiATQbXboEkVlD5BqCKfQyTiJmnFkodp
+aZk7bHjIBIne8AIgEdNv97jyTRrrOpbSFRlzwZiO+ypjLdgIsr8QD5iFVvw=
+-----END CERTIFICATE-----
+";
+    const OPENSSL_FINGERPRINT: &str = "5E:FD:80:92:01:1E:22:C8:81:14:F4:D7:C0:F3:F3:0B:\
+        14:F4:5A:18:2F:B0:A9:DE:F3:97:92:ED:3B:C1:4E:83";
+
+    #[test]
+    fn test_certificate_fingerprint() {
+        let fingerprint = CertificateFingerprint::from_pem(CERTIFICATE).expect("fingerprint");
+        assert_eq!(
+            fingerprint.as_str(),
+            "5efd8092011e22c88114f4d7c0f3f30b14f45a182fb0a9def39792ed3bc14e83"
+        );
+        assert_eq!(
+            CertificateFingerprint::from_str(OPENSSL_FINGERPRINT),
+            Ok(fingerprint.clone())
+        );
+        assert_eq!(
+            CertificateFingerprint::from_str(fingerprint.as_str()),
+            Ok(fingerprint)
+        );
+    }
+
+    #[test]
+    fn test_invalid_fingerprints() {
+        assert!(CertificateFingerprint::from_str("5efd8092011e22c8").is_err());
+        assert!(CertificateFingerprint::from_str(&"z".repeat(64)).is_err());
+        assert!(matches!(
+            CertificateFingerprint::from_pem("not a certificate"),
+            Err(Error::InvalidCertificate(_))
+        ));
+    }
+}
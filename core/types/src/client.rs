@@ -1,20 +1,22 @@
 use crate::{
     api_key::ApiKey,
-    encrypt::{master_key::MasterKey, Aes256Key, Encrypted},
+    client_key::{CredentialType, PublicKeyCredentials},
+    encrypt::{provider::KeyRing, row_context, Aes256Key, Encrypted},
     error::Error,
+    request_signature,
+    scope::Scope,
     secret::{
         mask::{expose_masked, Masked},
         redact::{expose_redacted, Redacted},
     },
+    tenant::{encrypt::EncryptedTenantKey, TenantKey, TenantKeyRing},
 };
-use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
-    Engine,
-};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use hmac::{Hmac, Mac};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, sqlx::Type)]
@@ -59,8 +61,8 @@ pub struct Client {
 
 impl Client {
     pub fn new(name: String) -> Self {
-        let credentials = Credentials::generate();
         let id = ClientId::from(name.as_str());
+        let credentials = Credentials::generate(id.clone());
         Client {
             id,
             name,
@@ -72,8 +74,11 @@ impl Client {
         &self.id
     }
 
-    pub fn encrypt(&self, master_key: &MasterKey) -> Result<encrypt::EncryptedClient, Error> {
-        let encrypted_credentials = self.credentials.encrypt(&master_key)?;
+    pub async fn encrypt(
+        &self,
+        key_ring: &impl KeyRing,
+    ) -> Result<encrypt::EncryptedClient, Error> {
+        let encrypted_credentials = self.credentials.encrypt(key_ring).await?;
 
         Ok(encrypt::EncryptedClient {
             id: self.id.clone(),
@@ -81,14 +86,31 @@ impl Client {
             credentials: encrypted_credentials,
         })
     }
+
+    /// Encrypts the client with a new tenant key, returned wrapped by the master keys.
+    pub async fn encrypt_with_tenant_key(
+        &self,
+        master_keys: &(impl KeyRing + Sync),
+    ) -> Result<(encrypt::EncryptedClient, EncryptedTenantKey), Error> {
+        let tenant_key = TenantKey::generate(self.id.clone());
+        let key_ring = TenantKeyRing::new(master_keys, Some(&tenant_key));
+
+        Ok((
+            self.encrypt(&key_ring).await?,
+            tenant_key.encrypt(master_keys).await?,
+        ))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Credentials {
+    #[serde(skip_serializing)]
+    pub client_id: ClientId,
     #[serde(serialize_with = "expose_masked")]
     pub api_key: Masked<ApiKey>,
     #[serde(serialize_with = "expose_redacted")]
     secret: Redacted<String>,
+    pub scopes: Vec<Scope>,
 }
 
 impl PartialEq for Credentials {
@@ -100,14 +122,45 @@ impl PartialEq for Credentials {
 impl Eq for Credentials {}
 
 impl Credentials {
-    pub fn generate() -> Self {
+    /// Generates credentials granted every scope.
+    pub fn generate(client_id: ClientId) -> Self {
+        Self::generate_with_scopes(client_id, Scope::ALL)
+    }
+
+    pub fn generate_with_scopes(
+        client_id: ClientId,
+        scopes: impl IntoIterator<Item = Scope>,
+    ) -> Self {
+        let mut scopes: Vec<Scope> = scopes.into_iter().collect();
+        scopes.sort();
+        scopes.dedup();
+
         let mut secret = [0u8; 32];
         rand::thread_rng().fill_bytes(&mut secret);
         let encoded_secret = URL_SAFE_NO_PAD.encode(secret);
 
         Credentials {
+            client_id,
             api_key: Masked::from(ApiKey::from(Uuid::new_v4())),
             secret: Redacted::from(encoded_secret),
+            scopes,
+        }
+    }
+
+    pub fn check_scope(&self, scope: Scope) -> Result<(), Error> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(Error::MissingScope(scope))
+        }
+    }
+
+    /// Compares `secret` with the one of the credentials, in constant time.
+    pub fn check_secret(&self, secret: &str) -> Result<(), Error> {
+        if bool::from(self.secret.expose().as_bytes().ct_eq(secret.as_bytes())) {
+            Ok(())
+        } else {
+            Err(Error::InvalidClientCredentials)
         }
     }
 
@@ -123,33 +176,138 @@ impl Credentials {
         // Update the hasher with the message
         hasher.update(message.as_bytes());
 
-        // Verify provided signature
-        // NOTE: It should be URL_SAFE_NO_PAD but most of online tools do STANDARD
+        // Verify provided signature, most of online tools encode it as STANDARD base64
         hasher
-            .verify_slice(&STANDARD.decode(signature.as_bytes())?)
+            .verify_slice(&request_signature::decode_signature(signature)?)
             .map_err(|_| Error::InvalidSignature)?;
 
         Ok(())
     }
 
-    pub fn encrypt(&self, master_key: &MasterKey) -> Result<encrypt::EncryptedCredentials, Error> {
+    pub async fn encrypt(
+        &self,
+        key_ring: &impl KeyRing,
+    ) -> Result<encrypt::EncryptedCredentials, Error> {
         let data_key = Aes256Key::generate();
-        let encrypted_secret = data_key.encrypt(&self.secret.expose())?;
-        let encrypted_data_key = master_key.encrypt(&data_key.to_string())?;
+        let encrypted_secret =
+            data_key.encrypt(self.secret.expose(), &secret_context(self.api_key.expose()))?;
+        let encrypted_data_key = key_ring.wrap_data_key(&data_key).await?;
 
         Ok(encrypt::EncryptedCredentials {
+            client_id: self.client_id.clone(),
             api_key: self.api_key.expose().to_owned().into(),
             encrypted_secret,
             encrypted_data_key,
+            master_key_id: key_ring.active_key_id().to_string(),
+            scopes: self.scopes.clone(),
         })
     }
 }
 
+/// Credentials a request is authenticated with, dispatched on their type.
+#[derive(Debug)]
+pub enum ClientCredentials {
+    Hmac(Credentials),
+    PublicKey(PublicKeyCredentials),
+}
+
+impl ClientCredentials {
+    pub fn client_id(&self) -> &ClientId {
+        match self {
+            ClientCredentials::Hmac(credentials) => &credentials.client_id,
+            ClientCredentials::PublicKey(credentials) => &credentials.client_id,
+        }
+    }
+
+    pub fn api_key(&self) -> &Masked<ApiKey> {
+        match self {
+            ClientCredentials::Hmac(credentials) => &credentials.api_key,
+            ClientCredentials::PublicKey(credentials) => &credentials.api_key,
+        }
+    }
+
+    pub fn credential_type(&self) -> CredentialType {
+        match self {
+            ClientCredentials::Hmac(_) => CredentialType::Hmac,
+            ClientCredentials::PublicKey(credentials) => credentials.credential_type,
+        }
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        match self {
+            ClientCredentials::Hmac(credentials) => &credentials.scopes,
+            ClientCredentials::PublicKey(credentials) => &credentials.scopes,
+        }
+    }
+
+    pub fn check_scope(&self, scope: Scope) -> Result<(), Error> {
+        match self {
+            ClientCredentials::Hmac(credentials) => credentials.check_scope(scope),
+            ClientCredentials::PublicKey(credentials) => credentials.check_scope(scope),
+        }
+    }
+
+    /// Checks the secret of HMAC credentials, public-key credentials have none.
+    pub fn check_secret(&self, secret: &str) -> Result<(), Error> {
+        match self {
+            ClientCredentials::Hmac(credentials) => credentials.check_secret(secret),
+            ClientCredentials::PublicKey(_) => Err(Error::InvalidClientCredentials),
+        }
+    }
+
+    pub fn check_authentication(&self, message: &str, signature: &str) -> Result<(), Error> {
+        match self {
+            ClientCredentials::Hmac(credentials) => {
+                credentials.check_authentication(message, signature)
+            }
+            ClientCredentials::PublicKey(credentials) => {
+                credentials.check_authentication(message, signature)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialsState {
+    Active,
+    Expired,
+    Revoked,
+}
+
+/// Lifecycle of credentials, listed without their secret and with a masked API key.
+///
+/// Timestamps are Unix seconds.
+#[derive(Debug, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct CredentialsInfo {
+    pub api_key: Masked<ApiKey>,
+    #[serde(rename = "type")]
+    pub credential_type: CredentialType,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    pub scopes: Vec<Scope>,
+}
+
+impl CredentialsInfo {
+    pub fn state(&self, now: i64) -> CredentialsState {
+        match (self.revoked_at, self.expires_at) {
+            (Some(_), _) => CredentialsState::Revoked,
+            (None, Some(expires_at)) if expires_at <= now => CredentialsState::Expired,
+            _ => CredentialsState::Active,
+        }
+    }
+}
+
+/// Binds the encrypted secret to its credentials row.
+fn secret_context(api_key: &ApiKey) -> Vec<u8> {
+    row_context("credentials", &api_key.to_uuid())
+}
+
 pub mod encrypt {
     use super::*;
     use serde::Deserialize;
     use sqlx::{postgres::PgRow, FromRow, Row};
-    use std::str::FromStr;
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
     pub struct EncryptedClient {
@@ -159,8 +317,9 @@ pub mod encrypt {
     }
 
     impl EncryptedClient {
-        pub fn decrypt(self, master_key: &MasterKey) -> Result<Client, Error> {
-            let credentials = self.credentials.decrypt(master_key)?;
+        /// Decrypts the client with its tenant key ring, see [`TenantKeyRing`].
+        pub async fn decrypt(self, key_ring: &impl KeyRing) -> Result<Client, Error> {
+            let credentials = self.credentials.decrypt(key_ring).await?;
             Ok(Client {
                 id: self.id,
                 name: self.name,
@@ -171,34 +330,147 @@ pub mod encrypt {
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, sqlx::Type)]
     pub struct EncryptedCredentials {
+        pub client_id: ClientId,
         pub api_key: Masked<ApiKey>,
         pub encrypted_secret: Encrypted,
         pub encrypted_data_key: Encrypted,
+        /// Master key wrapping `encrypted_data_key`, or [`TENANT_KEY_ID`](crate::tenant::TENANT_KEY_ID)
+        /// for the client's tenant key.
+        pub master_key_id: String,
+        pub scopes: Vec<Scope>,
     }
 
     impl EncryptedCredentials {
-        pub fn decrypt(self, master_key: &MasterKey) -> Result<Credentials, Error> {
-            let str = master_key.decrypt(&self.encrypted_data_key)?;
-            let data_key = Aes256Key::from_str(&str)?;
-            let secret = data_key.decrypt(&self.encrypted_secret)?;
+        pub async fn decrypt(self, key_ring: &impl KeyRing) -> Result<Credentials, Error> {
+            let data_key = key_ring
+                .unwrap_data_key(&self.master_key_id, &self.encrypted_data_key)
+                .await?;
+            let secret = data_key.decrypt(
+                &self.encrypted_secret,
+                &secret_context(self.api_key.expose()),
+            )?;
             Ok(Credentials {
+                client_id: self.client_id,
                 api_key: self.api_key,
                 secret: Redacted::from(secret),
+                scopes: self.scopes,
             })
         }
     }
 
+    /// Credentials row as stored, the secret of HMAC credentials still encrypted.
+    #[derive(Debug)]
+    pub enum StoredCredentials {
+        Hmac(EncryptedCredentials),
+        PublicKey(PublicKeyCredentials),
+    }
+
+    impl StoredCredentials {
+        pub fn client_id(&self) -> &ClientId {
+            match self {
+                StoredCredentials::Hmac(credentials) => &credentials.client_id,
+                StoredCredentials::PublicKey(credentials) => &credentials.client_id,
+            }
+        }
+
+        pub async fn decrypt(self, key_ring: &impl KeyRing) -> Result<ClientCredentials, Error> {
+            match self {
+                StoredCredentials::Hmac(credentials) => Ok(ClientCredentials::Hmac(
+                    credentials.decrypt(key_ring).await?,
+                )),
+                StoredCredentials::PublicKey(credentials) => {
+                    Ok(ClientCredentials::PublicKey(credentials))
+                }
+            }
+        }
+    }
+
+    impl<'r> FromRow<'r, PgRow> for StoredCredentials {
+        fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+            match row.try_get("credential_type")? {
+                CredentialType::Hmac => Ok(StoredCredentials::Hmac(
+                    EncryptedCredentials::from_row(row)?,
+                )),
+                CredentialType::Ed25519 | CredentialType::P256 => Ok(StoredCredentials::PublicKey(
+                    PublicKeyCredentials::from_row(row)?,
+                )),
+            }
+        }
+    }
+
     impl<'r> FromRow<'r, PgRow> for encrypt::EncryptedClient {
         fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
             Ok(encrypt::EncryptedClient {
                 id: row.try_get("id")?,
                 name: row.try_get("name")?,
                 credentials: encrypt::EncryptedCredentials {
+                    client_id: row.try_get("id")?,
                     api_key: row.try_get("api_key")?,
                     encrypted_secret: row.try_get("encrypted_secret")?,
                     encrypted_data_key: row.try_get("encrypted_data_key")?,
+                    master_key_id: row.try_get("master_key_id")?,
+                    scopes: row.try_get("scopes")?,
                 },
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credentials_state() {
+        let info = |expires_at, revoked_at| CredentialsInfo {
+            api_key: Masked::from(ApiKey::from(Uuid::new_v4())),
+            credential_type: CredentialType::Hmac,
+            created_at: 100,
+            expires_at,
+            revoked_at,
+            scopes: Scope::ALL.to_vec(),
+        };
+        assert_eq!(info(None, None).state(200), CredentialsState::Active);
+        assert_eq!(info(Some(300), None).state(200), CredentialsState::Active);
+        assert_eq!(info(Some(200), None).state(200), CredentialsState::Expired);
+        assert_eq!(
+            info(Some(300), Some(150)).state(200),
+            CredentialsState::Revoked
+        );
+    }
+
+    #[test]
+    fn test_credentials_scopes() {
+        let client_id = ClientId::from("client");
+        assert_eq!(
+            Credentials::generate(client_id.clone()).scopes,
+            Scope::ALL.to_vec()
+        );
+
+        let credentials = Credentials::generate_with_scopes(
+            client_id,
+            [Scope::UsersSign, Scope::UsersRead, Scope::UsersSign],
+        );
+        assert_eq!(credentials.scopes, vec![Scope::UsersSign, Scope::UsersRead]);
+        assert_eq!(credentials.check_scope(Scope::UsersSign), Ok(()));
+        assert_eq!(
+            credentials.check_scope(Scope::UsersRevoke),
+            Err(Error::MissingScope(Scope::UsersRevoke))
+        );
+    }
+
+    #[test]
+    fn test_credentials_secret() {
+        let credentials = Credentials::generate(ClientId::from("client"));
+        let secret = credentials.secret.expose().clone();
+        assert_eq!(credentials.check_secret(&secret), Ok(()));
+        assert_eq!(
+            credentials.check_secret(&secret[1..]),
+            Err(Error::InvalidClientCredentials)
+        );
+        assert_eq!(
+            Credentials::generate(ClientId::from("client")).check_secret(&secret),
+            Err(Error::InvalidClientCredentials)
+        );
+    }
+}
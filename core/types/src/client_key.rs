@@ -0,0 +1,311 @@
+use crate::{
+    api_key::ApiKey,
+    client::ClientId,
+    error::Error,
+    request_signature,
+    scope::Scope,
+    secret::mask::{expose_masked, Masked},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+use uuid::Uuid;
+
+/// How requests signed with credentials are verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialType {
+    /// HMAC SHA-256 with a secret shared with the server, stored encrypted.
+    Hmac,
+    /// Ed25519 signature, the server only stores the public key.
+    Ed25519,
+    /// ECDSA P-256 SHA-256 signature, the server only stores the public key.
+    P256,
+}
+
+impl CredentialType {
+    /// JWS `alg` of client assertions signed with credentials of this type.
+    pub fn jws_algorithm(&self) -> &'static str {
+        match self {
+            CredentialType::Hmac => "HS256",
+            CredentialType::Ed25519 => "EdDSA",
+            CredentialType::P256 => "ES256",
+        }
+    }
+}
+
+impl Display for CredentialType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CredentialType::Hmac => f.write_str("hmac"),
+            CredentialType::Ed25519 => f.write_str("ed25519"),
+            CredentialType::P256 => f.write_str("p256"),
+        }
+    }
+}
+
+impl FromStr for CredentialType {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "hmac" => Ok(CredentialType::Hmac),
+            "ed25519" => Ok(CredentialType::Ed25519),
+            "p256" => Ok(CredentialType::P256),
+            other => Err(Error::UnknownCredentialType(other.to_string())),
+        }
+    }
+}
+
+/// Public key a client signs requests with, parsed from its PEM encoded SubjectPublicKeyInfo.
+pub enum ClientPublicKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl ClientPublicKey {
+    pub fn from_pem(pem: &str) -> Result<Self, Error> {
+        {
+            use ed25519_dalek::pkcs8::DecodePublicKey;
+            if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem) {
+                return Ok(ClientPublicKey::Ed25519(key));
+            }
+        }
+        {
+            use p256::pkcs8::DecodePublicKey;
+            if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_pem(pem) {
+                return Ok(ClientPublicKey::P256(key));
+            }
+        }
+        Err(Error::InvalidClientKey(
+            "expected a PEM encoded Ed25519 or P-256 public key".to_string(),
+        ))
+    }
+
+    pub fn credential_type(&self) -> CredentialType {
+        match self {
+            ClientPublicKey::Ed25519(_) => CredentialType::Ed25519,
+            ClientPublicKey::P256(_) => CredentialType::P256,
+        }
+    }
+
+    pub fn to_pem(&self) -> Result<String, Error> {
+        let pem = match self {
+            ClientPublicKey::Ed25519(key) => {
+                use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePublicKey};
+                key.to_public_key_pem(LineEnding::LF)
+            }
+            ClientPublicKey::P256(key) => {
+                use p256::pkcs8::{EncodePublicKey, LineEnding};
+                key.to_public_key_pem(LineEnding::LF)
+            }
+        };
+        pem.map_err(|err| Error::InvalidClientKey(err.to_string()))
+    }
+
+    /// Checks the signature of the message.
+    ///
+    /// Ed25519 signatures are verified in strict mode, P-256 signatures are `r || s` or DER
+    /// encoded, over the SHA-256 digest of the message.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), Error> {
+        match self {
+            ClientPublicKey::Ed25519(key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|_| Error::InvalidSignature)?;
+                key.verify_strict(message, &signature)
+                    .map_err(|_| Error::InvalidSignature)
+            }
+            ClientPublicKey::P256(key) => {
+                use p256::ecdsa::{signature::Verifier, Signature};
+                let signature = match signature.len() {
+                    64 => Signature::from_slice(signature),
+                    _ => Signature::from_der(signature),
+                }
+                .map_err(|_| Error::InvalidSignature)?;
+                key.verify(message, &signature)
+                    .map_err(|_| Error::InvalidSignature)
+            }
+        }
+    }
+}
+
+/// Credentials of a client signing requests with its private key, the server holding no
+/// secret of it.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PublicKeyCredentials {
+    #[serde(skip_serializing)]
+    pub client_id: ClientId,
+    #[serde(serialize_with = "expose_masked")]
+    pub api_key: Masked<ApiKey>,
+    #[serde(rename = "type")]
+    pub credential_type: CredentialType,
+    /// PEM encoded SubjectPublicKeyInfo.
+    pub public_key: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl PublicKeyCredentials {
+    /// Registers `public_key` under a new API key.
+    pub fn new(
+        client_id: ClientId,
+        public_key: &str,
+        scopes: impl IntoIterator<Item = Scope>,
+    ) -> Result<Self, Error> {
+        let key = ClientPublicKey::from_pem(public_key)?;
+        let mut scopes: Vec<Scope> = scopes.into_iter().collect();
+        scopes.sort();
+        scopes.dedup();
+
+        Ok(PublicKeyCredentials {
+            client_id,
+            api_key: Masked::from(ApiKey::from(Uuid::new_v4())),
+            credential_type: key.credential_type(),
+            public_key: key.to_pem()?,
+            scopes,
+        })
+    }
+
+    pub fn check_scope(&self, scope: Scope) -> Result<(), Error> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(Error::MissingScope(scope))
+        }
+    }
+
+    /// Checks the base64 encoded `signature` of the message against the public key.
+    pub fn check_authentication(&self, message: &str, signature: &str) -> Result<(), Error> {
+        tracing::debug!(
+            "Checking {} signature for a message: {}",
+            self.credential_type,
+            message
+        );
+
+        let key = ClientPublicKey::from_pem(&self.public_key)?;
+        key.verify(
+            message.as_bytes(),
+            &request_signature::decode_signature(signature)?,
+        )
+    }
+}
+
+pub mod postgres {
+    use crate::client_key::CredentialType;
+    use sqlx::{
+        encode::IsNull,
+        postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+    use std::{error::Error, str::FromStr};
+
+    impl Type<Postgres> for CredentialType {
+        fn type_info() -> PgTypeInfo {
+            <String as Type<Postgres>>::type_info()
+        }
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <String as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl<'q> Encode<'q, Postgres> for CredentialType {
+        fn encode_by_ref(
+            &self,
+            buf: &mut PgArgumentBuffer,
+        ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+            <String as Encode<Postgres>>::encode_by_ref(&self.to_string(), buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for CredentialType {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+            let str = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(CredentialType::from_str(str)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    fn sign_ed25519(message: &str) -> (String, String) {
+        use ed25519_dalek::{
+            pkcs8::{spki::der::pem::LineEnding, EncodePublicKey},
+            Signer, SigningKey,
+        };
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        let signature = STANDARD.encode(signing_key.sign(message.as_bytes()).to_bytes());
+        (pem, signature)
+    }
+
+    #[test]
+    fn test_ed25519_credentials() {
+        let (pem, signature) = sign_ed25519("message");
+        let credentials =
+            PublicKeyCredentials::new(ClientId::from("client"), &pem, [Scope::UsersSign])
+                .expect("credentials");
+        assert_eq!(credentials.credential_type, CredentialType::Ed25519);
+        assert_eq!(
+            credentials.check_authentication("message", &signature),
+            Ok(())
+        );
+        assert_eq!(
+            credentials.check_authentication("tampered", &signature),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(
+            credentials.check_scope(Scope::UsersRead),
+            Err(Error::MissingScope(Scope::UsersRead))
+        );
+    }
+
+    #[test]
+    fn test_p256_credentials() {
+        use p256::{
+            ecdsa::{signature::Signer, Signature, SigningKey},
+            pkcs8::{EncodePublicKey, LineEnding},
+        };
+        let signing_key = SigningKey::from_slice(&[3u8; 32]).expect("key");
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        let credentials = PublicKeyCredentials::new(ClientId::from("client"), &pem, Scope::ALL)
+            .expect("credentials");
+        assert_eq!(credentials.credential_type, CredentialType::P256);
+
+        let signature: Signature = signing_key.sign(b"message");
+        // Raw `r || s`, as produced by WebCrypto, and DER, as produced by OpenSSL
+        let raw = STANDARD.encode(signature.to_bytes());
+        let der = STANDARD.encode(signature.to_der().as_bytes());
+        assert_eq!(credentials.check_authentication("message", &raw), Ok(()));
+        assert_eq!(credentials.check_authentication("message", &der), Ok(()));
+        assert_eq!(
+            credentials.check_authentication("tampered", &raw),
+            Err(Error::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_public_keys() {
+        // secp256k1 keys sign wallet users' messages, not requests
+        use k256::pkcs8::{EncodePublicKey, LineEnding};
+        let pem = k256::ecdsa::SigningKey::from_slice(&[1u8; 32])
+            .expect("key")
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        for pem in [pem.as_str(), "not a pem"] {
+            assert!(matches!(
+                PublicKeyCredentials::new(ClientId::from("client"), pem, Scope::ALL),
+                Err(Error::InvalidClientKey(_))
+            ));
+        }
+        assert_eq!(CredentialType::from_str("p256"), Ok(CredentialType::P256));
+        assert!(CredentialType::from_str("rsa").is_err());
+    }
+}
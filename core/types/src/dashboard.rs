@@ -0,0 +1,136 @@
+use crate::{client::ClientId, error::Error, password, secret::redact::Redacted};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Longest email address accepted, as limited by SMTP.
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Account of a client's staff on the dashboard, distinct from operators of the admin API.
+#[derive(Debug, sqlx::FromRow)]
+pub struct DashboardAccount {
+    pub id: Uuid,
+    pub client_id: ClientId,
+    pub email: String,
+    password_hash: String,
+}
+
+impl DashboardAccount {
+    pub fn new(client_id: ClientId, email: &str, password: &str) -> Result<Self, Error> {
+        Ok(DashboardAccount {
+            id: Uuid::new_v4(),
+            client_id,
+            email: normalize_email(email)?,
+            password_hash: password::hash_password(password)?,
+        })
+    }
+
+    pub fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    /// Checks the password of `account`, `None` if no account has the email address.
+    pub fn login(account: Option<&DashboardAccount>, password: &str) -> Result<(), Error> {
+        password::verify_password(account.map(DashboardAccount::password_hash), password)
+    }
+}
+
+/// Trims and lowercases an email address, so that accounts are found whatever its case.
+///
+/// Only the shape is checked, the address is proven by mailing a password reset token to it.
+pub fn normalize_email(email: &str) -> Result<String, Error> {
+    let email = email.trim().to_lowercase();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+        }
+        None => false,
+    };
+    if !valid || email.len() > MAX_EMAIL_LEN || email.chars().any(char::is_whitespace) {
+        return Err(Error::InvalidEmail(email));
+    }
+    Ok(email)
+}
+
+/// Password reset token mailed to the owner of an account, stored as its digest only.
+pub struct ResetToken {
+    pub token: Redacted<String>,
+    pub hash: String,
+}
+
+impl ResetToken {
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        ResetToken {
+            hash: Self::hash(&token),
+            token: Redacted::new(token),
+        }
+    }
+
+    /// Hex encoded SHA-256 digest of `token`, under which it is stored.
+    pub fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(
+            normalize_email(" Alice@Example.COM "),
+            Ok("alice@example.com".to_string())
+        );
+        for email in [
+            "alice",
+            "@example.com",
+            "alice@example",
+            "alice@.com",
+            "alice@@example.com",
+            "al ice@example.com",
+        ] {
+            assert!(
+                matches!(normalize_email(email), Err(Error::InvalidEmail(_))),
+                "{email}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_dashboard_login() {
+        let account = DashboardAccount::new(
+            ClientId::from("client"),
+            "Alice@example.com",
+            "correct horse battery",
+        )
+        .expect("new");
+        assert_eq!(account.email, "alice@example.com");
+        assert_eq!(
+            DashboardAccount::login(Some(&account), "correct horse battery"),
+            Ok(())
+        );
+        assert_eq!(
+            DashboardAccount::login(None, "correct horse battery"),
+            Err(Error::InvalidLogin)
+        );
+    }
+
+    #[test]
+    fn test_reset_token() {
+        let reset_token = ResetToken::generate();
+        assert_eq!(
+            reset_token.hash,
+            ResetToken::hash(reset_token.token.expose())
+        );
+        assert_ne!(reset_token.hash, ResetToken::generate().hash);
+    }
+}
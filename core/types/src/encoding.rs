@@ -0,0 +1,109 @@
+use crate::error::Error;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+/// Encoding of payloads sent for signing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    Hex,
+    Base64,
+}
+
+impl MessageEncoding {
+    pub fn decode(&self, message: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            MessageEncoding::Utf8 => Ok(message.as_bytes().to_vec()),
+            MessageEncoding::Hex => hex::decode(message.trim_start_matches("0x"))
+                .map_err(|err| Error::InvalidPayload(format!("invalid hex message: {}", err))),
+            MessageEncoding::Base64 => STANDARD
+                .decode(message)
+                .map_err(|err| Error::InvalidPayload(format!("invalid base64 message: {}", err))),
+        }
+    }
+}
+
+/// Encoding of returned signatures.
+///
+/// `hex` and `base64` encode the signature as produced by the key, `der` is the hex
+/// encoded ASN.1 DER form of ECDSA signatures and `raw` the hex encoded `r || s` of ECDSA
+/// signatures, without recovery id. Other keys have no DER form and their raw form is the
+/// signature itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+    Der,
+    Raw,
+}
+
+impl SignatureEncoding {
+    /// Decodes a signature sent in this encoding, DER signatures to `r || s`.
+    pub fn decode(&self, signature: &str) -> Result<Vec<u8>, Error> {
+        let hex =
+            || hex::decode(signature.trim_start_matches("0x")).map_err(|_| Error::InvalidSignature);
+        match self {
+            SignatureEncoding::Hex | SignatureEncoding::Raw => hex(),
+            SignatureEncoding::Base64 => STANDARD
+                .decode(signature)
+                .map_err(|_| Error::InvalidSignature),
+            SignatureEncoding::Der => k256::ecdsa::Signature::from_der(&hex()?)
+                .map(|signature| signature.to_bytes().to_vec())
+                .map_err(|_| Error::InvalidSignature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoding::{MessageEncoding, SignatureEncoding},
+        error::Error,
+    };
+
+    #[test]
+    fn test_message_decoding() {
+        assert_eq!(MessageEncoding::Utf8.decode("hi"), Ok(b"hi".to_vec()));
+        assert_eq!(MessageEncoding::Hex.decode("0x6869"), Ok(b"hi".to_vec()));
+        assert_eq!(MessageEncoding::Base64.decode("aGk="), Ok(b"hi".to_vec()));
+        assert!(matches!(
+            MessageEncoding::Hex.decode("zz"),
+            Err(Error::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_signature_decoding() {
+        let r_s = [[0x11u8; 32], [0x22u8; 32]].concat();
+        let der = k256::ecdsa::Signature::from_slice(&r_s).unwrap().to_der();
+
+        assert_eq!(
+            SignatureEncoding::Hex.decode("0x1122"),
+            Ok(vec![0x11, 0x22])
+        );
+        assert_eq!(
+            SignatureEncoding::Raw.decode(&hex::encode(&r_s)),
+            Ok(r_s.clone())
+        );
+        assert_eq!(
+            SignatureEncoding::Base64.decode("ESI="),
+            Ok(vec![0x11, 0x22])
+        );
+        assert_eq!(
+            SignatureEncoding::Der.decode(&hex::encode(der.as_bytes())),
+            Ok(r_s)
+        );
+        assert_eq!(
+            SignatureEncoding::Der.decode("1122"),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(
+            SignatureEncoding::Base64.decode("not base64!"),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
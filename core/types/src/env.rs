@@ -12,7 +12,7 @@ where
 
     let secret = content
         .parse()
-        .map_err(|err| ErrorKind::ParseFailure(format!("{}", err).into()))?;
+        .map_err(|err| ErrorKind::ParseFailure(format!("{}", err)))?;
 
     Ok(secret)
 }
@@ -1,3 +1,4 @@
+use crate::scope::Scope;
 use http::StatusCode;
 use thiserror::Error;
 
@@ -8,6 +9,114 @@ pub enum Error {
     #[error("invalid signature")]
     InvalidSignature,
 
+    #[error("request timestamp is outside of the accepted window")]
+    TimestampOutOfWindow,
+
+    #[error("request nonce has already been used")]
+    NonceReused,
+
+    #[error("unsupported signature version: {0}")]
+    UnsupportedSignatureVersion(String),
+
+    #[error("header {0} must be signed")]
+    UnsignedHeader(String),
+
+    #[error("x-content-sha256 does not match the request body")]
+    BodyDigestMismatch,
+
+    #[error("invalid client credentials")]
+    InvalidClientCredentials,
+
+    #[error("invalid or expired access token")]
+    InvalidAccessToken,
+
+    #[error("client certificate is missing or not registered to the client")]
+    UnknownClientCertificate,
+
+    #[error("requests from {0} are not allowed for the client")]
+    AddressNotAllowed(String),
+
+    #[error("credentials are locked out for {0} seconds after repeated authentication failures")]
+    CredentialsLocked(u64),
+
+    #[error("credentials lack the {0} scope")]
+    MissingScope(Scope),
+
+    #[error("unknown scope: {0}")]
+    UnknownScope(String),
+
+    #[error("invalid username or password")]
+    InvalidLogin,
+
+    #[error("password must be at least {0} characters long")]
+    WeakPassword(usize),
+
+    #[error("unknown role: {0}")]
+    UnknownRole(String),
+
+    #[error("password hashing failed: {0}")]
+    PasswordHash(String),
+
+    #[error("invalid email address: {0}")]
+    InvalidEmail(String),
+
+    #[error("invalid or expired password reset token")]
+    InvalidResetToken,
+
+    #[error("invalid client public key: {0}")]
+    InvalidClientKey(String),
+
+    #[error("unknown credential type: {0}")]
+    UnknownCredentialType(String),
+
+    #[error("invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("invalid network: {0}")]
+    InvalidNetwork(String),
+
+    #[error("unsupported key type: {0}")]
+    UnsupportedKeyType(String),
+
+    #[error("failed to sign message")]
+    SigningFailed,
+
+    #[error("invalid typed data: {0}")]
+    InvalidTypedData(String),
+
+    #[error("invalid transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+
+    #[error("batch exceeds the maximum of {0} messages")]
+    BatchTooLarge(usize),
+
+    #[error("invalid key options: {0}")]
+    InvalidKeyOptions(String),
+
+    #[error("invalid derivation path: {0}")]
+    InvalidDerivationPath(String),
+
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("key provider failure: {0}")]
+    KeyProvider(String),
+
+    #[error("unknown master key: {0}")]
+    UnknownMasterKey(String),
+
+    #[error("malformed ciphertext: {0}")]
+    MalformedCiphertext(String),
+
+    #[error("master key is sealed")]
+    Sealed,
+
+    #[error("invalid key share: {0}")]
+    InvalidKeyShare(String),
+
     // === Third-party / infrastructure ===
     #[error(transparent)]
     Env(#[from] crate::env::error::Error),
@@ -36,6 +145,42 @@ impl Error {
     pub fn code(&self) -> &'static str {
         match &self {
             Error::InvalidSignature => "ERR_SIG_MALFORMED",
+            Error::TimestampOutOfWindow => "ERR_AUTH_CLOCK_SKEW",
+            Error::NonceReused => "ERR_AUTH_REPLAY",
+            Error::UnsupportedSignatureVersion(_) => "ERR_SIG_VERSION",
+            Error::UnsignedHeader(_) => "ERR_SIG_HEADERS",
+            Error::BodyDigestMismatch => "ERR_SIG_DIGEST",
+            Error::InvalidClientCredentials => "ERR_AUTH_CLIENT",
+            Error::InvalidAccessToken => "ERR_AUTH_TOKEN",
+            Error::UnknownClientCertificate => "ERR_AUTH_CERT",
+            Error::AddressNotAllowed(_) => "ERR_AUTH_NETWORK",
+            Error::CredentialsLocked(_) => "ERR_AUTH_LOCKED",
+            Error::MissingScope(_) => "ERR_AUTH_SCOPE",
+            Error::UnknownScope(_) => "ERR_SCOPE",
+            Error::InvalidLogin => "ERR_AUTH_LOGIN",
+            Error::WeakPassword(_) => "ERR_PASSWORD",
+            Error::UnknownRole(_) => "ERR_ROLE",
+            Error::PasswordHash(_) => "ERR_PASSWORD_HASH",
+            Error::InvalidEmail(_) => "ERR_EMAIL",
+            Error::InvalidResetToken => "ERR_RESET_TOKEN",
+            Error::InvalidClientKey(_) => "ERR_CLIENT_KEY",
+            Error::UnknownCredentialType(_) => "ERR_CREDENTIAL_TYPE",
+            Error::InvalidCertificate(_) => "ERR_CERTIFICATE",
+            Error::InvalidNetwork(_) => "ERR_NETWORK",
+            Error::UnsupportedKeyType(_) => "ERR_KEY_TYPE",
+            Error::SigningFailed => "ERR_SIGNING",
+            Error::InvalidTypedData(_) => "ERR_EIP712",
+            Error::InvalidTransaction(_) => "ERR_TRANSACTION",
+            Error::InvalidPayload(_) => "ERR_PAYLOAD",
+            Error::BatchTooLarge(_) => "ERR_BATCH_SIZE",
+            Error::InvalidKeyOptions(_) => "ERR_KEY_OPTIONS",
+            Error::InvalidDerivationPath(_) => "ERR_DERIVATION_PATH",
+            Error::InvalidMnemonic(_) => "ERR_MNEMONIC",
+            Error::KeyProvider(_) => "ERR_KEY_PROVIDER",
+            Error::UnknownMasterKey(_) => "ERR_MASTER_KEY",
+            Error::MalformedCiphertext(_) => "ERR_CIPHERTEXT",
+            Error::Sealed => "ERR_SEALED",
+            Error::InvalidKeyShare(_) => "ERR_KEY_SHARE",
             Error::Env(_) => "ERR_ENV",
             Error::Base64(_) => "ERR_BASE64",
             Error::AesGcm(_) => "ERR_AES_GCM",
@@ -50,7 +195,42 @@ impl Error {
     pub fn http_status(&self) -> StatusCode {
         match &self {
             Error::InvalidSignature => StatusCode::BAD_REQUEST,
-            Error::Base64(_)
+            Error::TimestampOutOfWindow
+            | Error::NonceReused
+            | Error::UnsupportedSignatureVersion(_)
+            | Error::UnsignedHeader(_)
+            | Error::BodyDigestMismatch
+            | Error::InvalidClientCredentials
+            | Error::InvalidAccessToken
+            | Error::UnknownClientCertificate
+            | Error::InvalidLogin => StatusCode::UNAUTHORIZED,
+            Error::MissingScope(_) | Error::AddressNotAllowed(_) => StatusCode::FORBIDDEN,
+            Error::CredentialsLocked(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::UnknownScope(_)
+            | Error::WeakPassword(_)
+            | Error::UnknownRole(_)
+            | Error::InvalidEmail(_)
+            | Error::InvalidResetToken
+            | Error::InvalidClientKey(_)
+            | Error::UnknownCredentialType(_)
+            | Error::InvalidCertificate(_)
+            | Error::InvalidNetwork(_)
+            | Error::UnsupportedKeyType(_)
+            | Error::InvalidTypedData(_)
+            | Error::InvalidTransaction(_)
+            | Error::InvalidPayload(_)
+            | Error::BatchTooLarge(_)
+            | Error::InvalidKeyOptions(_)
+            | Error::InvalidDerivationPath(_)
+            | Error::InvalidKeyShare(_) => StatusCode::BAD_REQUEST,
+            Error::Sealed => StatusCode::SERVICE_UNAVAILABLE,
+            Error::SigningFailed
+            | Error::PasswordHash(_)
+            | Error::InvalidMnemonic(_)
+            | Error::KeyProvider(_)
+            | Error::UnknownMasterKey(_)
+            | Error::MalformedCiphertext(_)
+            | Error::Base64(_)
             | Error::AesGcm(_)
             | Error::Utf8(_)
             | Error::Rsa(_)
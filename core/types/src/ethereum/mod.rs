@@ -0,0 +1,551 @@
+pub mod rlp;
+pub mod transaction;
+
+use k256::ecdsa::VerifyingKey;
+use sha3::{Digest, Keccak256};
+
+pub type Address = [u8; 20];
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Ethereum address: last 20 bytes of the Keccak-256 hash of the uncompressed public key.
+pub fn address(verifying_key: &VerifyingKey) -> Address {
+    let point = verifying_key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// EIP-191 (version `0x45`) hash of a message, as used by `personal_sign`.
+pub fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    data.extend_from_slice(message);
+    keccak256(&data)
+}
+
+/// EIP-55 mixed-case checksum encoding of an address.
+pub fn to_checksum_address(address: &Address) -> String {
+    let lower = hex::encode(address);
+    let hash = keccak256(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+pub fn parse_address(str: &str) -> Option<Address> {
+    let mut address = [0u8; 20];
+    hex::decode_to_slice(str.strip_prefix("0x")?, &mut address).ok()?;
+    Some(address)
+}
+
+/// Parses a JSON number, decimal or `0x` prefixed hex string into sign and big-endian magnitude.
+pub(crate) fn parse_integer(value: &serde_json::Value) -> Option<(bool, [u8; 32])> {
+    let str = match value {
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::String(str) => str.clone(),
+        _ => return None,
+    };
+    let (negative, digits) = match str.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, str.as_str()),
+    };
+
+    let mut word = [0u8; 32];
+    if let Some(hex) = digits.strip_prefix("0x") {
+        let hex = if hex.len() % 2 == 1 {
+            format!("0{}", hex)
+        } else {
+            hex.to_string()
+        };
+        let bytes = hex::decode(hex).ok()?;
+        if bytes.is_empty() || bytes.len() > 32 {
+            return None;
+        }
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+    } else {
+        if digits.is_empty() {
+            return None;
+        }
+        for digit in digits.chars() {
+            let mut carry = digit.to_digit(10)?;
+            for byte in word.iter_mut().rev() {
+                let product = *byte as u32 * 10 + carry;
+                *byte = product as u8;
+                carry = product >> 8;
+            }
+            if carry != 0 {
+                return None;
+            }
+        }
+    }
+    Some((negative, word))
+}
+
+pub mod eip712 {
+    use crate::{
+        error::Error,
+        ethereum::{keccak256, parse_integer},
+    };
+    use serde::Deserialize;
+    use serde_json::{Map, Value};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    const DOMAIN_TYPE: &str = "EIP712Domain";
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    pub struct Field {
+        pub name: String,
+        #[serde(rename = "type")]
+        pub r#type: String,
+    }
+
+    /// EIP-712 typed data, as accepted by `eth_signTypedData_v4`.
+    #[derive(Debug, Clone, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct TypedData {
+        pub types: BTreeMap<String, Vec<Field>>,
+        pub primary_type: String,
+        pub domain: Map<String, Value>,
+        pub message: Map<String, Value>,
+    }
+
+    fn invalid(message: impl Into<String>) -> Error {
+        Error::InvalidTypedData(message.into())
+    }
+
+    impl TypedData {
+        /// `keccak256(0x1901 || domainSeparator || hashStruct(message))`
+        pub fn signing_hash(&self) -> Result<[u8; 32], Error> {
+            let mut data = vec![0x19, 0x01];
+            data.extend_from_slice(&self.domain_separator()?);
+            data.extend_from_slice(
+                &self.hash_struct(&self.primary_type, &Value::Object(self.message.clone()))?,
+            );
+            Ok(keccak256(&data))
+        }
+
+        pub fn domain_separator(&self) -> Result<[u8; 32], Error> {
+            self.hash_struct(DOMAIN_TYPE, &Value::Object(self.domain.clone()))
+        }
+
+        fn fields(&self, name: &str) -> Result<Vec<Field>, Error> {
+            if let Some(fields) = self.types.get(name) {
+                return Ok(fields.clone());
+            }
+            if name != DOMAIN_TYPE {
+                return Err(invalid(format!("unknown type '{}'", name)));
+            }
+            // Domain type may be omitted, it is then derived from the domain fields
+            let known = [
+                ("name", "string"),
+                ("version", "string"),
+                ("chainId", "uint256"),
+                ("verifyingContract", "address"),
+                ("salt", "bytes32"),
+            ];
+            Ok(known
+                .iter()
+                .filter(|(name, _)| self.domain.contains_key(*name))
+                .map(|(name, r#type)| Field {
+                    name: name.to_string(),
+                    r#type: r#type.to_string(),
+                })
+                .collect())
+        }
+
+        fn is_struct(&self, name: &str) -> bool {
+            self.types.contains_key(name) || name == DOMAIN_TYPE
+        }
+
+        fn dependencies(&self, name: &str, found: &mut BTreeSet<String>) -> Result<(), Error> {
+            if found.contains(name) || !self.is_struct(name) {
+                return Ok(());
+            }
+            found.insert(name.to_string());
+            for field in self.fields(name)? {
+                self.dependencies(base_type(&field.r#type), found)?;
+            }
+            Ok(())
+        }
+
+        pub fn encode_type(&self, name: &str) -> Result<String, Error> {
+            let mut dependencies = BTreeSet::new();
+            self.dependencies(name, &mut dependencies)?;
+            dependencies.remove(name);
+
+            let mut encoded = String::new();
+            for r#type in std::iter::once(name).chain(dependencies.iter().map(String::as_str)) {
+                let fields = self
+                    .fields(r#type)?
+                    .iter()
+                    .map(|field| format!("{} {}", field.r#type, field.name))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                encoded.push_str(&format!("{}({})", r#type, fields));
+            }
+            Ok(encoded)
+        }
+
+        pub fn hash_struct(&self, name: &str, value: &Value) -> Result<[u8; 32], Error> {
+            Ok(keccak256(&self.encode_data(name, value)?))
+        }
+
+        fn encode_data(&self, name: &str, value: &Value) -> Result<Vec<u8>, Error> {
+            let object = value
+                .as_object()
+                .ok_or_else(|| invalid(format!("expected object for '{}'", name)))?;
+
+            let mut encoded = keccak256(self.encode_type(name)?.as_bytes()).to_vec();
+            for field in self.fields(name)? {
+                let value = object.get(&field.name).unwrap_or(&Value::Null);
+                encoded.extend_from_slice(&self.encode_field(&field.r#type, value)?);
+            }
+            Ok(encoded)
+        }
+
+        fn encode_field(&self, r#type: &str, value: &Value) -> Result<[u8; 32], Error> {
+            if let Some((item_type, length)) = array_type(r#type) {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| invalid(format!("expected array for '{}'", r#type)))?;
+                if let Some(length) = length {
+                    let length: usize = length
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid array type '{}'", r#type)))?;
+                    if items.len() != length {
+                        return Err(invalid(format!(
+                            "expected {} items for '{}', got {}",
+                            length,
+                            r#type,
+                            items.len()
+                        )));
+                    }
+                }
+                let mut encoded = Vec::with_capacity(items.len() * 32);
+                for item in items {
+                    encoded.extend_from_slice(&self.encode_field(item_type, item)?);
+                }
+                return Ok(keccak256(&encoded));
+            }
+            if self.is_struct(r#type) {
+                return self.hash_struct(r#type, value);
+            }
+            encode_atomic(r#type, value)
+        }
+    }
+
+    /// Strips array suffixes, `Person[][2]` -> `Person`.
+    fn base_type(r#type: &str) -> &str {
+        r#type.split('[').next().unwrap_or(r#type)
+    }
+
+    /// Item type and length of an array type, `Person[][2]` -> `(Person[], Some("2"))`, without
+    /// length for dynamic arrays.
+    fn array_type(r#type: &str) -> Option<(&str, Option<&str>)> {
+        let (item_type, length) = r#type.strip_suffix(']')?.rsplit_once('[')?;
+        Some((item_type, (!length.is_empty()).then_some(length)))
+    }
+
+    fn encode_atomic(r#type: &str, value: &Value) -> Result<[u8; 32], Error> {
+        let type_error = || invalid(format!("invalid value for '{}': {}", r#type, value));
+        let mut word = [0u8; 32];
+        match r#type {
+            "string" => {
+                let str = value.as_str().ok_or_else(type_error)?;
+                Ok(keccak256(str.as_bytes()))
+            }
+            "bytes" => Ok(keccak256(&decode_hex(value).ok_or_else(type_error)?)),
+            "bool" => {
+                word[31] = value.as_bool().ok_or_else(type_error)? as u8;
+                Ok(word)
+            }
+            "address" => {
+                let address = value
+                    .as_str()
+                    .and_then(super::parse_address)
+                    .ok_or_else(type_error)?;
+                word[12..].copy_from_slice(&address);
+                Ok(word)
+            }
+            _ => {
+                if let Some(size) = r#type.strip_prefix("bytes") {
+                    let size: usize = size.parse().map_err(|_| type_error())?;
+                    let bytes = decode_hex(value).ok_or_else(type_error)?;
+                    if !(1..=32).contains(&size) || bytes.len() != size {
+                        return Err(type_error());
+                    }
+                    word[..size].copy_from_slice(&bytes);
+                    Ok(word)
+                } else if let Some(bits) = r#type.strip_prefix("uint") {
+                    let bits = integer_bits(bits).ok_or_else(type_error)?;
+                    let (negative, magnitude) = parse_integer(value).ok_or_else(type_error)?;
+                    if negative || !fits(&magnitude, bits) {
+                        return Err(type_error());
+                    }
+                    Ok(magnitude)
+                } else if let Some(bits) = r#type.strip_prefix("int") {
+                    let bits = integer_bits(bits).ok_or_else(type_error)?;
+                    let (negative, magnitude) = parse_integer(value).ok_or_else(type_error)?;
+                    if !fits_signed(&magnitude, negative, bits) {
+                        return Err(type_error());
+                    }
+                    Ok(if negative {
+                        twos_complement(magnitude)
+                    } else {
+                        magnitude
+                    })
+                } else {
+                    Err(invalid(format!("unknown type '{}'", r#type)))
+                }
+            }
+        }
+    }
+
+    fn integer_bits(bits: &str) -> Option<usize> {
+        let bits = if bits.is_empty() {
+            256
+        } else {
+            bits.parse().ok()?
+        };
+        (bits % 8 == 0 && (8..=256).contains(&bits)).then_some(bits)
+    }
+
+    fn decode_hex(value: &Value) -> Option<Vec<u8>> {
+        hex::decode(value.as_str()?.strip_prefix("0x")?).ok()
+    }
+
+    /// Checks that the magnitude is representable with `bits` bits.
+    fn fits(magnitude: &[u8; 32], bits: usize) -> bool {
+        let leading_zeros = magnitude
+            .iter()
+            .position(|byte| *byte != 0)
+            .map(|index| index * 8 + magnitude[index].leading_zeros() as usize)
+            .unwrap_or(256);
+        256 - leading_zeros <= bits
+    }
+
+    /// Checks that the value is within `-2^(bits-1)..2^(bits-1)`, the range of a signed
+    /// integer of `bits` bits.
+    fn fits_signed(magnitude: &[u8; 32], negative: bool, bits: usize) -> bool {
+        if negative && magnitude.iter().any(|byte| *byte != 0) {
+            fits(&decrement(*magnitude), bits - 1)
+        } else {
+            fits(magnitude, bits - 1)
+        }
+    }
+
+    fn decrement(mut word: [u8; 32]) -> [u8; 32] {
+        for byte in word.iter_mut().rev() {
+            let (difference, borrow) = byte.overflowing_sub(1);
+            *byte = difference;
+            if !borrow {
+                break;
+            }
+        }
+        word
+    }
+
+    fn twos_complement(mut word: [u8; 32]) -> [u8; 32] {
+        let mut carry = true;
+        for byte in word.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (sum, overflow) = byte.overflowing_add(1);
+                *byte = sum;
+                carry = overflow;
+            }
+        }
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::{address, eip712::TypedData, keccak256, to_checksum_address};
+    use k256::ecdsa::SigningKey;
+
+    #[test]
+    fn test_checksum_address() {
+        let mut raw = [0u8; 20];
+        hex::decode_to_slice("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", &mut raw).unwrap();
+        assert_eq!(
+            to_checksum_address(&raw),
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+    }
+
+    #[test]
+    fn test_address_from_private_key() {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let signing_key = SigningKey::from_slice(&secret).unwrap();
+        assert_eq!(
+            to_checksum_address(&address(signing_key.verifying_key())),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+    }
+
+    #[test]
+    fn test_eip712_mail_example() {
+        let typed_data: TypedData = serde_json::from_str(
+            r#"{
+                "types": {
+                    "EIP712Domain": [
+                        { "name": "name", "type": "string" },
+                        { "name": "version", "type": "string" },
+                        { "name": "chainId", "type": "uint256" },
+                        { "name": "verifyingContract", "type": "address" }
+                    ],
+                    "Person": [
+                        { "name": "name", "type": "string" },
+                        { "name": "wallet", "type": "address" }
+                    ],
+                    "Mail": [
+                        { "name": "from", "type": "Person" },
+                        { "name": "to", "type": "Person" },
+                        { "name": "contents", "type": "string" }
+                    ]
+                },
+                "primaryType": "Mail",
+                "domain": {
+                    "name": "Ether Mail",
+                    "version": "1",
+                    "chainId": 1,
+                    "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+                },
+                "message": {
+                    "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                    "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                    "contents": "Hello, Bob!"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            typed_data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            hex::encode(typed_data.domain_separator().unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(typed_data.signing_hash().unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        let signing_key = k256::ecdsa::SigningKey::from_slice(&keccak256(b"cow")).unwrap();
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(&typed_data.signing_hash().unwrap())
+            .unwrap();
+        assert_eq!(
+            hex::encode(signature.to_bytes()),
+            "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+             07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562"
+        );
+        assert_eq!(27 + recovery_id.to_byte(), 28);
+    }
+
+    /// Typed data with a single `Value` struct of one field of the given type.
+    fn single_field(r#type: &str) -> TypedData {
+        serde_json::from_value(serde_json::json!({
+            "types": { "Value": [{ "name": "value", "type": r#type }] },
+            "primaryType": "Value",
+            "domain": {},
+            "message": {}
+        }))
+        .unwrap()
+    }
+
+    fn value_hash(r#type: &str, value: serde_json::Value) -> Option<String> {
+        single_field(r#type)
+            .hash_struct("Value", &serde_json::json!({ "value": value }))
+            .ok()
+            .map(hex::encode)
+    }
+
+    /// Hash of the `Value` struct whose field is encoded as `word`.
+    fn expected_hash(r#type: &str, word: &str) -> String {
+        let mut encoded = keccak256(format!("Value({} value)", r#type).as_bytes()).to_vec();
+        encoded.extend_from_slice(&hex::decode(word).unwrap());
+        hex::encode(keccak256(&encoded))
+    }
+
+    #[test]
+    fn test_eip712_signed_integer_range() {
+        let min_int8 = format!("{}80", "ff".repeat(31));
+        assert_eq!(
+            value_hash("int8", serde_json::json!(-128)),
+            Some(expected_hash("int8", &min_int8))
+        );
+        assert_eq!(
+            value_hash("int8", serde_json::json!(127)),
+            Some(expected_hash("int8", &format!("{}7f", "00".repeat(31))))
+        );
+        assert_eq!(value_hash("int8", serde_json::json!(-129)), None);
+        assert_eq!(value_hash("int8", serde_json::json!(128)), None);
+
+        let min_int256 = format!("80{}", "00".repeat(31));
+        assert_eq!(
+            value_hash("int256", serde_json::json!(format!("-0x{}", min_int256))),
+            Some(expected_hash("int256", &min_int256))
+        );
+        assert_eq!(
+            value_hash("int256", serde_json::json!(format!("0x{}", min_int256))),
+            None
+        );
+        assert_eq!(
+            value_hash("int256", serde_json::json!("-0")),
+            Some(expected_hash("int256", &"00".repeat(32)))
+        );
+    }
+
+    #[test]
+    fn test_eip712_fixed_size_arrays() {
+        let items = |words: &[&str]| {
+            let encoded: Vec<u8> = words
+                .iter()
+                .flat_map(|word| hex::decode(word).unwrap())
+                .collect();
+            hex::encode(keccak256(&encoded))
+        };
+        let one = format!("{}01", "00".repeat(31));
+        let two = format!("{}02", "00".repeat(31));
+
+        assert_eq!(
+            value_hash("uint256[2]", serde_json::json!([1, 2])),
+            Some(expected_hash("uint256[2]", &items(&[&one, &two])))
+        );
+        assert_eq!(value_hash("uint256[2]", serde_json::json!([1])), None);
+        assert_eq!(value_hash("uint256[2]", serde_json::json!([1, 2, 3])), None);
+        assert!(value_hash("uint256[]", serde_json::json!([1, 2, 3])).is_some());
+        assert_eq!(value_hash("uint256[x]", serde_json::json!([1])), None);
+
+        // Lengths apply to their own dimension, `uint256[2][1]` is one array of two items
+        assert_eq!(
+            value_hash("uint256[2][1]", serde_json::json!([[1, 2]])),
+            Some(expected_hash(
+                "uint256[2][1]",
+                &items(&[&items(&[&one, &two])])
+            ))
+        );
+        assert_eq!(
+            value_hash("uint256[2][1]", serde_json::json!([[1], [2]])),
+            None
+        );
+    }
+}
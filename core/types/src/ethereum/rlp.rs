@@ -0,0 +1,81 @@
+//! Recursive Length Prefix encoding, as used by Ethereum transactions.
+
+pub enum Item {
+    Bytes(Vec<u8>),
+    List(Vec<Item>),
+}
+
+impl Item {
+    /// Unsigned integer encoded as big-endian bytes without leading zeros.
+    pub fn uint(bytes: &[u8]) -> Self {
+        let start = bytes
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(bytes.len());
+        Item::Bytes(bytes[start..].to_vec())
+    }
+
+    pub fn u64(value: u64) -> Self {
+        Item::uint(&value.to_be_bytes())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Item::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+            Item::Bytes(bytes) => {
+                encode_length(bytes.len(), 0x80, out);
+                out.extend_from_slice(bytes);
+            }
+            Item::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.encode_to(&mut payload);
+                }
+                encode_length(payload.len(), 0xc0, out);
+                out.extend_from_slice(&payload);
+            }
+        }
+    }
+}
+
+fn encode_length(length: usize, offset: u8, out: &mut Vec<u8>) {
+    if length < 56 {
+        out.push(offset + length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(0);
+        out.push(offset + 55 + (bytes.len() - start) as u8);
+        out.extend_from_slice(&bytes[start..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::rlp::Item;
+
+    #[test]
+    fn test_rlp_encoding() {
+        assert_eq!(Item::Bytes(b"dog".to_vec()).encode(), b"\x83dog");
+        assert_eq!(Item::u64(0).encode(), [0x80]);
+        assert_eq!(Item::u64(15).encode(), [0x0f]);
+        assert_eq!(Item::u64(1024).encode(), [0x82, 0x04, 0x00]);
+        assert_eq!(Item::List(vec![]).encode(), [0xc0]);
+        assert_eq!(
+            Item::List(vec![
+                Item::Bytes(b"cat".to_vec()),
+                Item::Bytes(b"dog".to_vec())
+            ])
+            .encode(),
+            b"\xc8\x83cat\x83dog"
+        );
+
+        let long = Item::Bytes(vec![b'a'; 56]).encode();
+        assert_eq!(&long[..2], [0xb8, 56]);
+    }
+}
@@ -0,0 +1,472 @@
+//! Legacy (EIP-155), EIP-2930 and EIP-1559 transactions.
+
+use crate::{
+    error::Error,
+    ethereum::{keccak256, parse_address, parse_integer, rlp::Item, Address},
+    user::SigningKey,
+};
+use serde::{de, Deserialize, Deserializer};
+
+/// Unsigned 256-bit integer, accepted as JSON number, decimal or `0x` prefixed hex string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quantity([u8; 32]);
+
+impl Quantity {
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+
+    fn to_u64(self) -> Option<u64> {
+        if self.0[..24].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        Some(u64::from_be_bytes(self.0[24..].try_into().ok()?))
+    }
+
+    fn rlp(&self) -> Item {
+        Item::uint(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        match parse_integer(&value) {
+            Some((false, word)) => Ok(Quantity(word)),
+            _ => Err(de::Error::custom(format!("invalid quantity: {}", value))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AccessListItem {
+    pub address: String,
+    #[serde(default)]
+    pub storage_keys: Vec<String>,
+}
+
+/// Transaction as submitted by the client, validated into a `Transaction` before signing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TransactionRequest {
+    #[serde(rename = "type")]
+    pub tx_type: Option<u8>,
+    pub chain_id: Option<Quantity>,
+    pub nonce: Quantity,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub value: Quantity,
+    pub data: Option<String>,
+    pub gas_limit: Quantity,
+    pub gas_price: Option<Quantity>,
+    pub max_fee_per_gas: Option<Quantity>,
+    pub max_priority_fee_per_gas: Option<Quantity>,
+    pub access_list: Option<Vec<AccessListItem>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionType {
+    Legacy,
+    Eip2930,
+    Eip1559,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Fees {
+    GasPrice(Quantity),
+    Dynamic {
+        max_priority_fee_per_gas: Quantity,
+        max_fee_per_gas: Quantity,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    tx_type: TransactionType,
+    chain_id: u64,
+    nonce: Quantity,
+    to: Option<Address>,
+    value: Quantity,
+    data: Vec<u8>,
+    gas_limit: Quantity,
+    fees: Fees,
+    access_list: Vec<(Address, Vec<[u8; 32]>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    pub raw: Vec<u8>,
+    pub hash: [u8; 32],
+}
+
+/// Largest chain id whose EIP-155 `v` (`chain_id * 2 + 35 + y_parity`) fits in 64 bits.
+const MAX_LEGACY_CHAIN_ID: u64 = (u64::MAX - 36) / 2;
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidTransaction(message.into())
+}
+
+impl TryFrom<TransactionRequest> for Transaction {
+    type Error = Error;
+
+    fn try_from(request: TransactionRequest) -> Result<Self, Self::Error> {
+        let tx_type = match request.tx_type {
+            Some(0) => TransactionType::Legacy,
+            Some(1) => TransactionType::Eip2930,
+            Some(2) => TransactionType::Eip1559,
+            Some(other) => return Err(invalid(format!("unsupported type {}", other))),
+            None if request.max_fee_per_gas.is_some() => TransactionType::Eip1559,
+            None if request.access_list.is_some() => TransactionType::Eip2930,
+            None => TransactionType::Legacy,
+        };
+
+        // Chain id is always required, legacy transactions are signed with EIP-155
+        let chain_id = request
+            .chain_id
+            .ok_or_else(|| invalid("chain_id is required"))?
+            .to_u64()
+            .filter(|chain_id| *chain_id != 0)
+            .ok_or_else(|| invalid("chain_id must be a positive 64-bit integer"))?;
+        if tx_type == TransactionType::Legacy && chain_id > MAX_LEGACY_CHAIN_ID {
+            return Err(invalid(format!(
+                "chain_id of legacy transactions must not exceed {}",
+                MAX_LEGACY_CHAIN_ID
+            )));
+        }
+
+        let fees = match tx_type {
+            TransactionType::Legacy | TransactionType::Eip2930 => {
+                if request.max_fee_per_gas.is_some() || request.max_priority_fee_per_gas.is_some() {
+                    return Err(invalid("max fees are only allowed in type 2 transactions"));
+                }
+                Fees::GasPrice(
+                    request
+                        .gas_price
+                        .ok_or_else(|| invalid("gas_price is required"))?,
+                )
+            }
+            TransactionType::Eip1559 => {
+                if request.gas_price.is_some() {
+                    return Err(invalid("gas_price is not allowed in type 2 transactions"));
+                }
+                let max_fee_per_gas = request
+                    .max_fee_per_gas
+                    .ok_or_else(|| invalid("max_fee_per_gas is required"))?;
+                let max_priority_fee_per_gas = request
+                    .max_priority_fee_per_gas
+                    .ok_or_else(|| invalid("max_priority_fee_per_gas is required"))?;
+                if max_priority_fee_per_gas.0 > max_fee_per_gas.0 {
+                    return Err(invalid(
+                        "max_priority_fee_per_gas must not exceed max_fee_per_gas",
+                    ));
+                }
+                Fees::Dynamic {
+                    max_priority_fee_per_gas,
+                    max_fee_per_gas,
+                }
+            }
+        };
+
+        if request.gas_limit.is_zero() {
+            return Err(invalid("gas_limit must be positive"));
+        }
+
+        let to = request
+            .to
+            .as_deref()
+            .map(|to| parse_address(to).ok_or_else(|| invalid(format!("invalid to: {}", to))))
+            .transpose()?;
+
+        let data = match request.data.as_deref() {
+            Some(data) => decode_hex(data).ok_or_else(|| invalid("invalid data"))?,
+            None => Vec::new(),
+        };
+        if to.is_none() && data.is_empty() {
+            return Err(invalid("contract creation requires data"));
+        }
+
+        if tx_type == TransactionType::Legacy && request.access_list.is_some() {
+            return Err(invalid("access_list is not allowed in legacy transactions"));
+        }
+        let access_list = request
+            .access_list
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| {
+                let address = parse_address(&item.address).ok_or_else(|| {
+                    invalid(format!("invalid access list address: {}", item.address))
+                })?;
+                let storage_keys = item
+                    .storage_keys
+                    .iter()
+                    .map(|key| {
+                        decode_hex(key)
+                            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                            .ok_or_else(|| invalid(format!("invalid storage key: {}", key)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((address, storage_keys))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Transaction {
+            tx_type,
+            chain_id,
+            nonce: request.nonce,
+            to,
+            value: request.value,
+            data,
+            gas_limit: request.gas_limit,
+            fees,
+            access_list,
+        })
+    }
+}
+
+fn decode_hex(str: &str) -> Option<Vec<u8>> {
+    hex::decode(str.strip_prefix("0x")?).ok()
+}
+
+impl Transaction {
+    pub fn tx_type(&self) -> TransactionType {
+        self.tx_type
+    }
+
+    fn fields(&self) -> Vec<Item> {
+        let to = Item::Bytes(self.to.map(|to| to.to_vec()).unwrap_or_default());
+        let access_list = Item::List(
+            self.access_list
+                .iter()
+                .map(|(address, keys)| {
+                    Item::List(vec![
+                        Item::Bytes(address.to_vec()),
+                        Item::List(keys.iter().map(|key| Item::Bytes(key.to_vec())).collect()),
+                    ])
+                })
+                .collect(),
+        );
+
+        match (&self.tx_type, &self.fees) {
+            (TransactionType::Legacy, Fees::GasPrice(gas_price)) => vec![
+                self.nonce.rlp(),
+                gas_price.rlp(),
+                self.gas_limit.rlp(),
+                to,
+                self.value.rlp(),
+                Item::Bytes(self.data.clone()),
+            ],
+            (TransactionType::Eip2930, Fees::GasPrice(gas_price)) => vec![
+                Item::u64(self.chain_id),
+                self.nonce.rlp(),
+                gas_price.rlp(),
+                self.gas_limit.rlp(),
+                to,
+                self.value.rlp(),
+                Item::Bytes(self.data.clone()),
+                access_list,
+            ],
+            (
+                _,
+                Fees::Dynamic {
+                    max_priority_fee_per_gas,
+                    max_fee_per_gas,
+                },
+            ) => vec![
+                Item::u64(self.chain_id),
+                self.nonce.rlp(),
+                max_priority_fee_per_gas.rlp(),
+                max_fee_per_gas.rlp(),
+                self.gas_limit.rlp(),
+                to,
+                self.value.rlp(),
+                Item::Bytes(self.data.clone()),
+                access_list,
+            ],
+            (_, Fees::GasPrice(_)) => unreachable!("type 2 transactions always have dynamic fees"),
+        }
+    }
+
+    /// Prepends the EIP-2718 type byte to typed transactions.
+    fn envelope(&self, fields: Vec<Item>) -> Vec<u8> {
+        let payload = Item::List(fields).encode();
+        match self.tx_type {
+            TransactionType::Legacy => payload,
+            TransactionType::Eip2930 => [&[0x01], payload.as_slice()].concat(),
+            TransactionType::Eip1559 => [&[0x02], payload.as_slice()].concat(),
+        }
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = self.fields();
+        if self.tx_type == TransactionType::Legacy {
+            // EIP-155 replay protection
+            fields.extend([Item::u64(self.chain_id), Item::u64(0), Item::u64(0)]);
+        }
+        keccak256(&self.envelope(fields))
+    }
+
+    pub fn sign(&self, signing_key: &SigningKey) -> Result<SignedTransaction, Error> {
+        let signature = signing_key.sign_ethereum_hash(&self.signing_hash())?;
+        let y_parity = (signature[64] - 27) as u64;
+        let v = match self.tx_type {
+            // Bounded by MAX_LEGACY_CHAIN_ID
+            TransactionType::Legacy => self.chain_id * 2 + 35 + y_parity,
+            TransactionType::Eip2930 | TransactionType::Eip1559 => y_parity,
+        };
+
+        let mut fields = self.fields();
+        fields.extend([
+            Item::u64(v),
+            Item::uint(&signature[..32]),
+            Item::uint(&signature[32..64]),
+        ]);
+        let raw = self.envelope(fields);
+        let hash = keccak256(&raw);
+        Ok(SignedTransaction { raw, hash })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        ethereum::transaction::{Transaction, TransactionRequest, TransactionType},
+        user::SigningKey,
+    };
+
+    fn request(json: &str) -> TransactionRequest {
+        serde_json::from_str(json).expect("valid request")
+    }
+
+    #[test]
+    fn test_eip155_example() {
+        let signing_key = SigningKey::Secp256k1(
+            k256::ecdsa::SigningKey::from_slice(&[0x46; 32]).expect("private key"),
+        );
+        let transaction = Transaction::try_from(request(
+            r#"{
+                "chain_id": 1,
+                "nonce": 9,
+                "gas_price": "20000000000",
+                "gas_limit": 21000,
+                "to": "0x3535353535353535353535353535353535353535",
+                "value": "1000000000000000000"
+            }"#,
+        ))
+        .expect("valid transaction");
+
+        assert_eq!(transaction.tx_type(), TransactionType::Legacy);
+        assert_eq!(
+            hex::encode(transaction.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        let signed = transaction.sign(&signing_key).expect("signing failed");
+        assert_eq!(
+            hex::encode(signed.raw),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d899\
+             7f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+    }
+
+    #[test]
+    fn test_typed_transactions_are_enveloped() {
+        let signing_key = SigningKey::Secp256k1(
+            k256::ecdsa::SigningKey::from_slice(&[0x46; 32]).expect("private key"),
+        );
+        let eip1559 = Transaction::try_from(request(
+            r#"{
+                "chain_id": 1,
+                "nonce": "0x0",
+                "max_fee_per_gas": "0x3b9aca00",
+                "max_priority_fee_per_gas": "0x3b9aca00",
+                "gas_limit": 21000,
+                "to": "0x3535353535353535353535353535353535353535",
+                "value": 1,
+                "access_list": []
+            }"#,
+        ))
+        .expect("valid transaction");
+        assert_eq!(eip1559.tx_type(), TransactionType::Eip1559);
+        assert_eq!(
+            eip1559.sign(&signing_key).expect("signing failed").raw[0],
+            0x02
+        );
+
+        let eip2930 = Transaction::try_from(request(
+            r#"{
+                "chain_id": 1,
+                "nonce": 0,
+                "gas_price": 1,
+                "gas_limit": 21000,
+                "to": "0x3535353535353535353535353535353535353535",
+                "access_list": [{
+                    "address": "0x3535353535353535353535353535353535353535",
+                    "storage_keys": ["0x0000000000000000000000000000000000000000000000000000000000000001"]
+                }]
+            }"#,
+        ))
+        .expect("valid transaction");
+        assert_eq!(eip2930.tx_type(), TransactionType::Eip2930);
+        assert_eq!(
+            eip2930.sign(&signing_key).expect("signing failed").raw[0],
+            0x01
+        );
+    }
+
+    #[test]
+    fn test_invalid_transactions_are_rejected() {
+        let missing_chain_id = Transaction::try_from(request(
+            r#"{ "nonce": 0, "gas_price": 1, "gas_limit": 21000, "to": "0x3535353535353535353535353535353535353535" }"#,
+        ));
+        assert!(matches!(
+            missing_chain_id,
+            Err(Error::InvalidTransaction(_))
+        ));
+
+        let priority_above_max = Transaction::try_from(request(
+            r#"{
+                "chain_id": 1, "nonce": 0, "gas_limit": 21000,
+                "max_fee_per_gas": 1, "max_priority_fee_per_gas": 2,
+                "to": "0x3535353535353535353535353535353535353535"
+            }"#,
+        ));
+        assert!(matches!(
+            priority_above_max,
+            Err(Error::InvalidTransaction(_))
+        ));
+
+        // EIP-155 `v` would overflow
+        let legacy_chain_id_too_large = Transaction::try_from(request(
+            r#"{
+                "chain_id": "0x7fffffffffffffee", "nonce": 0, "gas_price": 1, "gas_limit": 21000,
+                "to": "0x3535353535353535353535353535353535353535"
+            }"#,
+        ));
+        assert!(matches!(
+            legacy_chain_id_too_large,
+            Err(Error::InvalidTransaction(_))
+        ));
+        let legacy_max_chain_id = Transaction::try_from(request(
+            r#"{
+                "chain_id": "0x7fffffffffffffed", "nonce": 0, "gas_price": 1, "gas_limit": 21000,
+                "to": "0x3535353535353535353535353535353535353535"
+            }"#,
+        ))
+        .expect("valid transaction");
+        let signing_key = SigningKey::Secp256k1(
+            k256::ecdsa::SigningKey::from_slice(&[0x46; 32]).expect("private key"),
+        );
+        assert!(legacy_max_chain_id.sign(&signing_key).is_ok());
+        let eip1559_large_chain_id = Transaction::try_from(request(
+            r#"{
+                "chain_id": "0x7fffffffffffffee", "nonce": 0, "gas_limit": 21000,
+                "max_fee_per_gas": 1, "max_priority_fee_per_gas": 1,
+                "to": "0x3535353535353535353535353535353535353535"
+            }"#,
+        ));
+        assert!(eip1559_large_chain_id.is_ok());
+    }
+}
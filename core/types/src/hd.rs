@@ -0,0 +1,112 @@
+use crate::error::Error;
+use bip32::{DerivationPath, XPrv};
+use bip39::Mnemonic;
+use serde::Serialize;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// BIP-44 path of the first Ethereum account, used as the wallet default key.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// BIP-44 path of the Ethereum account with the given index.
+pub fn account_path(index: u32) -> Result<String, Error> {
+    // Address indexes are not hardened, the top bit is reserved for hardened derivation
+    if index >= 1 << 31 {
+        return Err(Error::InvalidDerivationPath(index.to_string()));
+    }
+    Ok(format!("m/44'/60'/0'/0/{}", index))
+}
+
+/// Account derived from an HD wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Account {
+    #[sqlx(rename = "account_index", try_from = "i32")]
+    pub index: u32,
+    pub path: String,
+    pub address: String,
+}
+
+/// Hierarchical deterministic wallet backed by a BIP-39 mnemonic.
+///
+/// The key at [`DEFAULT_DERIVATION_PATH`] is derived once and used whenever no explicit
+/// path is requested.
+pub struct HdWallet {
+    mnemonic: Mnemonic,
+    default_key: k256::ecdsa::SigningKey,
+}
+
+impl HdWallet {
+    pub fn generate() -> Result<Self, Error> {
+        let mnemonic = Mnemonic::generate(24).map_err(|_| Error::SigningFailed)?;
+        Self::from_mnemonic(mnemonic)
+    }
+
+    pub fn from_phrase(phrase: &str) -> Result<Self, Error> {
+        let mnemonic =
+            Mnemonic::parse(phrase).map_err(|err| Error::InvalidMnemonic(err.to_string()))?;
+        Self::from_mnemonic(mnemonic)
+    }
+
+    fn from_mnemonic(mnemonic: Mnemonic) -> Result<Self, Error> {
+        let default_key = derive(&mnemonic, DEFAULT_DERIVATION_PATH)?;
+        Ok(HdWallet {
+            mnemonic,
+            default_key,
+        })
+    }
+
+    pub fn phrase(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.mnemonic.to_string())
+    }
+
+    pub fn default_key(&self) -> &k256::ecdsa::SigningKey {
+        &self.default_key
+    }
+
+    /// Derives the secp256k1 key at the given BIP-32 path.
+    pub fn derive(&self, path: &str) -> Result<k256::ecdsa::SigningKey, Error> {
+        derive(&self.mnemonic, path)
+    }
+}
+
+fn derive(mnemonic: &Mnemonic, path: &str) -> Result<k256::ecdsa::SigningKey, Error> {
+    let derivation_path = DerivationPath::from_str(path)
+        .map_err(|_| Error::InvalidDerivationPath(path.to_string()))?;
+    let seed = Zeroizing::new(mnemonic.to_seed(""));
+    let xprv = XPrv::derive_from_path(seed.as_slice(), &derivation_path)
+        .map_err(|_| Error::InvalidDerivationPath(path.to_string()))?;
+    Ok(xprv.private_key().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ethereum,
+        hd::{account_path, HdWallet, DEFAULT_DERIVATION_PATH},
+    };
+
+    #[test]
+    fn test_bip44_derivation() {
+        // Well-known test mnemonic, addresses as derived by common Ethereum wallets
+        let wallet =
+            HdWallet::from_phrase("test test test test test test test test test test test junk")
+                .expect("valid mnemonic");
+
+        let first = wallet.derive(DEFAULT_DERIVATION_PATH).expect("derivation");
+        assert_eq!(
+            ethereum::to_checksum_address(&ethereum::address(first.verifying_key())),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+        );
+
+        let second = wallet
+            .derive(&account_path(1).expect("valid index"))
+            .expect("derivation");
+        assert_eq!(
+            ethereum::to_checksum_address(&ethereum::address(second.verifying_key())),
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+        );
+
+        assert!(wallet.derive("m/not-a-path").is_err());
+        assert!(account_path(1 << 31).is_err());
+    }
+}
@@ -1,9 +1,22 @@
 pub mod api_key;
+pub mod certificate;
 pub mod client;
+pub mod client_key;
 pub mod config;
+pub mod dashboard;
 pub mod db;
+pub mod encoding;
 pub mod encrypt;
 pub mod env;
 pub mod error;
+pub mod ethereum;
+pub mod hd;
+pub mod lockout;
+pub mod network;
+pub mod operator;
+pub mod password;
+pub mod request_signature;
+pub mod scope;
 pub mod secret;
+pub mod tenant;
 pub mod user;
@@ -0,0 +1,95 @@
+use crate::{api_key::ApiKey, secret::mask::Masked};
+use serde::{Deserialize, Serialize};
+
+/// Progressive lockout of credentials after consecutive authentication failures, e.g.
+/// `AUTH__LOCKOUT__THRESHOLD=5`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LockoutPolicy {
+    /// Consecutive failures locking the credentials out, `0` to never lock them out.
+    #[serde(default = "default_threshold")]
+    pub threshold: u32,
+    /// Seconds of the first lockout, doubled by every further failure.
+    #[serde(default = "default_duration")]
+    pub duration: u64,
+    /// Longest lockout in seconds.
+    #[serde(default = "default_max_duration")]
+    pub max_duration: u64,
+}
+
+fn default_threshold() -> u32 {
+    5
+}
+
+fn default_duration() -> u64 {
+    60
+}
+
+fn default_max_duration() -> u64 {
+    3600
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            duration: default_duration(),
+            max_duration: default_max_duration(),
+        }
+    }
+}
+
+impl LockoutPolicy {
+    /// Seconds credentials are locked out for after `failures` consecutive failures, `None`
+    /// below the threshold.
+    pub fn lock_duration(&self, failures: u32) -> Option<u64> {
+        if self.threshold == 0 || failures < self.threshold {
+            return None;
+        }
+        let factor = 2u64
+            .checked_pow(failures - self.threshold)
+            .unwrap_or(u64::MAX);
+        Some(self.duration.saturating_mul(factor).min(self.max_duration))
+    }
+}
+
+/// Authentication failures of credentials since they last authenticated.
+///
+/// Timestamps are Unix seconds.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Lockout {
+    pub api_key: Masked<ApiKey>,
+    pub failures: i32,
+    pub last_failure_at: i64,
+    pub locked_until: Option<i64>,
+}
+
+impl Lockout {
+    /// Seconds until the credentials may authenticate again, `None` if they are not locked out.
+    pub fn retry_after(&self, now: i64) -> Option<u64> {
+        self.locked_until
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| (locked_until - now) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_duration() {
+        let policy = LockoutPolicy::default();
+        assert_eq!(policy.lock_duration(4), None);
+        assert_eq!(policy.lock_duration(5), Some(60));
+        assert_eq!(policy.lock_duration(6), Some(120));
+        assert_eq!(policy.lock_duration(10), Some(1920));
+        assert_eq!(policy.lock_duration(11), Some(3600));
+        assert_eq!(policy.lock_duration(200), Some(3600));
+
+        let disabled = LockoutPolicy {
+            threshold: 0,
+            ..LockoutPolicy::default()
+        };
+        assert_eq!(disabled.lock_duration(100), None);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// IPv4 or IPv6 network in CIDR notation, e.g. `10.0.0.0/8`. A bare address is the network of
+/// that address alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, canonical(address)) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => network == mask_v4(address, self.prefix),
+            (IpAddr::V6(network), IpAddr::V6(address)) => network == mask_v6(address, self.prefix),
+            _ => false,
+        }
+    }
+}
+
+fn mask_v4(address: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+    Ipv4Addr::from(u32::from(address) & mask)
+}
+
+fn mask_v6(address: Ipv6Addr, prefix: u8) -> Ipv6Addr {
+    let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+    Ipv6Addr::from(u128::from(address) & mask)
+}
+
+/// IPv4 addresses mapped to IPv6 by dual-stack sockets, `::ffff:10.0.0.1`, as IPv4 addresses.
+fn canonical(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+        IpAddr::V4(_) => address,
+    }
+}
+
+impl Display for IpNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = Error;
+
+    /// Clears the host bits of the address, `10.1.2.3/8` is `10.0.0.0/8`.
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidNetwork(str.to_string());
+        let (address, prefix) = match str.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (str.trim(), None),
+        };
+        let address = canonical(IpAddr::from_str(address).map_err(|_| invalid())?);
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(invalid());
+        }
+        let address = match address {
+            IpAddr::V4(v4) => IpAddr::V4(mask_v4(v4, prefix)),
+            IpAddr::V6(v6) => IpAddr::V6(mask_v6(v6, prefix)),
+        };
+        Ok(IpNetwork { address, prefix })
+    }
+}
+
+impl TryFrom<String> for IpNetwork {
+    type Error = Error;
+
+    fn try_from(str: String) -> Result<Self, Self::Error> {
+        IpNetwork::from_str(&str)
+    }
+}
+
+impl From<IpNetwork> for String {
+    fn from(network: IpNetwork) -> Self {
+        network.to_string()
+    }
+}
+
+/// Address of the client of a request received from `peer`.
+///
+/// `X-Forwarded-For` is only trusted as far as it was appended by `trusted_proxies`: starting
+/// from the peer, each hop that is a trusted proxy hands over to the address it forwarded for,
+/// from the last entry of the header to the first. The first untrusted hop is the client.
+/// Malformed entries stop the walk at the proxy that appended them.
+pub fn client_address(
+    peer: IpAddr,
+    forwarded_for: &[&str],
+    trusted_proxies: &[IpNetwork],
+) -> IpAddr {
+    let trusted = |address: IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(address));
+    let hops: Vec<&str> = forwarded_for
+        .iter()
+        .flat_map(|header| header.split(','))
+        .map(str::trim)
+        .collect();
+
+    let mut client = canonical(peer);
+    for hop in hops.into_iter().rev() {
+        if !trusted(client) {
+            break;
+        }
+        // Some proxies append the port, `[::1]:8080` for IPv6
+        match IpAddr::from_str(hop).or_else(|_| SocketAddr::from_str(hop).map(|addr| addr.ip())) {
+            Ok(address) => client = canonical(address),
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+pub mod postgres {
+    use crate::network::IpNetwork;
+    use sqlx::{
+        encode::IsNull,
+        postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+    use std::{error::Error, str::FromStr};
+
+    impl Type<Postgres> for IpNetwork {
+        fn type_info() -> PgTypeInfo {
+            <String as Type<Postgres>>::type_info()
+        }
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <String as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl PgHasArrayType for IpNetwork {
+        fn array_type_info() -> PgTypeInfo {
+            <String as PgHasArrayType>::array_type_info()
+        }
+        fn array_compatible(ty: &PgTypeInfo) -> bool {
+            <String as PgHasArrayType>::array_compatible(ty)
+        }
+    }
+
+    impl<'q> Encode<'q, Postgres> for IpNetwork {
+        fn encode_by_ref(
+            &self,
+            buf: &mut PgArgumentBuffer,
+        ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+            <String as Encode<Postgres>>::encode_by_ref(&self.to_string(), buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for IpNetwork {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+            let str = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(IpNetwork::from_str(str)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(str: &str) -> IpNetwork {
+        IpNetwork::from_str(str).expect("network")
+    }
+
+    fn address(str: &str) -> IpAddr {
+        IpAddr::from_str(str).expect("address")
+    }
+
+    #[test]
+    fn test_network_contains() {
+        let private = network("10.1.2.3/8");
+        assert_eq!(private.to_string(), "10.0.0.0/8");
+        assert!(private.contains(address("10.255.0.1")));
+        assert!(private.contains(address("::ffff:10.0.0.1")));
+        assert!(!private.contains(address("11.0.0.1")));
+
+        assert!(network("192.168.1.7").contains(address("192.168.1.7")));
+        assert!(!network("192.168.1.7").contains(address("192.168.1.8")));
+        assert!(network("0.0.0.0/0").contains(address("203.0.113.9")));
+        assert!(!network("0.0.0.0/0").contains(address("2001:db8::1")));
+        assert!(network("2001:db8::/32").contains(address("2001:db8:ffff::1")));
+        assert!(!network("2001:db8::/32").contains(address("2001:db9::1")));
+    }
+
+    #[test]
+    fn test_invalid_networks() {
+        for invalid in [
+            "10.0.0.0/33",
+            "2001:db8::/129",
+            "10.0.0/8",
+            "10.0.0.0/",
+            "any",
+        ] {
+            assert_eq!(
+                IpNetwork::from_str(invalid),
+                Err(Error::InvalidNetwork(invalid.to_string()))
+            );
+        }
+        let networks: Vec<IpNetwork> =
+            serde_json::from_str(r#"["10.0.0.0/8", "2001:db8::1"]"#).expect("networks");
+        assert_eq!(networks[1].to_string(), "2001:db8::1/128");
+    }
+
+    #[test]
+    fn test_client_address() {
+        let proxies = [network("10.0.0.0/8")];
+        let client = address("203.0.113.9");
+
+        // Without a trusted proxy in front, the header is whatever the client sent
+        assert_eq!(client_address(client, &["198.51.100.1"], &proxies), client);
+        assert_eq!(
+            client_address(address("10.0.0.2"), &["203.0.113.9"], &proxies),
+            client
+        );
+        // Entries prepended by the client are skipped, chained proxies are followed
+        assert_eq!(
+            client_address(
+                address("10.0.0.2"),
+                &["198.51.100.1, 203.0.113.9", "10.0.0.1"],
+                &proxies
+            ),
+            client
+        );
+        assert_eq!(
+            client_address(address("10.0.0.2"), &["[2001:db8::1]:4711"], &proxies),
+            address("2001:db8::1")
+        );
+        assert_eq!(
+            client_address(address("10.0.0.2"), &["203.0.113.9, garbage"], &proxies),
+            address("10.0.0.2")
+        );
+        assert_eq!(
+            client_address(address("10.0.0.2"), &[], &proxies),
+            address("10.0.0.2")
+        );
+        assert_eq!(
+            client_address(address("10.0.0.2"), &["203.0.113.9"], &[]),
+            address("10.0.0.2")
+        );
+    }
+}
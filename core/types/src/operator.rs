@@ -0,0 +1,130 @@
+use crate::{error::Error, password};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+use uuid::Uuid;
+
+/// Role of an operator of the admin API, each granting the permissions of the roles before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads clients and key rotation progress.
+    Viewer,
+    /// Manages clients and their credentials.
+    Operator,
+    /// Manages operators and master keys, and deletes clients.
+    Superadmin,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Viewer => f.write_str("viewer"),
+            Role::Operator => f.write_str("operator"),
+            Role::Superadmin => f.write_str("superadmin"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "superadmin" => Ok(Role::Superadmin),
+            other => Err(Error::UnknownRole(other.to_string())),
+        }
+    }
+}
+
+/// Account of a person operating the admin API.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Operator {
+    pub id: Uuid,
+    pub username: String,
+    pub role: Role,
+    password_hash: String,
+}
+
+impl Operator {
+    pub fn new(username: String, password: &str, role: Role) -> Result<Self, Error> {
+        Ok(Operator {
+            id: Uuid::new_v4(),
+            username,
+            role,
+            password_hash: password::hash_password(password)?,
+        })
+    }
+
+    pub fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    /// Checks the password of `operator`, `None` if no operator has the username.
+    pub fn login(operator: Option<&Operator>, password: &str) -> Result<(), Error> {
+        password::verify_password(operator.map(Operator::password_hash), password)
+    }
+}
+
+pub mod postgres {
+    use crate::operator::Role;
+    use sqlx::{
+        encode::IsNull,
+        postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+    use std::{error::Error, str::FromStr};
+
+    impl Type<Postgres> for Role {
+        fn type_info() -> PgTypeInfo {
+            <String as Type<Postgres>>::type_info()
+        }
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <String as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl<'q> Encode<'q, Postgres> for Role {
+        fn encode_by_ref(
+            &self,
+            buf: &mut PgArgumentBuffer,
+        ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+            <String as Encode<Postgres>>::encode_by_ref(&self.to_string(), buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for Role {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+            let str = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(Role::from_str(str)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_are_ordered() {
+        assert!(Role::Viewer < Role::Operator);
+        assert!(Role::Operator < Role::Superadmin);
+        assert_eq!(Role::from_str("operator"), Ok(Role::Operator));
+        assert!(Role::from_str("root").is_err());
+    }
+
+    #[test]
+    fn test_operator_login() {
+        let operator =
+            Operator::new("alice".to_string(), "correct horse battery", Role::Viewer).expect("new");
+        assert_eq!(
+            Operator::login(Some(&operator), "correct horse battery"),
+            Ok(())
+        );
+        assert_eq!(
+            Operator::login(Some(&operator), "wrong"),
+            Err(Error::InvalidLogin)
+        );
+    }
+}
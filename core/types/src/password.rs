@@ -0,0 +1,71 @@
+use crate::error::Error;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use std::sync::LazyLock;
+
+/// Shortest password accepted for new accounts.
+pub const MIN_PASSWORD_LEN: usize = 12;
+
+/// Checked instead of a missing account's hash, so that both cases take as long.
+static UNKNOWN_ACCOUNT_HASH: LazyLock<String> = LazyLock::new(|| {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(salt.as_str().as_bytes(), &salt)
+        .expect("hash password")
+        .to_string()
+});
+
+/// Hashes a new password with Argon2id, into a PHC string carrying its salt and parameters.
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(Error::WeakPassword(MIN_PASSWORD_LEN));
+    }
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| Error::PasswordHash(err.to_string()))?;
+    Ok(hash.to_string())
+}
+
+/// Checks `password` against `password_hash`, `None` when the account does not exist.
+pub fn verify_password(password_hash: Option<&str>, password: &str) -> Result<(), Error> {
+    let hash = PasswordHash::new(password_hash.unwrap_or(&UNKNOWN_ACCOUNT_HASH))
+        .map_err(|err| Error::PasswordHash(err.to_string()))?;
+    let verified = Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok();
+    if verified && password_hash.is_some() {
+        Ok(())
+    } else {
+        Err(Error::InvalidLogin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_password() {
+        let hash = hash_password("correct horse battery").expect("hash");
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(
+            verify_password(Some(&hash), "correct horse battery"),
+            Ok(())
+        );
+        assert_eq!(
+            verify_password(Some(&hash), "wrong horse battery"),
+            Err(Error::InvalidLogin)
+        );
+        assert_eq!(
+            verify_password(None, "correct horse battery"),
+            Err(Error::InvalidLogin)
+        );
+        assert_eq!(
+            hash_password("short"),
+            Err(Error::WeakPassword(MIN_PASSWORD_LEN))
+        );
+    }
+}
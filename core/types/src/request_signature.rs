@@ -0,0 +1,277 @@
+use crate::error::Error;
+use base64::{
+    alphabet,
+    engine::{general_purpose::GeneralPurpose, DecodePaddingMode, GeneralPurposeConfig},
+    Engine,
+};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+pub const SIGNATURE_VERSION_HEADER: &str = "x-signature-version";
+pub const SIGNED_HEADERS_HEADER: &str = "x-signed-headers";
+pub const CONTENT_SHA256_HEADER: &str = "x-content-sha256";
+const NONCE_HEADER: &str = "x-nonce";
+
+/// Headers every v2 signature covers, besides `x-nonce` when it is sent.
+pub const REQUIRED_SIGNED_HEADERS: [&str; 3] = ["x-api-key", CONTENT_SHA256_HEADER, "x-timestamp"];
+
+const DECODE_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD_INDIFFERENT: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::STANDARD, DECODE_INDIFFERENT);
+const URL_SAFE_INDIFFERENT: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::URL_SAFE, DECODE_INDIFFERENT);
+
+/// Version of the message clients sign, selected by the `x-signature-version` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureVersion {
+    /// Bare concatenation of the request parts, see [`message_v1`].
+    #[default]
+    V1,
+    /// Delimited canonical request with signed headers, see [`canonical_request`].
+    V2,
+}
+
+impl FromStr for SignatureVersion {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str.trim() {
+            "1" => Ok(SignatureVersion::V1),
+            "2" => Ok(SignatureVersion::V2),
+            other => Err(Error::UnsupportedSignatureVersion(other.to_string())),
+        }
+    }
+}
+
+/// Message of v1 signatures: `{timestamp}{method}{path}{query}{body}`, or
+/// `{timestamp}\n{nonce}\n{method}{path}{query}{body}` with a nonce.
+///
+/// The nonce is delimited by newlines, which header values cannot contain, so that a nonce
+/// starting with digits cannot be read as part of the timestamp. Bodies that are not UTF-8 are
+/// left out, as they always were.
+pub fn message_v1(
+    timestamp: u64,
+    nonce: Option<&str>,
+    method: &str,
+    path: &str,
+    query: &str,
+    body: &[u8],
+) -> String {
+    let nonce = nonce.map(|nonce| format!("\n{}\n", nonce));
+    format!(
+        "{}{}{}{}{}{}",
+        timestamp,
+        nonce.unwrap_or_default(),
+        method,
+        path,
+        query,
+        std::str::from_utf8(body).unwrap_or_default()
+    )
+}
+
+/// Parses the `x-signed-headers` list: lowercase names separated by `;`.
+///
+/// Returns the names sorted, once each, after checking the required headers are listed.
+pub fn signed_headers(list: &str, with_nonce: bool) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = list
+        .split(';')
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let nonce = with_nonce.then_some(NONCE_HEADER);
+    for required in REQUIRED_SIGNED_HEADERS.into_iter().chain(nonce) {
+        if !names.iter().any(|name| name == required) {
+            return Err(Error::UnsignedHeader(required.to_string()));
+        }
+    }
+    Ok(names)
+}
+
+/// Sorts the `name=value` pairs of a query string, as sent, by name then value.
+pub fn canonical_query(query: &str) -> String {
+    let mut pairs: Vec<(&str, &str)> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    pairs.sort_unstable();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Hex encoded SHA-256 digest of a request body, as sent in `x-content-sha256`.
+pub fn content_sha256(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+/// Message of v2 signatures, one field per line:
+///
+/// ```text
+/// v2
+/// {method}
+/// {path}
+/// {canonical query}
+/// {name}:{value}     one line per signed header, sorted by name
+/// {signed headers}   names joined by `;`
+/// {content sha256}
+/// ```
+///
+/// `headers` are the signed headers with their trimmed values. The body itself is only
+/// checked against its digest in `x-content-sha256`.
+pub fn canonical_request(
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<String, Error> {
+    let mut headers: Vec<&(String, String)> = headers.iter().collect();
+    headers.sort();
+
+    let digest = content_sha256(body);
+    match headers
+        .iter()
+        .find(|(name, _)| name == CONTENT_SHA256_HEADER)
+    {
+        Some((_, value)) if value.eq_ignore_ascii_case(&digest) => {}
+        Some(_) => return Err(Error::BodyDigestMismatch),
+        None => return Err(Error::UnsignedHeader(CONTENT_SHA256_HEADER.to_string())),
+    }
+
+    let mut canonical = format!("v2\n{}\n{}\n{}\n", method, path, canonical_query(query));
+    for (name, value) in &headers {
+        canonical.push_str(&format!("{}:{}\n", name, value));
+    }
+    let names: Vec<&str> = headers.iter().map(|(name, _)| name.as_str()).collect();
+    canonical.push_str(&format!("{}\n{}", names.join(";"), digest));
+    Ok(canonical)
+}
+
+/// Decodes a base64 signature, URL-safe or standard, padded or not.
+pub fn decode_signature(signature: &str) -> Result<Vec<u8>, Error> {
+    let decoded = if signature.contains(['-', '_']) {
+        URL_SAFE_INDIFFERENT.decode(signature)
+    } else {
+        STANDARD_INDIFFERENT.decode(signature)
+    }?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(body: &[u8]) -> Vec<(String, String)> {
+        vec![
+            ("x-timestamp".to_string(), "1700000000".to_string()),
+            ("x-api-key".to_string(), "key".to_string()),
+            (CONTENT_SHA256_HEADER.to_string(), content_sha256(body)),
+        ]
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let body = br#"{"message":"hello"}"#;
+        let canonical =
+            canonical_request("POST", "/wallet/u/sign", "b=2&a=1", &headers(body), body)
+                .expect("canonical");
+        assert_eq!(
+            canonical,
+            format!(
+                "v2\nPOST\n/wallet/u/sign\na=1&b=2\nx-api-key:key\nx-content-sha256:{0}\n\
+                 x-timestamp:1700000000\nx-api-key;x-content-sha256;x-timestamp\n{0}",
+                content_sha256(body)
+            )
+        );
+
+        // Characters shifted between the path and the query no longer give the same message
+        assert_ne!(
+            canonical_request("GET", "/wallet/ua", "", &headers(b""), b""),
+            canonical_request("GET", "/wallet/u", "a", &headers(b""), b"")
+        );
+        assert_eq!(
+            message_v1(1, None, "GET", "/wallet/ua", "", b""),
+            message_v1(1, None, "GET", "/wallet/u", "a", b"")
+        );
+    }
+
+    #[test]
+    fn test_message_v1() {
+        assert_eq!(
+            message_v1(1700000000, None, "POST", "/wallet/u", "a=1", b"{}"),
+            "1700000000POST/wallet/ua=1{}"
+        );
+        assert_eq!(
+            message_v1(1700000000, Some("0abc"), "GET", "/wallet/u", "", b""),
+            "1700000000\n0abc\nGET/wallet/u"
+        );
+        // Digits shifted from the nonce to the timestamp no longer give the same message
+        assert_ne!(
+            message_v1(1700000000, Some("0abc"), "GET", "/wallet/u", "", b""),
+            message_v1(17000000000, Some("abc"), "GET", "/wallet/u", "", b"")
+        );
+    }
+
+    #[test]
+    fn test_body_digest() {
+        assert_eq!(
+            canonical_request("POST", "/", "", &headers(b"signed"), b"tampered"),
+            Err(Error::BodyDigestMismatch)
+        );
+        let mut uppercase = headers(b"body");
+        uppercase[2].1 = uppercase[2].1.to_uppercase();
+        assert!(canonical_request("POST", "/", "", &uppercase, b"body").is_ok());
+    }
+
+    #[test]
+    fn test_signed_headers() {
+        assert_eq!(
+            signed_headers("X-Timestamp; x-api-key;x-content-sha256;host", false),
+            Ok(vec![
+                "host".to_string(),
+                "x-api-key".to_string(),
+                "x-content-sha256".to_string(),
+                "x-timestamp".to_string()
+            ])
+        );
+        assert_eq!(
+            signed_headers("x-api-key;x-content-sha256;x-timestamp", true),
+            Err(Error::UnsignedHeader("x-nonce".to_string()))
+        );
+        assert_eq!(
+            signed_headers("x-api-key;x-timestamp", false),
+            Err(Error::UnsignedHeader(CONTENT_SHA256_HEADER.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        assert_eq!(canonical_query(""), "");
+        assert_eq!(canonical_query("b=2&a=3&a=1&c"), "a=1&a=3&b=2&c=");
+    }
+
+    #[test]
+    fn test_signature_version() {
+        assert_eq!(SignatureVersion::from_str("2"), Ok(SignatureVersion::V2));
+        assert_eq!(
+            SignatureVersion::from_str("3"),
+            Err(Error::UnsupportedSignatureVersion("3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decode_signature() {
+        assert_eq!(decode_signature("+/+/"), Ok(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(decode_signature("-_-_"), Ok(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(decode_signature("-_8"), Ok(vec![0xfb, 0xff]));
+        assert_eq!(decode_signature("+/8="), Ok(vec![0xfb, 0xff]));
+        assert!(decode_signature("not base64!").is_err());
+    }
+}
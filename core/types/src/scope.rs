@@ -0,0 +1,122 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Permission granted to credentials, checked against the wallet route of each request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    UsersRegister,
+    UsersSign,
+    UsersRevoke,
+    UsersRead,
+}
+
+impl Scope {
+    /// Scopes of credentials created without an explicit set, as before scopes existed.
+    pub const ALL: [Scope; 4] = [
+        Scope::UsersRegister,
+        Scope::UsersSign,
+        Scope::UsersRevoke,
+        Scope::UsersRead,
+    ];
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::UsersRegister => f.write_str("users:register"),
+            Scope::UsersSign => f.write_str("users:sign"),
+            Scope::UsersRevoke => f.write_str("users:revoke"),
+            Scope::UsersRead => f.write_str("users:read"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "users:register" => Ok(Scope::UsersRegister),
+            "users:sign" => Ok(Scope::UsersSign),
+            "users:revoke" => Ok(Scope::UsersRevoke),
+            "users:read" => Ok(Scope::UsersRead),
+            other => Err(Error::UnknownScope(other.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = Error;
+
+    fn try_from(str: String) -> Result<Self, Self::Error> {
+        Scope::from_str(&str)
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+pub mod postgres {
+    use crate::scope::Scope;
+    use sqlx::{
+        encode::IsNull,
+        postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+    use std::{error::Error, str::FromStr};
+
+    impl Type<Postgres> for Scope {
+        fn type_info() -> PgTypeInfo {
+            <String as Type<Postgres>>::type_info()
+        }
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <String as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl PgHasArrayType for Scope {
+        fn array_type_info() -> PgTypeInfo {
+            <String as PgHasArrayType>::array_type_info()
+        }
+        fn array_compatible(ty: &PgTypeInfo) -> bool {
+            <String as PgHasArrayType>::array_compatible(ty)
+        }
+    }
+
+    impl<'q> Encode<'q, Postgres> for Scope {
+        fn encode_by_ref(
+            &self,
+            buf: &mut PgArgumentBuffer,
+        ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+            <String as Encode<Postgres>>::encode_by_ref(&self.to_string(), buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for Scope {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+            let str = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(Scope::from_str(str)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in Scope::ALL {
+            assert_eq!(Scope::from_str(&scope.to_string()), Ok(scope));
+        }
+        let scopes: Vec<Scope> =
+            serde_json::from_str(r#"["users:sign", "users:read"]"#).expect("scopes");
+        assert_eq!(scopes, vec![Scope::UsersSign, Scope::UsersRead]);
+        assert!(serde_json::from_str::<Scope>(r#""users:admin""#).is_err());
+    }
+}
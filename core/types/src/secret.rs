@@ -86,7 +86,7 @@ pub mod mask {
 
     impl<T: MaskableSecret + PartialEq> PartialEq for Masked<T> {
         fn eq(&self, other: &Self) -> bool {
-            self.0.expose_secret().eq(&other.0.expose_secret())
+            self.0.expose_secret().eq(other.0.expose_secret())
         }
     }
 
@@ -167,7 +167,7 @@ pub mod mask {
                 &self,
                 buf: &mut PgArgumentBuffer,
             ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
-                <T as Encode<Postgres>>::encode_by_ref(&self.0.expose_secret(), buf)
+                <T as Encode<Postgres>>::encode_by_ref(self.0.expose_secret(), buf)
             }
         }
 
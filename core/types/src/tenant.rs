@@ -0,0 +1,207 @@
+use crate::{
+    client::ClientId,
+    encrypt::{provider::KeyRing, row_context, Aes256Key, Encrypted},
+    error::Error,
+};
+use std::str::FromStr;
+
+/// Key id stored with data keys wrapped by their client's tenant key instead of a master key.
+pub const TENANT_KEY_ID: &str = "tenant";
+
+/// Key encryption key of a client, between the master keys and the client's data keys.
+///
+/// Stored in the key store, apart from the data keys it wraps: deleting it makes the copies
+/// of the client's data keys in backups of the main database unrecoverable too.
+pub struct TenantKey {
+    client_id: ClientId,
+    key: Aes256Key,
+}
+
+impl TenantKey {
+    pub fn generate(client_id: ClientId) -> Self {
+        TenantKey {
+            client_id,
+            key: Aes256Key::generate(),
+        }
+    }
+
+    pub fn client_id(&self) -> &ClientId {
+        &self.client_id
+    }
+
+    pub async fn encrypt(
+        &self,
+        key_ring: &impl KeyRing,
+    ) -> Result<encrypt::EncryptedTenantKey, Error> {
+        Ok(encrypt::EncryptedTenantKey {
+            client_id: self.client_id.clone(),
+            encrypted_key: key_ring.wrap_data_key(&self.key).await?,
+            master_key_id: key_ring.active_key_id().to_string(),
+        })
+    }
+
+    fn wrap(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        let wrapped_key = self.key.encrypt(&data_key.to_string(), &self.context())?;
+        Ok(wrapped_key.with_key_id(TENANT_KEY_ID))
+    }
+
+    fn unwrap(&self, wrapped_key: &Encrypted) -> Result<Aes256Key, Error> {
+        let data_key = self
+            .key
+            .decrypt(&wrapped_key.for_key(TENANT_KEY_ID)?, &self.context())?;
+        Aes256Key::from_str(&data_key)
+    }
+
+    /// Binds the wrapped data keys to the client.
+    fn context(&self) -> Vec<u8> {
+        row_context("clients", &self.client_id.clone().into())
+    }
+}
+
+/// Wraps new data keys with the client's tenant key, if it has one, and unwraps data keys
+/// wrapped by master keys before the client had one.
+pub struct TenantKeyRing<'a, R> {
+    master_keys: &'a R,
+    tenant_key: Option<&'a TenantKey>,
+}
+
+impl<'a, R: KeyRing> TenantKeyRing<'a, R> {
+    pub fn new(master_keys: &'a R, tenant_key: Option<&'a TenantKey>) -> Self {
+        TenantKeyRing {
+            master_keys,
+            tenant_key,
+        }
+    }
+}
+
+impl<R: KeyRing + Sync> KeyRing for TenantKeyRing<'_, R> {
+    fn active_key_id(&self) -> &str {
+        match self.tenant_key {
+            Some(_) => TENANT_KEY_ID,
+            None => self.master_keys.active_key_id(),
+        }
+    }
+
+    async fn wrap_data_key(&self, data_key: &Aes256Key) -> Result<Encrypted, Error> {
+        match self.tenant_key {
+            Some(tenant_key) => tenant_key.wrap(data_key),
+            None => self.master_keys.wrap_data_key(data_key).await,
+        }
+    }
+
+    async fn unwrap_data_key(
+        &self,
+        key_id: &str,
+        wrapped_key: &Encrypted,
+    ) -> Result<Aes256Key, Error> {
+        match self.tenant_key {
+            Some(tenant_key) if key_id == TENANT_KEY_ID => tenant_key.unwrap(wrapped_key),
+            _ => self.master_keys.unwrap_data_key(key_id, wrapped_key).await,
+        }
+    }
+}
+
+pub mod encrypt {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, sqlx::Type)]
+    pub struct EncryptedTenantKey {
+        pub client_id: ClientId,
+        pub encrypted_key: Encrypted,
+        /// Master key wrapping `encrypted_key`.
+        pub master_key_id: String,
+    }
+
+    impl EncryptedTenantKey {
+        pub async fn decrypt(self, key_ring: &impl KeyRing) -> Result<TenantKey, Error> {
+            let key = key_ring
+                .unwrap_data_key(&self.master_key_id, &self.encrypted_key)
+                .await?;
+            Ok(TenantKey {
+                client_id: self.client_id,
+                key,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encrypt::{master_key::MasterKey, provider::DEFAULT_KEY_ID};
+
+    #[tokio::test]
+    async fn test_tenant_key_wraps_data_keys() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let tenant_key = TenantKey::generate(ClientId::from("client"));
+        let encrypted_tenant_key = tenant_key.encrypt(&master_key).await.expect("encrypt");
+        assert_eq!(encrypted_tenant_key.master_key_id, DEFAULT_KEY_ID);
+
+        let data_key = Aes256Key::generate();
+        let key_ring = TenantKeyRing::new(&master_key, Some(&tenant_key));
+        assert_eq!(key_ring.active_key_id(), TENANT_KEY_ID);
+        let wrapped = key_ring.wrap_data_key(&data_key).await.expect("wrap");
+        assert_eq!(wrapped.key_id.as_deref(), Some(TENANT_KEY_ID));
+
+        // Master keys alone cannot unwrap data keys of a tenant
+        assert!(master_key
+            .unwrap_data_key(TENANT_KEY_ID, &wrapped)
+            .await
+            .is_err());
+
+        let tenant_key = encrypted_tenant_key
+            .decrypt(&master_key)
+            .await
+            .expect("decrypt");
+        let key_ring = TenantKeyRing::new(&master_key, Some(&tenant_key));
+        let unwrapped = key_ring
+            .unwrap_data_key(TENANT_KEY_ID, &wrapped)
+            .await
+            .expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_key_is_bound_to_its_client() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let tenant_key = TenantKey::generate(ClientId::from("client"));
+        let wrapped = TenantKeyRing::new(&master_key, Some(&tenant_key))
+            .wrap_data_key(&Aes256Key::generate())
+            .await
+            .expect("wrap");
+
+        // Same key material claimed by another client
+        let other_key = TenantKey {
+            client_id: ClientId::from("other"),
+            key: Aes256Key::from_str(&tenant_key.key.to_string()).expect("key"),
+        };
+        assert!(TenantKeyRing::new(&master_key, Some(&other_key))
+            .unwrap_data_key(TENANT_KEY_ID, &wrapped)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_key_ring_unwraps_master_wrapped_data_keys() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let data_key = Aes256Key::generate();
+        let wrapped = master_key.wrap_data_key(&data_key).await.expect("wrap");
+
+        let tenant_key = TenantKey::generate(ClientId::from("client"));
+        let key_ring = TenantKeyRing::new(&master_key, Some(&tenant_key));
+        let unwrapped = key_ring
+            .unwrap_data_key(DEFAULT_KEY_ID, &wrapped)
+            .await
+            .expect("unwrap");
+        assert_eq!(unwrapped.expose_secret(), data_key.expose_secret());
+
+        // Clients without a tenant key keep wrapping with the master keys
+        let key_ring = TenantKeyRing::new(&master_key, None);
+        assert_eq!(key_ring.active_key_id(), DEFAULT_KEY_ID);
+        assert!(key_ring
+            .unwrap_data_key(TENANT_KEY_ID, &wrapped)
+            .await
+            .is_err());
+    }
+}
@@ -1,14 +1,24 @@
 use crate::encrypt::Aes256Key;
-use crate::{encrypt::master_key::MasterKey, error::Error};
+use crate::{
+    encoding::SignatureEncoding,
+    encrypt::{provider::KeyRing, row_context},
+    error::Error,
+    ethereum,
+    hd::HdWallet,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use k256::ecdsa::{RecoveryId, Signature as EcdsaSignature};
 use rsa::{
-    pkcs1v15,
     pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding},
-    sha2::Sha256,
-    signature::{Keypair, SignerMut, Verifier},
-    RsaPrivateKey,
+    sha2::{Sha256, Sha384, Sha512},
+    traits::PublicKeyParts,
+    Pkcs1v15Sign, Pss, RsaPrivateKey,
 };
 use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
 use uuid::Uuid;
+use verifier::{HashAlgorithm, RsaPadding, RsaScheme};
+use zeroize::Zeroizing;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, sqlx::Type)]
 #[sqlx(transparent)]
@@ -48,9 +58,21 @@ pub struct User {
     pub signing_key: SigningKey,
 }
 
+/// Details of a user kept in clear, listed on the dashboard of its client.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UserInfo {
+    pub id: UserId,
+    pub key_type: KeyType,
+    /// Unset for users registered before public keys were kept in clear.
+    pub public_key: Option<String>,
+}
+
 impl User {
-    pub fn new() -> Result<Self, Error> {
-        let signing_key = SigningKey::generate()?;
+    pub fn new(key_type: KeyType) -> Result<Self, Error> {
+        Self::with_signing_key(SigningKey::generate(key_type)?)
+    }
+
+    pub fn with_signing_key(signing_key: SigningKey) -> Result<Self, Error> {
         let id = UserId::from(signing_key.public_key_pem()?.as_str());
         Ok(User { id, signing_key })
     }
@@ -59,71 +81,449 @@ impl User {
         &self.id
     }
 
-    pub fn encrypt(self, master_key: &MasterKey) -> Result<encrypt::EncryptedUser, Error> {
-        let encrypted_signing_key = self.signing_key.encrypt(&master_key)?;
+    pub async fn encrypt(self, key_ring: &impl KeyRing) -> Result<encrypt::EncryptedUser, Error> {
+        let encrypted_signing_key = self.signing_key.encrypt(&self.id, key_ring).await?;
 
         Ok(encrypt::EncryptedUser {
             id: self.id,
+            public_key: Some(self.signing_key.public_key_pem()?),
             encrypted_signing_key,
         })
     }
 }
 
-pub struct SigningKey {
-    pub private_key: RsaPrivateKey,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    #[default]
+    Rsa,
+    Secp256k1,
+    Ed25519,
+    Hd,
+}
+
+impl Display for KeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyType::Rsa => f.write_str("rsa"),
+            KeyType::Secp256k1 => f.write_str("secp256k1"),
+            KeyType::Ed25519 => f.write_str("ed25519"),
+            KeyType::Hd => f.write_str("hd"),
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "rsa" => Ok(KeyType::Rsa),
+            "secp256k1" => Ok(KeyType::Secp256k1),
+            "ed25519" => Ok(KeyType::Ed25519),
+            "hd" => Ok(KeyType::Hd),
+            other => Err(Error::UnsupportedKeyType(other.to_string())),
+        }
+    }
+}
+
+/// Size in bits of generated RSA keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum RsaKeySize {
+    #[default]
+    Bits2048,
+    Bits3072,
+    Bits4096,
+}
+
+impl TryFrom<u32> for RsaKeySize {
+    type Error = Error;
+
+    fn try_from(bits: u32) -> Result<Self, Self::Error> {
+        match bits {
+            2048 => Ok(RsaKeySize::Bits2048),
+            3072 => Ok(RsaKeySize::Bits3072),
+            4096 => Ok(RsaKeySize::Bits4096),
+            other => Err(Error::InvalidKeyOptions(format!(
+                "unsupported RSA key size {}, expected 2048, 3072 or 4096",
+                other
+            ))),
+        }
+    }
+}
+
+impl From<RsaKeySize> for u32 {
+    fn from(key_size: RsaKeySize) -> Self {
+        match key_size {
+            RsaKeySize::Bits2048 => 2048,
+            RsaKeySize::Bits3072 => 3072,
+            RsaKeySize::Bits4096 => 4096,
+        }
+    }
+}
+
+/// Key size and signature scheme of RSA users.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RsaOptions {
+    #[serde(default)]
+    pub key_size: RsaKeySize,
+    #[serde(flatten)]
+    pub scheme: RsaScheme,
+}
+
+pub mod postgres {
+    use crate::user::KeyType;
+    use sqlx::{
+        encode::IsNull,
+        postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+    use std::{error::Error, str::FromStr};
+
+    impl Type<Postgres> for KeyType {
+        fn type_info() -> PgTypeInfo {
+            <String as Type<Postgres>>::type_info()
+        }
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <String as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl<'q> Encode<'q, Postgres> for KeyType {
+        fn encode_by_ref(
+            &self,
+            buf: &mut PgArgumentBuffer,
+        ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+            <String as Encode<Postgres>>::encode_by_ref(&self.to_string(), buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for KeyType {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+            let str = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(KeyType::from_str(str)?)
+        }
+    }
+}
+
+pub enum SigningKey {
+    Rsa(Box<RsaPrivateKey>, RsaScheme),
+    Secp256k1(k256::ecdsa::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+    /// BIP-32 secp256k1 wallet, signing with its default BIP-44 account unless derived.
+    Hd(Box<HdWallet>),
 }
 
 impl SigningKey {
-    pub fn generate() -> Result<Self, Error> {
-        tracing::debug!("Generating RSA key");
+    pub fn generate(key_type: KeyType) -> Result<Self, Error> {
         let mut rng = rand::thread_rng();
-        let private_key = RsaPrivateKey::new(&mut rng, 2048)?;
+        match key_type {
+            KeyType::Rsa => Self::generate_rsa(RsaOptions::default()),
+            KeyType::Secp256k1 => Ok(SigningKey::Secp256k1(k256::ecdsa::SigningKey::random(
+                &mut rng,
+            ))),
+            KeyType::Ed25519 => Ok(SigningKey::Ed25519(ed25519_dalek::SigningKey::generate(
+                &mut rng,
+            ))),
+            KeyType::Hd => Ok(SigningKey::Hd(Box::new(HdWallet::generate()?))),
+        }
+    }
+
+    pub fn generate_rsa(options: RsaOptions) -> Result<Self, Error> {
+        let bits = u32::from(options.key_size);
+        tracing::debug!("Generating {} bits RSA key", bits);
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), bits as usize)?;
         tracing::debug!("Generated RSA key");
+        Ok(SigningKey::Rsa(Box::new(private_key), options.scheme))
+    }
+
+    /// Key size and signature scheme of RSA keys.
+    pub fn rsa_options(&self) -> Option<RsaOptions> {
+        match self {
+            SigningKey::Rsa(private_key, scheme) => Some(RsaOptions {
+                // Keys are only ever generated with supported sizes
+                key_size: RsaKeySize::try_from(private_key.size() as u32 * 8).unwrap_or_default(),
+                scheme: *scheme,
+            }),
+            _ => None,
+        }
+    }
 
-        Ok(SigningKey { private_key })
+    /// Derives the secp256k1 key at the given BIP-32 path of an HD wallet.
+    pub fn derive(&self, path: &str) -> Result<SigningKey, Error> {
+        match self {
+            SigningKey::Hd(wallet) => Ok(SigningKey::Secp256k1(wallet.derive(path)?)),
+            _ => Err(Error::UnsupportedKeyType(self.key_type().to_string())),
+        }
+    }
+
+    /// The secp256k1 key used for signing, if the key type has one.
+    fn secp256k1_key(&self) -> Option<&k256::ecdsa::SigningKey> {
+        match self {
+            SigningKey::Secp256k1(signing_key) => Some(signing_key),
+            SigningKey::Hd(wallet) => Some(wallet.default_key()),
+            _ => None,
+        }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            SigningKey::Rsa(..) => KeyType::Rsa,
+            SigningKey::Secp256k1(_) => KeyType::Secp256k1,
+            SigningKey::Ed25519(_) => KeyType::Ed25519,
+            SigningKey::Hd(_) => KeyType::Hd,
+        }
     }
 
     pub fn public_key_pem(&self) -> Result<String, Error> {
-        Ok(self
-            .private_key
-            .to_public_key()
-            .to_public_key_pem(LineEnding::LF)?)
+        match self {
+            SigningKey::Rsa(private_key, _) => Ok(private_key
+                .to_public_key()
+                .to_public_key_pem(LineEnding::LF)?),
+            SigningKey::Secp256k1(signing_key) => Ok(signing_key
+                .verifying_key()
+                .to_public_key_pem(LineEnding::LF)?),
+            SigningKey::Ed25519(signing_key) => Ok(signing_key
+                .verifying_key()
+                .to_public_key_pem(LineEnding::LF)?),
+            SigningKey::Hd(wallet) => Ok(wallet
+                .default_key()
+                .verifying_key()
+                .to_public_key_pem(LineEnding::LF)?),
+        }
     }
 
-    pub fn sign_message(&self, message: &str) -> String {
-        let mut signing_key = pkcs1v15::SigningKey::<Sha256>::new(self.private_key.clone());
-        signing_key.sign(message.as_bytes()).to_string()
+    /// Blockchain address derived from the public key, if the key type has one.
+    pub fn address(&self) -> Option<String> {
+        match self {
+            SigningKey::Rsa(..) => None,
+            SigningKey::Secp256k1(signing_key) => Some(ethereum::to_checksum_address(
+                &ethereum::address(signing_key.verifying_key()),
+            )),
+            SigningKey::Ed25519(signing_key) => {
+                Some(bs58::encode(signing_key.verifying_key().as_bytes()).into_string())
+            }
+            SigningKey::Hd(wallet) => Some(ethereum::to_checksum_address(&ethereum::address(
+                wallet.default_key().verifying_key(),
+            ))),
+        }
+    }
+
+    /// Signs the message and returns the hex encoded signature.
+    ///
+    /// RSA keys produce signatures following their [`RsaScheme`], secp256k1 keys sign the
+    /// Keccak-256 digest of the message and produce recoverable `r || s || v` signatures,
+    /// Ed25519 keys produce 64 bytes signatures of the message itself.
+    pub fn sign_message(&self, message: &str) -> Result<String, Error> {
+        let signature = self.sign_bytes(message.as_bytes())?;
+        match self {
+            // RSA signatures have always been reported in upper case hex
+            SigningKey::Rsa(..) => Ok(hex::encode_upper(signature)),
+            _ => Ok(hex::encode(signature)),
+        }
+    }
+
+    /// Signs a binary message, see [`SigningKey::sign_message`] for the signature formats.
+    pub fn sign_bytes(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        self.sign_payload(Payload::Message(message))
+    }
+
+    /// Signs a digest computed by the caller: with the digest of the user's RSA scheme for
+    /// RSA keys, Keccak-256 for secp256k1 keys. Ed25519 keys can only sign messages.
+    pub fn sign_digest(&self, digest: &[u8]) -> Result<Vec<u8>, Error> {
+        self.sign_payload(Payload::Digest(digest))
+    }
+
+    fn sign_payload(&self, payload: Payload) -> Result<Vec<u8>, Error> {
+        match self {
+            SigningKey::Rsa(private_key, scheme) => match scheme.digest {
+                HashAlgorithm::Sha256 => sign_rsa::<Sha256>(private_key, scheme.padding, payload),
+                HashAlgorithm::Sha384 => sign_rsa::<Sha384>(private_key, scheme.padding, payload),
+                HashAlgorithm::Sha512 => sign_rsa::<Sha512>(private_key, scheme.padding, payload),
+            },
+            SigningKey::Secp256k1(_) | SigningKey::Hd(_) => {
+                let digest = match payload {
+                    Payload::Message(message) => ethereum::keccak256(message),
+                    Payload::Digest(digest) => digest.try_into().map_err(|_| {
+                        Error::InvalidPayload(format!(
+                            "expected a 32 bytes Keccak-256 digest, got {} bytes",
+                            digest.len()
+                        ))
+                    })?,
+                };
+                Ok(self.sign_ethereum_hash(&digest)?.to_vec())
+            }
+            SigningKey::Ed25519(signing_key) => {
+                use ed25519_dalek::Signer;
+
+                match payload {
+                    Payload::Message(message) => Ok(signing_key.sign(message).to_bytes().to_vec()),
+                    Payload::Digest(_) => Err(Error::InvalidPayload(
+                        "ed25519 keys cannot sign prehashed messages".to_string(),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Encodes a signature produced by this key, see [`SignatureEncoding`].
+    pub fn encode_signature(
+        &self,
+        signature: &[u8],
+        encoding: SignatureEncoding,
+    ) -> Result<String, Error> {
+        let is_ecdsa = self.secp256k1_key().is_some();
+        match encoding {
+            SignatureEncoding::Hex => Ok(hex::encode(signature)),
+            SignatureEncoding::Base64 => Ok(STANDARD.encode(signature)),
+            SignatureEncoding::Der if is_ecdsa => {
+                let signature = EcdsaSignature::from_slice(&signature[..64.min(signature.len())])
+                    .map_err(|_| Error::SigningFailed)?;
+                Ok(hex::encode(signature.to_der()))
+            }
+            SignatureEncoding::Der => Err(Error::InvalidPayload(format!(
+                "{} signatures have no DER encoding",
+                self.key_type()
+            ))),
+            SignatureEncoding::Raw if is_ecdsa => {
+                Ok(hex::encode(&signature[..64.min(signature.len())]))
+            }
+            SignatureEncoding::Raw => Ok(hex::encode(signature)),
+        }
+    }
+
+    /// Signs a 32 bytes hash with a secp256k1 key, returning a recoverable `r || s || v`
+    /// signature as expected by Ethereum tooling.
+    pub fn sign_ethereum_hash(&self, hash: &[u8; 32]) -> Result<[u8; 65], Error> {
+        match self.secp256k1_key() {
+            Some(signing_key) => {
+                let (signature, recovery_id) = signing_key
+                    .sign_prehash_recoverable(hash)
+                    .map_err(|_| Error::SigningFailed)?;
+                Ok(recoverable_signature(&signature, recovery_id))
+            }
+            None => Err(Error::UnsupportedKeyType(self.key_type().to_string())),
+        }
+    }
+
+    /// EIP-191 `personal_sign` of the message.
+    pub fn sign_personal_message(&self, message: &[u8]) -> Result<[u8; 65], Error> {
+        self.sign_ethereum_hash(&ethereum::personal_message_hash(message))
     }
 
     pub fn verify_signature(&self, message: &str, signature: &[u8]) -> Result<bool, Error> {
-        let signature =
-            pkcs1v15::Signature::try_from(signature).map_err(|_| Error::InvalidSignature)?;
-        let signing_key = pkcs1v15::SigningKey::<Sha256>::new(self.private_key.clone());
-        let verifying_key = signing_key.verifying_key();
-        let is_valid = verifying_key.verify(message.as_bytes(), &signature).is_ok();
-        Ok(is_valid)
+        let rsa_scheme = self
+            .rsa_options()
+            .map(|options| options.scheme)
+            .unwrap_or_default();
+        verifier::verify_with_scheme(
+            &self.public_key_pem()?,
+            rsa_scheme,
+            message.as_bytes(),
+            signature,
+        )
+        .map_err(|_| Error::InvalidSignature)
+    }
+
+    /// Secret material to be encrypted: a PKCS#8 PEM, or the mnemonic phrase of HD wallets.
+    fn to_secret(&self) -> Result<Zeroizing<String>, Error> {
+        match self {
+            SigningKey::Rsa(private_key, _) => Ok(private_key.to_pkcs8_pem(LineEnding::LF)?),
+            SigningKey::Secp256k1(signing_key) => Ok(signing_key.to_pkcs8_pem(LineEnding::LF)?),
+            SigningKey::Ed25519(signing_key) => Ok(signing_key.to_pkcs8_pem(LineEnding::LF)?),
+            SigningKey::Hd(wallet) => Ok(wallet.phrase()),
+        }
     }
 
-    pub fn encrypt(&self, master_key: &MasterKey) -> Result<encrypt::EncryptedSigningKey, Error> {
+    pub async fn encrypt(
+        &self,
+        user_id: &UserId,
+        key_ring: &impl KeyRing,
+    ) -> Result<encrypt::EncryptedSigningKey, Error> {
         let data_key = Aes256Key::generate();
 
-        let private_pem = self.private_key.to_pkcs8_pem(LineEnding::LF)?;
+        let secret = self.to_secret()?;
 
-        let encrypted_private_key = data_key.encrypt(&private_pem)?;
-        let encrypted_data_key = master_key.encrypt(&data_key.to_string())?;
+        let encrypted_private_key = data_key.encrypt(&secret, &private_key_context(user_id))?;
+        let encrypted_data_key = key_ring.wrap_data_key(&data_key).await?;
 
         Ok(encrypt::EncryptedSigningKey {
+            key_type: self.key_type(),
+            rsa_scheme: self.rsa_options().map(|options| options.scheme),
             encrypted_private_key,
             encrypted_data_key,
+            master_key_id: key_ring.active_key_id().to_string(),
         })
     }
 }
 
+/// Binds the encrypted private key to its user row.
+fn private_key_context(user_id: &UserId) -> Vec<u8> {
+    row_context("users", &user_id.0)
+}
+
+/// Signs the message with an RSA key, returning the raw signature bytes.
+///
+/// Signs through the padding schemes rather than the `pkcs1v15`/`pss` signing keys, which
+/// would take ownership of a copy of the private key for every signature.
+fn sign_rsa<D>(
+    private_key: &RsaPrivateKey,
+    padding: RsaPadding,
+    payload: Payload,
+) -> Result<Vec<u8>, Error>
+where
+    D: rsa::sha2::Digest
+        + rsa::sha2::digest::DynDigest
+        + rsa::pkcs8::spki::der::oid::AssociatedOid
+        + Send
+        + Sync
+        + 'static,
+{
+    let digest = match payload {
+        Payload::Message(message) => D::digest(message).to_vec(),
+        Payload::Digest(digest) => {
+            let expected = <D as rsa::sha2::Digest>::output_size();
+            if digest.len() != expected {
+                return Err(Error::InvalidPayload(format!(
+                    "expected a {} bytes digest, got {} bytes",
+                    expected,
+                    digest.len()
+                )));
+            }
+            digest.to_vec()
+        }
+    };
+
+    let signature = match padding {
+        RsaPadding::Pkcs1v15 => private_key.sign(Pkcs1v15Sign::new::<D>(), &digest),
+        RsaPadding::Pss => {
+            private_key.sign_with_rng(&mut rand::thread_rng(), Pss::new::<D>(), &digest)
+        }
+    };
+    signature.map_err(|_| Error::SigningFailed)
+}
+
+/// Data handed to the key for signing.
+#[derive(Clone, Copy)]
+enum Payload<'a> {
+    Message(&'a [u8]),
+    /// Digest of the message, computed by the caller.
+    Digest(&'a [u8]),
+}
+
+/// Encodes an ECDSA signature as `r || s || v`, with `v` in the Ethereum `{27, 28}` range.
+fn recoverable_signature(signature: &EcdsaSignature, recovery_id: RecoveryId) -> [u8; 65] {
+    let mut bytes = [0u8; 65];
+    bytes[..64].copy_from_slice(&signature.to_bytes());
+    bytes[64] = 27 + recovery_id.to_byte();
+    bytes
+}
+
 pub mod encrypt {
     use super::*;
-    use crate::encrypt::Aes256Key;
     use crate::{
-        encrypt::{master_key::MasterKey, Encrypted},
+        encrypt::{provider::KeyRing, Encrypted},
         user::UserId,
     };
     use rsa::{pkcs8::DecodePrivateKey, RsaPrivateKey};
@@ -134,12 +534,18 @@ pub mod encrypt {
     #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
     pub struct EncryptedUser {
         pub id: UserId,
+        /// PEM encoded public key, stored in clear so signatures can be verified without
+        /// decrypting the private key. Missing for users registered before it was stored.
+        pub public_key: Option<String>,
         pub encrypted_signing_key: EncryptedSigningKey,
     }
 
     impl EncryptedUser {
-        pub fn decrypt(self, master_key: &MasterKey) -> Result<User, Error> {
-            let encrypted_signing_key = self.encrypted_signing_key.decrypt(master_key)?;
+        pub async fn decrypt(self, key_ring: &impl KeyRing) -> Result<User, Error> {
+            let encrypted_signing_key = self
+                .encrypted_signing_key
+                .decrypt(&self.id, key_ring)
+                .await?;
             Ok(User {
                 id: self.id,
                 signing_key: encrypted_signing_key,
@@ -151,29 +557,321 @@ pub mod encrypt {
         fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
             Ok(EncryptedUser {
                 id: row.try_get("id")?,
+                public_key: row.try_get("public_key")?,
                 encrypted_signing_key: EncryptedSigningKey {
+                    key_type: row.try_get("key_type")?,
+                    rsa_scheme: rsa_scheme(row)?,
                     encrypted_private_key: row.try_get("encrypted_private_key")?,
                     encrypted_data_key: row.try_get("encrypted_data_key")?,
+                    master_key_id: row.try_get("master_key_id")?,
                 },
             })
         }
     }
 
-    #[derive(Debug, Clone, PartialEq, Eq, Deserialize, sqlx::FromRow, sqlx::Type)]
+    /// Reads the RSA signature scheme, stored as `rsa_padding` and `rsa_digest` columns.
+    fn rsa_scheme(row: &PgRow) -> Result<Option<RsaScheme>, sqlx::Error> {
+        let padding: Option<String> = row.try_get("rsa_padding")?;
+        let digest: Option<String> = row.try_get("rsa_digest")?;
+        match (padding, digest) {
+            (Some(padding), Some(digest)) => Ok(Some(RsaScheme {
+                padding: RsaPadding::from_str(&padding).map_err(|err| {
+                    sqlx::Error::ColumnDecode {
+                        index: "rsa_padding".to_string(),
+                        source: Box::new(err),
+                    }
+                })?,
+                digest: HashAlgorithm::from_str(&digest).map_err(|err| {
+                    sqlx::Error::ColumnDecode {
+                        index: "rsa_digest".to_string(),
+                        source: Box::new(err),
+                    }
+                })?,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
     pub struct EncryptedSigningKey {
+        pub key_type: KeyType,
+        /// Signature scheme of RSA keys, not secret and stored in clear.
+        pub rsa_scheme: Option<RsaScheme>,
         pub encrypted_private_key: Encrypted,
         pub encrypted_data_key: Encrypted,
+        /// Master key wrapping `encrypted_data_key`, or [`TENANT_KEY_ID`](crate::tenant::TENANT_KEY_ID)
+        /// for the client's tenant key.
+        pub master_key_id: String,
     }
 
     impl EncryptedSigningKey {
-        pub fn decrypt(self, master_key: &MasterKey) -> Result<SigningKey, Error> {
-            let data_key_str = master_key.decrypt(&self.encrypted_data_key)?;
-            let data_key = Aes256Key::from_str(&data_key_str)?;
+        pub async fn decrypt(
+            self,
+            user_id: &UserId,
+            key_ring: &impl KeyRing,
+        ) -> Result<SigningKey, Error> {
+            let data_key = key_ring
+                .unwrap_data_key(&self.master_key_id, &self.encrypted_data_key)
+                .await?;
+
+            let secret = Zeroizing::new(
+                data_key.decrypt(&self.encrypted_private_key, &private_key_context(user_id))?,
+            );
+            match self.key_type {
+                KeyType::Rsa => Ok(SigningKey::Rsa(
+                    Box::new(RsaPrivateKey::from_pkcs8_pem(secret.as_str())?),
+                    self.rsa_scheme.unwrap_or_default(),
+                )),
+                KeyType::Secp256k1 => Ok(SigningKey::Secp256k1(
+                    k256::ecdsa::SigningKey::from_pkcs8_pem(secret.as_str())?,
+                )),
+                KeyType::Ed25519 => Ok(SigningKey::Ed25519(
+                    ed25519_dalek::SigningKey::from_pkcs8_pem(secret.as_str())?,
+                )),
+                KeyType::Hd => Ok(SigningKey::Hd(Box::new(HdWallet::from_phrase(
+                    secret.as_str(),
+                )?))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoding::SignatureEncoding,
+        encrypt::{master_key::MasterKey, Aes256Key},
+        error::Error,
+        ethereum, hd,
+        user::{KeyType, RsaKeySize, RsaOptions, SigningKey, UserId},
+    };
+    use k256::ecdsa::{RecoveryId, Signature as EcdsaSignature, VerifyingKey};
+    use verifier::{HashAlgorithm, RsaPadding, RsaScheme};
+
+    #[tokio::test]
+    async fn test_secp256k1_signing_key_encrypt_decrypt() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let user_id = UserId::from("user");
+        let signing_key = SigningKey::generate(KeyType::Secp256k1).expect("key generation failed");
+        let address = signing_key.address().expect("secp256k1 key has an address");
+        let message = "The quick brown fox jumps over the lazy dog.";
 
-            let private_pem = data_key.decrypt(&self.encrypted_private_key)?;
-            let private_key = RsaPrivateKey::from_pkcs8_pem(private_pem.as_str())?;
+        let encrypted = signing_key
+            .encrypt(&user_id, &master_key)
+            .await
+            .expect("encryption failed");
+        assert_eq!(encrypted.key_type, KeyType::Secp256k1);
 
-            Ok(SigningKey { private_key })
+        let decrypted = encrypted
+            .decrypt(&user_id, &master_key)
+            .await
+            .expect("decryption failed");
+        assert_eq!(decrypted.address(), Some(address));
+
+        let signature = hex::decode(decrypted.sign_message(message).expect("signing failed"))
+            .expect("hex signature");
+        assert_eq!(signature.len(), 65);
+        assert!(signing_key
+            .verify_signature(message, &signature)
+            .expect("verification failed"));
+    }
+
+    #[tokio::test]
+    async fn test_ed25519_signing_key_encrypt_decrypt() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let user_id = UserId::from("user");
+        let signing_key = SigningKey::generate(KeyType::Ed25519).expect("key generation failed");
+        let address = signing_key.address().expect("ed25519 key has an address");
+        assert_eq!(bs58::decode(&address).into_vec().expect("base58").len(), 32);
+        let message = "The quick brown fox jumps over the lazy dog.";
+
+        let encrypted = signing_key
+            .encrypt(&user_id, &master_key)
+            .await
+            .expect("encryption failed");
+        assert_eq!(encrypted.key_type, KeyType::Ed25519);
+
+        let decrypted = encrypted
+            .decrypt(&user_id, &master_key)
+            .await
+            .expect("decryption failed");
+        assert_eq!(decrypted.address(), Some(address));
+
+        let signature = hex::decode(decrypted.sign_message(message).expect("signing failed"))
+            .expect("hex signature");
+        assert_eq!(signature.len(), 64);
+        assert!(signing_key
+            .verify_signature(message, &signature)
+            .expect("verification failed"));
+    }
+
+    #[test]
+    fn test_secp256k1_personal_sign_recovers_address() {
+        let signing_key = SigningKey::generate(KeyType::Secp256k1).expect("key generation failed");
+        let message = b"Hello, Ethereum!";
+
+        let signature = signing_key
+            .sign_personal_message(message)
+            .expect("signing failed");
+        let recovery_id = RecoveryId::from_byte(signature[64] - 27).expect("recovery id");
+        let recovered = VerifyingKey::recover_from_prehash(
+            &ethereum::personal_message_hash(message),
+            &EcdsaSignature::from_slice(&signature[..64]).expect("signature"),
+            recovery_id,
+        )
+        .expect("recovery failed");
+
+        assert_eq!(
+            Some(ethereum::to_checksum_address(&ethereum::address(
+                &recovered
+            ))),
+            signing_key.address()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hd_signing_key_encrypt_decrypt_and_derive() {
+        let master_key = MasterKey::from(Aes256Key::generate());
+        let user_id = UserId::from("user");
+        let signing_key = SigningKey::generate(KeyType::Hd).expect("key generation failed");
+        let address = signing_key.address().expect("hd key has an address");
+
+        let default_account = signing_key
+            .derive(hd::DEFAULT_DERIVATION_PATH)
+            .expect("derivation failed");
+        assert_eq!(default_account.address(), Some(address.clone()));
+
+        let encrypted = signing_key
+            .encrypt(&user_id, &master_key)
+            .await
+            .expect("encryption failed");
+        assert_eq!(encrypted.key_type, KeyType::Hd);
+
+        let decrypted = encrypted
+            .decrypt(&user_id, &master_key)
+            .await
+            .expect("decryption failed");
+        assert_eq!(decrypted.address(), Some(address));
+
+        let second = decrypted
+            .derive(&hd::account_path(1).expect("valid index"))
+            .expect("derivation failed");
+        assert_ne!(second.address(), default_account.address());
+        assert_eq!(
+            SigningKey::generate(KeyType::Ed25519)
+                .expect("key generation failed")
+                .derive(hd::DEFAULT_DERIVATION_PATH)
+                .err(),
+            Some(Error::UnsupportedKeyType("ed25519".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_secp256k1_prehashed_signature_encodings() {
+        let signing_key = SigningKey::generate(KeyType::Secp256k1).expect("key generation failed");
+        let message = b"\x00\x01binary payload";
+
+        // ECDSA signatures are deterministic, signing the digest yields the same signature
+        let signature = signing_key.sign_bytes(message).expect("signing failed");
+        let prehashed = signing_key
+            .sign_digest(&ethereum::keccak256(message))
+            .expect("signing failed");
+        assert_eq!(signature, prehashed);
+        assert!(matches!(
+            signing_key.sign_digest(&[0u8; 20]),
+            Err(Error::InvalidPayload(_))
+        ));
+
+        let raw = signing_key
+            .encode_signature(&signature, SignatureEncoding::Raw)
+            .expect("encoding failed");
+        assert_eq!(raw, hex::encode(&signature[..64]));
+        let der = signing_key
+            .encode_signature(&signature, SignatureEncoding::Der)
+            .expect("encoding failed");
+        let der = EcdsaSignature::from_der(&hex::decode(der).expect("hex")).expect("der");
+        assert_eq!(der.to_bytes().as_slice(), &signature[..64]);
+    }
+
+    #[test]
+    fn test_rsa_message_and_digest_signatures() {
+        use rsa::sha2::{Digest, Sha256, Sha384};
+
+        let message = "message";
+        let signing_key =
+            SigningKey::generate_rsa(RsaOptions::default()).expect("key generation failed");
+        // PKCS#1 v1.5 signatures are deterministic, signing the digest yields the same signature
+        let signature = signing_key
+            .sign_bytes(message.as_bytes())
+            .expect("signing failed");
+        let prehashed = signing_key
+            .sign_digest(&Sha256::digest(message))
+            .expect("signing failed");
+        assert_eq!(signature, prehashed);
+        assert!(signing_key
+            .verify_signature(message, &signature)
+            .expect("verification failed"));
+
+        let signing_key = SigningKey::generate_rsa(RsaOptions {
+            key_size: RsaKeySize::default(),
+            scheme: RsaScheme {
+                padding: RsaPadding::Pss,
+                digest: HashAlgorithm::Sha384,
+            },
+        })
+        .expect("key generation failed");
+        for signature in [
+            signing_key.sign_bytes(message.as_bytes()),
+            signing_key.sign_digest(&Sha384::digest(message)),
+        ] {
+            let signature = signature.expect("signing failed");
+            assert!(signing_key
+                .verify_signature(message, &signature)
+                .expect("verification failed"));
         }
+        assert!(matches!(
+            signing_key.sign_digest(&Sha256::digest(message)),
+            Err(Error::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_ed25519_cannot_sign_digest_or_encode_der() {
+        let signing_key = SigningKey::generate(KeyType::Ed25519).expect("key generation failed");
+        assert!(matches!(
+            signing_key.sign_digest(&[0u8; 32]),
+            Err(Error::InvalidPayload(_))
+        ));
+
+        let signature = signing_key.sign_bytes(b"message").expect("signing failed");
+        assert!(matches!(
+            signing_key.encode_signature(&signature, SignatureEncoding::Der),
+            Err(Error::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn test_rsa_options_deserialize() {
+        let options: RsaOptions =
+            serde_json::from_str(r#"{"key_size": 3072, "padding": "pss", "digest": "sha384"}"#)
+                .expect("valid options");
+        assert_eq!(options.key_size, RsaKeySize::Bits3072);
+        assert_eq!(options.scheme.padding, RsaPadding::Pss);
+        assert_eq!(options.scheme.digest, HashAlgorithm::Sha384);
+
+        let options: RsaOptions = serde_json::from_str("{}").expect("valid options");
+        assert_eq!(options, RsaOptions::default());
+
+        assert!(serde_json::from_str::<RsaOptions>(r#"{"key_size": 1024}"#).is_err());
+        assert!(serde_json::from_str::<RsaOptions>(r#"{"digest": "md5"}"#).is_err());
+    }
+
+    #[test]
+    fn test_ed25519_key_cannot_personal_sign() {
+        let signing_key = SigningKey::generate(KeyType::Ed25519).expect("key generation failed");
+        assert_eq!(
+            signing_key.sign_personal_message(b"message").err(),
+            Some(Error::UnsupportedKeyType("ed25519".to_string()))
+        );
     }
 }
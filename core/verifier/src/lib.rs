@@ -0,0 +1,284 @@
+//! Offline verification of signatures produced by the wallet `sign` endpoint.
+//!
+//! The crate only depends on the primitives of the enabled algorithms, so tenants can
+//! embed it to check signatures against the `pub_key` returned at user registration
+//! without calling the wallet service. Each algorithm sits behind a cargo feature of
+//! the same name: `rsa`, `secp256k1` and `ed25519`, all enabled by default. The `serde`
+//! feature adds (de)serialization of the RSA scheme types.
+//!
+//! ```ignore
+//! let valid = verifier::verify(&pub_key_pem, message.as_bytes(), &hex::decode(signature)?)?;
+//! ```
+
+pub mod scheme;
+
+pub use scheme::{HashAlgorithm, RsaPadding, RsaScheme};
+use std::fmt::Display;
+
+#[cfg(not(any(feature = "rsa", feature = "secp256k1", feature = "ed25519")))]
+compile_error!("at least one of the `rsa`, `secp256k1` or `ed25519` features must be enabled");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The public key is not a PEM encoded SPKI key of an enabled algorithm.
+    UnsupportedPublicKey,
+    /// The signature is not well formed for the key algorithm.
+    MalformedSignature,
+    /// Unknown RSA padding or digest.
+    UnsupportedScheme,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnsupportedPublicKey => f.write_str("unsupported public key"),
+            Error::MalformedSignature => f.write_str("malformed signature"),
+            Error::UnsupportedScheme => f.write_str("unsupported signature scheme"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Public key parsed from its PEM encoded SubjectPublicKeyInfo.
+pub enum PublicKey {
+    #[cfg(feature = "rsa")]
+    Rsa(rsa::RsaPublicKey, RsaScheme),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    #[cfg(feature = "ed25519")]
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl PublicKey {
+    pub fn from_pem(pem: &str) -> Result<Self, Error> {
+        #[cfg(feature = "rsa")]
+        {
+            use rsa::pkcs8::DecodePublicKey;
+            if let Ok(key) = rsa::RsaPublicKey::from_public_key_pem(pem) {
+                return Ok(PublicKey::Rsa(key, RsaScheme::default()));
+            }
+        }
+        #[cfg(feature = "secp256k1")]
+        {
+            use k256::pkcs8::DecodePublicKey;
+            if let Ok(key) = k256::ecdsa::VerifyingKey::from_public_key_pem(pem) {
+                return Ok(PublicKey::Secp256k1(key));
+            }
+        }
+        #[cfg(feature = "ed25519")]
+        {
+            use ed25519_dalek::pkcs8::DecodePublicKey;
+            if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(pem) {
+                return Ok(PublicKey::Ed25519(key));
+            }
+        }
+        Err(Error::UnsupportedPublicKey)
+    }
+
+    /// Sets the scheme used to check RSA signatures, other keys are left unchanged.
+    pub fn with_rsa_scheme(self, scheme: RsaScheme) -> Self {
+        match self {
+            #[cfg(feature = "rsa")]
+            PublicKey::Rsa(key, _) => PublicKey::Rsa(key, scheme),
+            #[allow(unreachable_patterns)]
+            other => {
+                let _ = scheme;
+                other
+            }
+        }
+    }
+
+    /// Checks the signature of the message.
+    ///
+    /// RSA signatures follow the key's [`RsaScheme`], secp256k1 signatures are `r || s` or
+    /// `r || s || v` over the Keccak-256 digest of the message, Ed25519 signatures are
+    /// verified in strict mode.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<bool, Error> {
+        match self {
+            #[cfg(feature = "rsa")]
+            PublicKey::Rsa(public_key, scheme) => {
+                use rsa::sha2::{Sha256, Sha384, Sha512};
+
+                match scheme.digest {
+                    HashAlgorithm::Sha256 => {
+                        verify_rsa::<Sha256>(public_key, scheme.padding, message, signature)
+                    }
+                    HashAlgorithm::Sha384 => {
+                        verify_rsa::<Sha384>(public_key, scheme.padding, message, signature)
+                    }
+                    HashAlgorithm::Sha512 => {
+                        verify_rsa::<Sha512>(public_key, scheme.padding, message, signature)
+                    }
+                }
+            }
+            #[cfg(feature = "secp256k1")]
+            PublicKey::Secp256k1(verifying_key) => {
+                use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature};
+                use sha3::{Digest, Keccak256};
+
+                // The trailing recovery byte is not needed when the public key is known
+                if signature.len() != 64 && signature.len() != 65 {
+                    return Err(Error::MalformedSignature);
+                }
+                let signature = Signature::from_slice(&signature[..64])
+                    .map_err(|_| Error::MalformedSignature)?;
+                let digest = Keccak256::digest(message);
+                Ok(verifying_key.verify_prehash(&digest, &signature).is_ok())
+            }
+            #[cfg(feature = "ed25519")]
+            PublicKey::Ed25519(verifying_key) => {
+                let signature = ed25519_dalek::Signature::from_slice(signature)
+                    .map_err(|_| Error::MalformedSignature)?;
+                Ok(verifying_key.verify_strict(message, &signature).is_ok())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rsa")]
+fn verify_rsa<D>(
+    public_key: &rsa::RsaPublicKey,
+    padding: RsaPadding,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error>
+where
+    D: rsa::sha2::Digest
+        + rsa::sha2::digest::FixedOutputReset
+        + rsa::pkcs8::spki::der::oid::AssociatedOid,
+{
+    use rsa::{pkcs1v15, pss, signature::Verifier};
+
+    match padding {
+        RsaPadding::Pkcs1v15 => {
+            let signature =
+                pkcs1v15::Signature::try_from(signature).map_err(|_| Error::MalformedSignature)?;
+            let verifying_key = pkcs1v15::VerifyingKey::<D>::new(public_key.clone());
+            Ok(verifying_key.verify(message, &signature).is_ok())
+        }
+        RsaPadding::Pss => {
+            let signature =
+                pss::Signature::try_from(signature).map_err(|_| Error::MalformedSignature)?;
+            let verifying_key = pss::VerifyingKey::<D>::new(public_key.clone());
+            Ok(verifying_key.verify(message, &signature).is_ok())
+        }
+    }
+}
+
+/// Checks the signature of the message against a PEM encoded public key, RSA signatures
+/// being PKCS#1 v1.5 SHA-256.
+pub fn verify(public_key_pem: &str, message: &[u8], signature: &[u8]) -> Result<bool, Error> {
+    verify_with_scheme(public_key_pem, RsaScheme::default(), message, signature)
+}
+
+/// Checks the signature of the message against a PEM encoded public key, RSA signatures
+/// following the given scheme.
+pub fn verify_with_scheme(
+    public_key_pem: &str,
+    rsa_scheme: RsaScheme,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    PublicKey::from_pem(public_key_pem)?
+        .with_rsa_scheme(rsa_scheme)
+        .verify(message, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{verify, Error};
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_secp256k1_signature() {
+        use k256::{
+            ecdsa::SigningKey,
+            pkcs8::{EncodePublicKey, LineEnding},
+        };
+        use sha3::{Digest, Keccak256};
+
+        let signing_key = SigningKey::from_slice(&[1u8; 32]).expect("valid key");
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(&Keccak256::digest(b"message"))
+            .expect("signing");
+        let mut recoverable = signature.to_bytes().to_vec();
+        recoverable.push(27 + recovery_id.to_byte());
+
+        assert_eq!(verify(&pem, b"message", &recoverable), Ok(true));
+        assert_eq!(verify(&pem, b"message", &recoverable[..64]), Ok(true));
+        assert_eq!(verify(&pem, b"tampered", &recoverable), Ok(false));
+        assert_eq!(
+            verify(&pem, b"message", &recoverable[..10]),
+            Err(Error::MalformedSignature)
+        );
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn test_ed25519_signature() {
+        use ed25519_dalek::{
+            pkcs8::{spki::der::pem::LineEnding, EncodePublicKey},
+            Signer, SigningKey,
+        };
+
+        let signing_key = SigningKey::from_bytes(&[2u8; 32]);
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        let signature = signing_key.sign(b"message").to_bytes();
+
+        assert_eq!(verify(&pem, b"message", &signature), Ok(true));
+        assert_eq!(verify(&pem, b"tampered", &signature), Ok(false));
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn test_rsa_pss_signature() {
+        use crate::{verify_with_scheme, HashAlgorithm, RsaPadding, RsaScheme};
+        use rsa::{
+            pkcs8::{EncodePublicKey, LineEnding},
+            pss,
+            sha2::Sha384,
+            signature::{RandomizedSigner, SignatureEncoding},
+            RsaPrivateKey,
+        };
+
+        // Small key to keep the test fast, the wallet only issues 2048 bits keys and above
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).expect("rsa key");
+        let pem = private_key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .expect("pem");
+        let signature = pss::SigningKey::<Sha384>::new(private_key)
+            .sign_with_rng(&mut rand::thread_rng(), b"message")
+            .to_vec();
+        let scheme = RsaScheme {
+            padding: RsaPadding::Pss,
+            digest: HashAlgorithm::Sha384,
+        };
+
+        assert_eq!(
+            verify_with_scheme(&pem, scheme, b"message", &signature),
+            Ok(true)
+        );
+        assert_eq!(
+            verify_with_scheme(&pem, scheme, b"tampered", &signature),
+            Ok(false)
+        );
+        assert_eq!(verify(&pem, b"message", &signature), Ok(false));
+    }
+
+    #[test]
+    fn test_unsupported_public_key() {
+        assert_eq!(
+            verify("not a pem", b"message", &[0u8; 64]),
+            Err(Error::UnsupportedPublicKey)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::Error;
+use std::{fmt::Display, str::FromStr};
+
+/// Padding of RSA signatures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RsaPadding {
+    /// RSASSA-PKCS1-v1_5
+    #[default]
+    Pkcs1v15,
+    /// RSASSA-PSS with MGF1 and a salt as long as the digest
+    Pss,
+}
+
+impl Display for RsaPadding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RsaPadding::Pkcs1v15 => f.write_str("pkcs1v15"),
+            RsaPadding::Pss => f.write_str("pss"),
+        }
+    }
+}
+
+impl FromStr for RsaPadding {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "pkcs1v15" => Ok(RsaPadding::Pkcs1v15),
+            "pss" => Ok(RsaPadding::Pss),
+            _ => Err(Error::UnsupportedScheme),
+        }
+    }
+}
+
+/// Digest of the message signed with RSA keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => f.write_str("sha256"),
+            HashAlgorithm::Sha384 => f.write_str("sha384"),
+            HashAlgorithm::Sha512 => f.write_str("sha512"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha384" => Ok(HashAlgorithm::Sha384),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::UnsupportedScheme),
+        }
+    }
+}
+
+/// RSA signature scheme, PKCS#1 v1.5 with SHA-256 unless chosen otherwise at registration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RsaScheme {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub padding: RsaPadding,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub digest: HashAlgorithm,
+}
@@ -0,0 +1,8 @@
+CREATE TABLE nonces (
+  api_key     UUID         NOT NULL,
+  nonce       TEXT         NOT NULL,
+  expires_at  TIMESTAMPTZ  NOT NULL,
+  PRIMARY KEY (api_key, nonce)
+);
+
+CREATE INDEX nonces_expires_at_idx ON nonces (expires_at);
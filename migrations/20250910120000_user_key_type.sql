@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN key_type TEXT NOT NULL DEFAULT 'rsa';
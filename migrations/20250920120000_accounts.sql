@@ -0,0 +1,7 @@
+CREATE TABLE accounts (
+  user_id       UUID     NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  account_index INTEGER  NOT NULL,
+  path          TEXT     NOT NULL,
+  address       TEXT     NOT NULL,
+  PRIMARY KEY (user_id, account_index)
+);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN public_key TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN rsa_padding TEXT;
+ALTER TABLE users ADD COLUMN rsa_digest TEXT;
+
+UPDATE users SET rsa_padding = 'pkcs1v15', rsa_digest = 'sha256' WHERE key_type = 'rsa';
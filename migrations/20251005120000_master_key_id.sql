@@ -0,0 +1,8 @@
+ALTER TABLE credentials ADD COLUMN master_key_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE users ADD COLUMN master_key_id TEXT NOT NULL DEFAULT 'default';
+
+ALTER TABLE credentials ALTER COLUMN master_key_id DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN master_key_id DROP DEFAULT;
+
+CREATE INDEX credentials_master_key_id ON credentials (master_key_id);
+CREATE INDEX users_master_key_id ON users (master_key_id);
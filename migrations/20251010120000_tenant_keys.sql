@@ -0,0 +1,7 @@
+CREATE TABLE tenant_keys (
+  client_id      UUID  PRIMARY KEY REFERENCES clients(id),
+  encrypted_key  TEXT  NOT NULL,
+  master_key_id  TEXT  NOT NULL
+);
+
+CREATE INDEX tenant_keys_master_key_id ON tenant_keys (master_key_id);
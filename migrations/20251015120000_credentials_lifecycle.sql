@@ -0,0 +1,5 @@
+ALTER TABLE credentials ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE credentials ADD COLUMN expires_at TIMESTAMPTZ;
+ALTER TABLE credentials ADD COLUMN revoked_at TIMESTAMPTZ;
+
+CREATE INDEX credentials_client_id ON credentials (client_id);
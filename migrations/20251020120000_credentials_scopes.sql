@@ -0,0 +1,3 @@
+ALTER TABLE credentials ADD COLUMN scopes TEXT[] NOT NULL DEFAULT ARRAY['users:register', 'users:sign', 'users:revoke', 'users:read'];
+
+ALTER TABLE credentials ALTER COLUMN scopes DROP DEFAULT;
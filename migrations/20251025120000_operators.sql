@@ -0,0 +1,7 @@
+CREATE TABLE operators (
+  id             UUID         PRIMARY KEY,
+  username       TEXT         NOT NULL UNIQUE,
+  password_hash  TEXT         NOT NULL,
+  role           TEXT         NOT NULL,
+  created_at     TIMESTAMPTZ  NOT NULL DEFAULT now()
+);
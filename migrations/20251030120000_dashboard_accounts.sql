@@ -0,0 +1,18 @@
+CREATE TABLE dashboard_accounts (
+  id             UUID         PRIMARY KEY,
+  client_id      UUID         NOT NULL REFERENCES clients(id),
+  email          TEXT         NOT NULL UNIQUE,
+  password_hash  TEXT         NOT NULL,
+  created_at     TIMESTAMPTZ  NOT NULL DEFAULT now()
+);
+
+CREATE INDEX dashboard_accounts_client_id ON dashboard_accounts (client_id);
+
+CREATE TABLE password_resets (
+  token_hash  TEXT         PRIMARY KEY,
+  account_id  UUID         NOT NULL REFERENCES dashboard_accounts(id) ON DELETE CASCADE,
+  expires_at  TIMESTAMPTZ  NOT NULL,
+  used_at     TIMESTAMPTZ
+);
+
+CREATE INDEX password_resets_account_id ON password_resets (account_id);
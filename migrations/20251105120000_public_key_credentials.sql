@@ -0,0 +1,14 @@
+ALTER TABLE credentials ADD COLUMN credential_type TEXT NOT NULL DEFAULT 'hmac';
+ALTER TABLE credentials ADD COLUMN public_key TEXT;
+
+-- Public-key credentials hold no secret, hence no data key
+ALTER TABLE credentials ALTER COLUMN encrypted_secret DROP NOT NULL;
+ALTER TABLE credentials ALTER COLUMN encrypted_data_key DROP NOT NULL;
+ALTER TABLE credentials ALTER COLUMN master_key_id DROP NOT NULL;
+
+ALTER TABLE credentials ADD CONSTRAINT credentials_type_material CHECK (
+  CASE credential_type
+    WHEN 'hmac' THEN encrypted_secret IS NOT NULL AND encrypted_data_key IS NOT NULL AND master_key_id IS NOT NULL
+    ELSE public_key IS NOT NULL AND encrypted_secret IS NULL
+  END
+);
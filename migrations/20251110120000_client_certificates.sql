@@ -0,0 +1,7 @@
+CREATE TABLE client_certificates (
+  fingerprint  TEXT         PRIMARY KEY,
+  client_id    UUID         NOT NULL REFERENCES clients(id),
+  created_at   TIMESTAMPTZ  NOT NULL DEFAULT now()
+);
+
+CREATE INDEX client_certificates_client_id ON client_certificates (client_id);
@@ -0,0 +1,12 @@
+CREATE TABLE client_networks (
+  client_id         UUID         PRIMARY KEY REFERENCES clients(id),
+  allowed_networks  TEXT[]       NOT NULL,
+  updated_at        TIMESTAMPTZ  NOT NULL DEFAULT now()
+);
+
+CREATE TABLE credential_lockouts (
+  api_key          UUID         PRIMARY KEY REFERENCES credentials(api_key),
+  failures         INTEGER      NOT NULL,
+  last_failure_at  TIMESTAMPTZ  NOT NULL,
+  locked_until     TIMESTAMPTZ
+);
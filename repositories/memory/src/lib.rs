@@ -0,0 +1,30 @@
+pub mod nonce;
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::Mutex,
+};
+use uuid::Uuid;
+
+/// In-process storage, suitable for single instance deployments and tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    nonces: Mutex<Nonces>,
+}
+
+type NonceKey = (Uuid, String);
+
+/// Used nonces with their expiry, and the same entries ordered by expiry so that expired ones
+/// are dropped without scanning the whole map.
+#[derive(Default)]
+struct Nonces {
+    expiries: HashMap<NonceKey, u64>,
+    queue: BinaryHeap<Reverse<(u64, NonceKey)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{MemoryStore, Nonces};
+use repositories::nonce::NonceRepository;
+use std::{
+    cmp::Reverse,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use types::{api_key::ApiKey, secret::mask::Masked};
+
+impl NonceRepository for MemoryStore {
+    async fn use_nonce(
+        &self,
+        api_key: &Masked<ApiKey>,
+        nonce: &str,
+        expires_at: u64,
+    ) -> anyhow::Result<bool> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut nonces = self
+            .nonces
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Nonce store lock poisoned"))?;
+
+        nonces.prune(now);
+
+        let key = (api_key.expose().to_uuid(), nonce.to_string());
+        if nonces.expiries.contains_key(&key) {
+            return Ok(false);
+        }
+        nonces.expiries.insert(key.clone(), expires_at);
+        nonces.queue.push(Reverse((expires_at, key)));
+        Ok(true)
+    }
+}
+
+impl Nonces {
+    /// Drops the nonces that expired before `now`, earliest first.
+    fn prune(&mut self, now: u64) {
+        while let Some(Reverse((expires_at, _))) = self.queue.peek() {
+            if *expires_at >= now {
+                break;
+            }
+            if let Some(Reverse((expires_at, key))) = self.queue.pop() {
+                // The nonce may have been used again since, with a later expiry
+                if self.expiries.get(&key) == Some(&expires_at) {
+                    self.expiries.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryStore;
+    use repositories::nonce::NonceRepository;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use types::{api_key::ApiKey, secret::mask::Masked};
+    use uuid::Uuid;
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[tokio::test]
+    async fn test_nonce_is_rejected_within_expiry() {
+        let store = MemoryStore::new();
+        let api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+        let other_api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+
+        assert!(store
+            .use_nonce(&api_key, "nonce", now() + 60)
+            .await
+            .unwrap());
+        assert!(!store
+            .use_nonce(&api_key, "nonce", now() + 60)
+            .await
+            .unwrap());
+        assert!(store
+            .use_nonce(&other_api_key, "nonce", now() + 60)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_nonce_is_accepted_again() {
+        let store = MemoryStore::new();
+        let api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+
+        assert!(store.use_nonce(&api_key, "nonce", now() - 1).await.unwrap());
+        assert!(store
+            .use_nonce(&api_key, "nonce", now() + 60)
+            .await
+            .unwrap());
+        assert!(!store
+            .use_nonce(&api_key, "nonce", now() + 60)
+            .await
+            .unwrap());
+
+        let nonces = store.nonces.lock().unwrap();
+        assert_eq!(nonces.expiries.len(), 1);
+        assert_eq!(nonces.queue.len(), 1);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::PostgresPool;
+use repositories::certificate::CertificateRepository;
+use types::{
+    certificate::{CertificateFingerprint, CertificateInfo},
+    client::ClientId,
+};
+use uuid::Uuid;
+
+impl CertificateRepository for PostgresPool {
+    async fn create_certificate(
+        &self,
+        client_id: ClientId,
+        fingerprint: &CertificateFingerprint,
+    ) -> anyhow::Result<Option<CertificateInfo>> {
+        let res = sqlx::query_as(
+            r#"
+        INSERT INTO client_certificates (fingerprint, client_id) VALUES ($1, $2)
+        ON CONFLICT (fingerprint) DO NOTHING
+        RETURNING fingerprint, EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+        "#,
+        )
+        .bind(fingerprint)
+        .bind::<Uuid>(client_id.into())
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn list_certificates(&self, client_id: ClientId) -> anyhow::Result<Vec<CertificateInfo>> {
+        let res = sqlx::query_as(
+            r#"
+        SELECT fingerprint, EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+        FROM client_certificates
+        WHERE client_id = $1
+        ORDER BY created_at, fingerprint
+        "#,
+        )
+        .bind::<Uuid>(client_id.into())
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn delete_certificate(
+        &self,
+        client_id: ClientId,
+        fingerprint: &CertificateFingerprint,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "DELETE FROM client_certificates WHERE client_id = $1 AND fingerprint = $2",
+        )
+        .bind::<Uuid>(client_id.into())
+        .bind(fingerprint)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,26 +1,109 @@
-use crate::PostgresPool;
-use repositories::client::ClientRepository;
-use types::client::{encrypt::EncryptedClient, ClientId};
+use crate::{dashboard::insert_account, PostgresPool};
+use repositories::{
+    client::{ClientRepository, CreateClientResult},
+    tenant_key::TenantKeyRepository,
+};
+use sqlx::PgConnection;
+use types::{
+    client::{encrypt::EncryptedClient, ClientId},
+    dashboard::DashboardAccount,
+    tenant::encrypt::EncryptedTenantKey,
+    user::UserInfo,
+};
 use uuid::Uuid;
 
+/// Returns `false` if a client with the same id exists.
+async fn insert_client(
+    connection: &mut PgConnection,
+    client: EncryptedClient,
+) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        r#"
+    WITH inserted_client AS (
+        INSERT INTO clients (id, name) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING RETURNING id
+    )
+    INSERT INTO credentials (client_id, api_key, encrypted_secret, encrypted_data_key, master_key_id, scopes)
+    SELECT id, $3, $4, $5, $6, $7 FROM inserted_client
+    "#,
+    )
+    .bind::<Uuid>(client.id.into())
+    .bind(client.name)
+    .bind(client.credentials.api_key)
+    .bind(client.credentials.encrypted_secret)
+    .bind(client.credentials.encrypted_data_key)
+    .bind(client.credentials.master_key_id)
+    .bind(client.credentials.scopes)
+    .execute(connection)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes the tenant key of a client that failed to be created, at best: a key left over is
+/// deleted by the next creation of the client.
+async fn discard_tenant_key(key_store: &impl TenantKeyRepository, client_id: ClientId) {
+    if let Err(err) = key_store.delete_tenant_key(client_id.clone()).await {
+        tracing::error!(
+            "Failed to delete tenant key of client {:?} not created: {}",
+            client_id,
+            err
+        );
+    }
+}
+
 impl ClientRepository for PostgresPool {
     async fn create(&self, client: EncryptedClient) -> anyhow::Result<()> {
-        sqlx::query(
-            r#"
-        WITH inserted_client AS (INSERT INTO clients (id, name) VALUES ($1, $2))
-        INSERT INTO credentials (client_id, api_key, encrypted_secret, encrypted_data_key) VALUES ($1, $3, $4, $5)
-        "#,
-        )
-        .bind::<Uuid>(client.id.into())
-        .bind(client.name)
-        .bind(client.credentials.api_key)
-        .bind(client.credentials.encrypted_secret)
-        .bind(client.credentials.encrypted_data_key)
-        .execute(&self.pg_pool)
-        .await?;
+        let client_id = client.id.clone();
+        let mut connection = self.pg_pool.acquire().await?;
+        anyhow::ensure!(
+            insert_client(&mut connection, client).await?,
+            "client {:?} already exists",
+            client_id
+        );
         Ok(())
     }
 
+    async fn create_with_tenant_key(
+        &self,
+        client: EncryptedClient,
+        tenant_key: EncryptedTenantKey,
+        dashboard_account: Option<DashboardAccount>,
+        key_store: &(impl TenantKeyRepository + Sync),
+    ) -> anyhow::Result<CreateClientResult> {
+        let client_id = client.id.clone();
+        let mut tx = self.pg_pool.begin().await?;
+        if !insert_client(&mut tx, client).await? {
+            return Ok(CreateClientResult::NameTaken);
+        }
+        if let Some(account) = dashboard_account {
+            if !insert_account(&mut *tx, &account).await? {
+                return Ok(CreateClientResult::EmailTaken);
+            }
+        }
+
+        // The key store is another database, the tenant key is not part of the transaction.
+        // Inserting the client holds back other creations of the same id until it ends, so a
+        // tenant key already stored was left over by a creation that failed.
+        key_store.delete_tenant_key(client_id.clone()).await?;
+        let created = match key_store.create_tenant_key(tenant_key).await {
+            Ok(created) => created,
+            Err(err) => {
+                discard_tenant_key(key_store, client_id).await;
+                return Err(err);
+            }
+        };
+        anyhow::ensure!(
+            created,
+            "tenant key of client {:?} created concurrently",
+            client_id
+        );
+
+        if let Err(err) = tx.commit().await {
+            discard_tenant_key(key_store, client_id).await;
+            return Err(err.into());
+        }
+        Ok(CreateClientResult::Created)
+    }
+
     async fn find(&self, client_id: ClientId) -> anyhow::Result<Option<EncryptedClient>> {
         let res = sqlx::query_as(
             r#"
@@ -29,9 +112,16 @@ impl ClientRepository for PostgresPool {
             clients.name,
             credentials.api_key,
             credentials.encrypted_secret,
-            credentials.encrypted_data_key
+            credentials.encrypted_data_key,
+            credentials.master_key_id,
+            credentials.scopes
         FROM clients
-        INNER JOIN credentials ON (credentials.client_id = clients.id)
+        INNER JOIN LATERAL (
+            SELECT * FROM credentials
+            WHERE credentials.client_id = clients.id AND credential_type = 'hmac'
+            ORDER BY (revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())) DESC, created_at
+            LIMIT 1
+        ) credentials ON TRUE
         WHERE clients.id = $1
         "#,
         )
@@ -42,4 +132,72 @@ impl ClientRepository for PostgresPool {
         tracing::debug!("Find client by id '{:?}' result: {:?}", client_id, res);
         Ok(res)
     }
+
+    async fn delete(&self, client_id: ClientId) -> anyhow::Result<bool> {
+        let client_id: Uuid = client_id.into();
+        let mut tx = self.pg_pool.begin().await?;
+
+        sqlx::query("DELETE FROM users WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM client_networks WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM client_certificates WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM dashboard_accounts WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "DELETE FROM nonces WHERE api_key IN (SELECT api_key FROM credentials WHERE client_id = $1)",
+        )
+        .bind(client_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM credential_lockouts WHERE api_key IN (SELECT api_key FROM credentials WHERE client_id = $1)",
+        )
+        .bind(client_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM credentials WHERE client_id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM clients WHERE id = $1")
+            .bind(client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_users(
+        &self,
+        client_id: ClientId,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<UserInfo>> {
+        let res = sqlx::query_as(
+            r#"
+        SELECT id, key_type, public_key FROM users
+        WHERE client_id = $1
+        ORDER BY id
+        LIMIT $2 OFFSET $3
+        "#,
+        )
+        .bind::<Uuid>(client_id.into())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
 }
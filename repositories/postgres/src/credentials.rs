@@ -0,0 +1,118 @@
+use crate::PostgresPool;
+use repositories::credentials::CredentialsRepository;
+use types::{
+    api_key::ApiKey,
+    client::{encrypt::EncryptedCredentials, ClientId, CredentialsInfo},
+    client_key::PublicKeyCredentials,
+    secret::mask::Masked,
+};
+use uuid::Uuid;
+
+const CREDENTIALS_INFO: &str = r#"
+    api_key,
+    credential_type,
+    EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at,
+    EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at,
+    EXTRACT(EPOCH FROM revoked_at)::BIGINT AS revoked_at,
+    scopes
+"#;
+
+impl CredentialsRepository for PostgresPool {
+    async fn create_credentials(
+        &self,
+        credentials: EncryptedCredentials,
+        expires_at: Option<i64>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+        INSERT INTO credentials (client_id, api_key, encrypted_secret, encrypted_data_key, master_key_id, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, to_timestamp($7))
+        "#,
+        )
+        .bind::<Uuid>(credentials.client_id.into())
+        .bind(credentials.api_key)
+        .bind(credentials.encrypted_secret)
+        .bind(credentials.encrypted_data_key)
+        .bind(credentials.master_key_id)
+        .bind(credentials.scopes)
+        .bind(expires_at.map(|expires_at| expires_at as f64))
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn create_public_key_credentials(
+        &self,
+        credentials: &PublicKeyCredentials,
+        expires_at: Option<i64>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+        INSERT INTO credentials (client_id, api_key, credential_type, public_key, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, to_timestamp($6))
+        "#,
+        )
+        .bind::<Uuid>(credentials.client_id.clone().into())
+        .bind(credentials.api_key.expose().to_uuid())
+        .bind(credentials.credential_type)
+        .bind(&credentials.public_key)
+        .bind(&credentials.scopes)
+        .bind(expires_at.map(|expires_at| expires_at as f64))
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_credentials(&self, client_id: ClientId) -> anyhow::Result<Vec<CredentialsInfo>> {
+        let res = sqlx::query_as(&format!(
+            "SELECT {CREDENTIALS_INFO} FROM credentials WHERE client_id = $1 ORDER BY credentials.created_at, api_key"
+        ))
+        .bind::<Uuid>(client_id.into())
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn set_credentials_expiry(
+        &self,
+        client_id: ClientId,
+        api_key: &Masked<ApiKey>,
+        expires_at: Option<i64>,
+    ) -> anyhow::Result<Option<CredentialsInfo>> {
+        let res = sqlx::query_as(&format!(
+            r#"
+        UPDATE credentials SET expires_at = to_timestamp($3)
+        WHERE client_id = $1 AND api_key = $2 AND revoked_at IS NULL
+            AND (expires_at IS NULL OR expires_at > now())
+        RETURNING {CREDENTIALS_INFO}
+        "#
+        ))
+        .bind::<Uuid>(client_id.into())
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .bind(expires_at.map(|expires_at| expires_at as f64))
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn revoke_credentials(
+        &self,
+        client_id: ClientId,
+        api_key: &Masked<ApiKey>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+        UPDATE credentials SET revoked_at = now()
+        WHERE client_id = $1 AND api_key = $2 AND revoked_at IS NULL
+        "#,
+        )
+        .bind::<Uuid>(client_id.into())
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
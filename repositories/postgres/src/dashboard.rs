@@ -0,0 +1,93 @@
+use crate::PostgresPool;
+use repositories::dashboard::DashboardRepository;
+use sqlx::PgExecutor;
+use types::dashboard::DashboardAccount;
+use uuid::Uuid;
+
+/// Returns `false` if the email address is already taken.
+pub(crate) async fn insert_account(
+    executor: impl PgExecutor<'_>,
+    account: &DashboardAccount,
+) -> anyhow::Result<bool> {
+    let result = sqlx::query(
+        r#"
+    INSERT INTO dashboard_accounts (id, client_id, email, password_hash) VALUES ($1, $2, $3, $4)
+    ON CONFLICT (email) DO NOTHING
+    "#,
+    )
+    .bind(account.id)
+    .bind(&account.client_id)
+    .bind(&account.email)
+    .bind(account.password_hash())
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+impl DashboardRepository for PostgresPool {
+    async fn create_account(&self, account: DashboardAccount) -> anyhow::Result<bool> {
+        insert_account(&self.pg_pool, &account).await
+    }
+
+    async fn find_account(&self, email: &str) -> anyhow::Result<Option<DashboardAccount>> {
+        let res = sqlx::query_as(
+            "SELECT id, client_id, email, password_hash FROM dashboard_accounts WHERE email = $1",
+        )
+        .bind(email)
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn create_password_reset(
+        &self,
+        account_id: Uuid,
+        token_hash: &str,
+        expires_at: i64,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO password_resets (token_hash, account_id, expires_at) VALUES ($1, $2, to_timestamp($3))",
+        )
+        .bind(token_hash)
+        .bind(account_id)
+        .bind(expires_at)
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn reset_password(&self, token_hash: &str, password_hash: &str) -> anyhow::Result<bool> {
+        let mut tx = self.pg_pool.begin().await?;
+
+        let account_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+        UPDATE password_resets SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING account_id
+        "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(account_id) = account_id else {
+            return Ok(false);
+        };
+
+        sqlx::query("UPDATE dashboard_accounts SET password_hash = $2 WHERE id = $1")
+            .bind(account_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE password_resets SET used_at = now() WHERE account_id = $1 AND used_at IS NULL",
+        )
+        .bind(account_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
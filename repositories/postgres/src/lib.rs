@@ -1,16 +1,42 @@
+pub mod certificate;
 pub mod client;
+pub mod credentials;
+pub mod dashboard;
+pub mod lockout;
+pub mod master_key;
+pub mod network;
+pub mod nonce;
+pub mod operator;
+pub mod tenant_key;
 pub mod wallet;
 
 use secrecy::ExposeSecret;
 use types::db::DatabaseConnection;
 
+#[derive(Clone)]
 pub struct PostgresPool {
     pub pg_pool: sqlx::PgPool,
 }
 
 impl PostgresPool {
     pub async fn new(settings: &impl DatabaseConnection) -> Result<Self, sqlx::Error> {
-        let pg_pool = sqlx::PgPool::connect(&settings.connection_string().expose_secret()).await?;
+        let pg_pool = sqlx::PgPool::connect(settings.connection_string().expose_secret()).await?;
+        Ok(Self { pg_pool })
+    }
+}
+
+/// Database of the tenant keys, migrated from `migrations/key_store`.
+///
+/// Kept apart from the main database so that backups of the main database hold no tenant key:
+/// deleting the tenant key of a client shreds every copy of its data keys.
+#[derive(Clone)]
+pub struct KeyStore {
+    pub pg_pool: sqlx::PgPool,
+}
+
+impl KeyStore {
+    pub async fn new(settings: &impl DatabaseConnection) -> Result<Self, sqlx::Error> {
+        let pg_pool = sqlx::PgPool::connect(settings.connection_string().expose_secret()).await?;
         Ok(Self { pg_pool })
     }
 }
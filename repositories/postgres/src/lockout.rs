@@ -0,0 +1,89 @@
+use crate::PostgresPool;
+use repositories::lockout::LockoutRepository;
+use types::{api_key::ApiKey, client::ClientId, lockout::Lockout, secret::mask::Masked};
+use uuid::Uuid;
+
+const LOCKOUT: &str = r#"
+    api_key,
+    failures,
+    EXTRACT(EPOCH FROM last_failure_at)::BIGINT AS last_failure_at,
+    EXTRACT(EPOCH FROM locked_until)::BIGINT AS locked_until
+"#;
+
+impl LockoutRepository for PostgresPool {
+    async fn find_lockout(&self, api_key: &Masked<ApiKey>) -> anyhow::Result<Option<Lockout>> {
+        let res = sqlx::query_as(&format!(
+            "SELECT {LOCKOUT} FROM credential_lockouts WHERE api_key = $1"
+        ))
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn record_failure(&self, api_key: &Masked<ApiKey>) -> anyhow::Result<u32> {
+        let failures: i32 = sqlx::query_scalar(
+            r#"
+        INSERT INTO credential_lockouts (api_key, failures, last_failure_at) VALUES ($1, 1, now())
+        ON CONFLICT (api_key) DO UPDATE
+        SET failures = credential_lockouts.failures + 1, last_failure_at = now()
+        RETURNING failures
+        "#,
+        )
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .fetch_one(&self.pg_pool)
+        .await?;
+
+        Ok(failures.try_into()?)
+    }
+
+    async fn lock(&self, api_key: &Masked<ApiKey>, locked_until: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE credential_lockouts SET locked_until = to_timestamp($2) WHERE api_key = $1",
+        )
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .bind(locked_until as f64)
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn clear_failures(&self, api_key: &Masked<ApiKey>) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM credential_lockouts WHERE api_key = $1")
+            .bind::<Uuid>(api_key.expose().clone().into())
+            .execute(&self.pg_pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_lockouts(&self, client_id: ClientId) -> anyhow::Result<Vec<Lockout>> {
+        let res = sqlx::query_as(&format!(
+            r#"
+        SELECT {LOCKOUT} FROM credential_lockouts
+        WHERE api_key IN (SELECT api_key FROM credentials WHERE client_id = $1)
+        ORDER BY last_failure_at DESC, api_key
+        "#
+        ))
+        .bind::<Uuid>(client_id.into())
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn unlock(&self, client_id: ClientId, api_key: &Masked<ApiKey>) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+        DELETE FROM credential_lockouts
+        WHERE api_key = $2 AND api_key IN (SELECT api_key FROM credentials WHERE client_id = $1)
+        "#,
+        )
+        .bind::<Uuid>(client_id.into())
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
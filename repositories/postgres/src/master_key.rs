@@ -0,0 +1,150 @@
+use crate::{KeyStore, PostgresPool};
+use repositories::master_key::MasterKeyRepository;
+use sqlx::{postgres::PgRow, PgPool, Row};
+use types::encrypt::{
+    rotation::{DataKeyOwner, WrappedDataKey},
+    Encrypted,
+};
+use uuid::Uuid;
+
+/// Table, id column and wrapped key column holding the keys of `owner`.
+fn table(owner: DataKeyOwner) -> (&'static str, &'static str, &'static str) {
+    match owner {
+        DataKeyOwner::TenantKeys => ("tenant_keys", "client_id", "encrypted_key"),
+        DataKeyOwner::Credentials => ("credentials", "api_key", "encrypted_data_key"),
+        DataKeyOwner::Users => ("users", "id", "encrypted_data_key"),
+    }
+}
+
+impl MasterKeyRepository for PostgresPool {
+    async fn count_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+    ) -> anyhow::Result<u64> {
+        count_data_keys_to_rewrap(&self.pg_pool, owner, key_id).await
+    }
+
+    async fn find_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+        after: Option<Uuid>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<WrappedDataKey>> {
+        find_data_keys_to_rewrap(&self.pg_pool, owner, key_id, after, limit).await
+    }
+
+    async fn replace_data_key(
+        &self,
+        data_key: &WrappedDataKey,
+        key_id: &str,
+        encrypted_data_key: Encrypted,
+    ) -> anyhow::Result<bool> {
+        replace_data_key(&self.pg_pool, data_key, key_id, encrypted_data_key).await
+    }
+}
+
+impl MasterKeyRepository for KeyStore {
+    async fn count_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+    ) -> anyhow::Result<u64> {
+        count_data_keys_to_rewrap(&self.pg_pool, owner, key_id).await
+    }
+
+    async fn find_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+        after: Option<Uuid>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<WrappedDataKey>> {
+        find_data_keys_to_rewrap(&self.pg_pool, owner, key_id, after, limit).await
+    }
+
+    async fn replace_data_key(
+        &self,
+        data_key: &WrappedDataKey,
+        key_id: &str,
+        encrypted_data_key: Encrypted,
+    ) -> anyhow::Result<bool> {
+        replace_data_key(&self.pg_pool, data_key, key_id, encrypted_data_key).await
+    }
+}
+
+async fn count_data_keys_to_rewrap(
+    pg_pool: &PgPool,
+    owner: DataKeyOwner,
+    key_id: &str,
+) -> anyhow::Result<u64> {
+    let (table, _, _) = table(owner);
+    let count: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM {table} WHERE master_key_id <> $1"
+    ))
+    .bind(key_id)
+    .fetch_one(pg_pool)
+    .await?;
+    Ok(u64::try_from(count)?)
+}
+
+async fn find_data_keys_to_rewrap(
+    pg_pool: &PgPool,
+    owner: DataKeyOwner,
+    key_id: &str,
+    after: Option<Uuid>,
+    limit: u32,
+) -> anyhow::Result<Vec<WrappedDataKey>> {
+    let (table, id, key) = table(owner);
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT {id} AS id, client_id, master_key_id, {key} AS encrypted_data_key
+        FROM {table}
+        WHERE master_key_id <> $1 AND ($2::UUID IS NULL OR {id} > $2)
+        ORDER BY {id}
+        LIMIT $3
+        "#
+    ))
+    .bind(key_id)
+    .bind(after)
+    .bind(i64::from(limit))
+    .fetch_all(pg_pool)
+    .await?;
+
+    rows.iter()
+        .map(|row: &PgRow| {
+            Ok(WrappedDataKey {
+                owner,
+                id: row.try_get("id")?,
+                client_id: row.try_get("client_id")?,
+                master_key_id: row.try_get("master_key_id")?,
+                encrypted_data_key: row.try_get("encrypted_data_key")?,
+            })
+        })
+        .collect()
+}
+
+/// Replaces the data key, unless it changed since it was read.
+async fn replace_data_key(
+    pg_pool: &PgPool,
+    data_key: &WrappedDataKey,
+    key_id: &str,
+    encrypted_data_key: Encrypted,
+) -> anyhow::Result<bool> {
+    let (table, id, key) = table(data_key.owner);
+    let result = sqlx::query(&format!(
+        r#"
+        UPDATE {table} SET master_key_id = $2, {key} = $3
+        WHERE {id} = $1 AND master_key_id = $4 AND {key} = $5
+        "#
+    ))
+    .bind(data_key.id)
+    .bind(key_id)
+    .bind(encrypted_data_key)
+    .bind(&data_key.master_key_id)
+    .bind(&data_key.encrypted_data_key)
+    .execute(pg_pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,42 @@
+use crate::PostgresPool;
+use repositories::network::NetworkRepository;
+use types::{client::ClientId, network::IpNetwork};
+use uuid::Uuid;
+
+impl NetworkRepository for PostgresPool {
+    async fn find_allowed_networks(&self, client_id: ClientId) -> anyhow::Result<Vec<IpNetwork>> {
+        let res: Option<Vec<IpNetwork>> =
+            sqlx::query_scalar("SELECT allowed_networks FROM client_networks WHERE client_id = $1")
+                .bind::<Uuid>(client_id.into())
+                .fetch_optional(&self.pg_pool)
+                .await?;
+
+        Ok(res.unwrap_or_default())
+    }
+
+    async fn set_allowed_networks(
+        &self,
+        client_id: ClientId,
+        networks: &[IpNetwork],
+    ) -> anyhow::Result<()> {
+        let client_id: Uuid = client_id.into();
+        if networks.is_empty() {
+            sqlx::query("DELETE FROM client_networks WHERE client_id = $1")
+                .bind(client_id)
+                .execute(&self.pg_pool)
+                .await?;
+            return Ok(());
+        }
+        sqlx::query(
+            r#"
+        INSERT INTO client_networks (client_id, allowed_networks) VALUES ($1, $2)
+        ON CONFLICT (client_id) DO UPDATE SET allowed_networks = $2, updated_at = now()
+        "#,
+        )
+        .bind(client_id)
+        .bind(networks)
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use crate::PostgresPool;
+use repositories::nonce::NonceRepository;
+use types::{api_key::ApiKey, secret::mask::Masked};
+use uuid::Uuid;
+
+impl NonceRepository for PostgresPool {
+    async fn use_nonce(
+        &self,
+        api_key: &Masked<ApiKey>,
+        nonce: &str,
+        expires_at: u64,
+    ) -> anyhow::Result<bool> {
+        let api_key: Uuid = api_key.expose().clone().into();
+
+        // Whole seconds, as timestamps are checked: a nonce is kept through its last second
+        sqlx::query(
+            r#"
+        DELETE FROM nonces
+        WHERE api_key = $1 AND expires_at < to_timestamp(floor(extract(epoch FROM now())))
+        "#,
+        )
+        .bind(api_key)
+        .execute(&self.pg_pool)
+        .await?;
+
+        let result = sqlx::query(
+            r#"
+        INSERT INTO nonces (api_key, nonce, expires_at) VALUES ($1, $2, to_timestamp($3))
+        ON CONFLICT (api_key, nonce) DO NOTHING
+        "#,
+        )
+        .bind(api_key)
+        .bind(nonce)
+        .bind(expires_at as f64)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
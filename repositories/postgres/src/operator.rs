@@ -0,0 +1,43 @@
+use crate::PostgresPool;
+use repositories::operator::OperatorRepository;
+use types::operator::Operator;
+
+impl OperatorRepository for PostgresPool {
+    async fn create_operator(&self, operator: Operator) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+        INSERT INTO operators (id, username, password_hash, role) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (username) DO NOTHING
+        "#,
+        )
+        .bind(operator.id)
+        .bind(&operator.username)
+        .bind(operator.password_hash())
+        .bind(operator.role)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_operator(&self, username: &str) -> anyhow::Result<Option<Operator>> {
+        let res = sqlx::query_as(
+            "SELECT id, username, password_hash, role FROM operators WHERE username = $1",
+        )
+        .bind(username)
+        .fetch_optional(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+
+    async fn list_operators(&self) -> anyhow::Result<Vec<Operator>> {
+        let res = sqlx::query_as(
+            "SELECT id, username, password_hash, role FROM operators ORDER BY username",
+        )
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        Ok(res)
+    }
+}
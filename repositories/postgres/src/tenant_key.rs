@@ -0,0 +1,78 @@
+use crate::{KeyStore, PostgresPool};
+use repositories::tenant_key::TenantKeyRepository;
+use types::{client::ClientId, tenant::encrypt::EncryptedTenantKey};
+use uuid::Uuid;
+
+impl TenantKeyRepository for KeyStore {
+    async fn find_tenant_key(
+        &self,
+        client_id: ClientId,
+    ) -> anyhow::Result<Option<EncryptedTenantKey>> {
+        let res = sqlx::query_as("SELECT * FROM tenant_keys WHERE client_id = $1")
+            .bind::<Uuid>(client_id.into())
+            .fetch_optional(&self.pg_pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    async fn create_tenant_key(&self, tenant_key: EncryptedTenantKey) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+        INSERT INTO tenant_keys (client_id, encrypted_key, master_key_id) VALUES ($1, $2, $3)
+        ON CONFLICT (client_id) DO NOTHING
+        "#,
+        )
+        .bind::<Uuid>(tenant_key.client_id.into())
+        .bind(tenant_key.encrypted_key)
+        .bind(tenant_key.master_key_id)
+        .execute(&self.pg_pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_tenant_key(&self, client_id: ClientId) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM tenant_keys WHERE client_id = $1")
+            .bind::<Uuid>(client_id.into())
+            .execute(&self.pg_pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+impl PostgresPool {
+    /// Moves the tenant keys stored in the main database before the key store existed.
+    ///
+    /// Returns the number of tenant keys moved.
+    pub async fn move_tenant_keys(&self, key_store: &KeyStore) -> anyhow::Result<u64> {
+        let tenant_keys: Vec<EncryptedTenantKey> = sqlx::query_as("SELECT * FROM tenant_keys")
+            .fetch_all(&self.pg_pool)
+            .await?;
+
+        let mut moved = 0;
+        for tenant_key in tenant_keys {
+            let client_id = tenant_key.client_id.clone();
+            match key_store.find_tenant_key(client_id.clone()).await? {
+                // Copied before the row of the main database could be deleted
+                Some(stored) => anyhow::ensure!(
+                    stored == tenant_key,
+                    "tenant key of client {:?} differs in the key store",
+                    client_id
+                ),
+                None => anyhow::ensure!(
+                    key_store.create_tenant_key(tenant_key).await?,
+                    "tenant key of client {:?} created concurrently",
+                    client_id
+                ),
+            }
+            sqlx::query("DELETE FROM tenant_keys WHERE client_id = $1")
+                .bind::<Uuid>(client_id.into())
+                .execute(&self.pg_pool)
+                .await?;
+            moved += 1;
+        }
+        Ok(moved)
+    }
+}
@@ -1,8 +1,10 @@
 use crate::PostgresPool;
 use repositories::wallet::WalletRepository;
+use sqlx::{postgres::PgRow, FromRow, Row};
 use types::{
     api_key::ApiKey,
-    client::encrypt::EncryptedCredentials,
+    client::{encrypt::StoredCredentials, ClientId},
+    hd::Account,
     secret::mask::Masked,
     user::{encrypt::EncryptedUser, UserId},
 };
@@ -12,63 +14,197 @@ impl WalletRepository for PostgresPool {
     async fn get_credentials(
         &self,
         api_key: &Masked<ApiKey>,
-    ) -> anyhow::Result<Option<EncryptedCredentials>> {
-        let res = sqlx::query_as("SELECT * FROM credentials WHERE api_key = $1")
-            .bind::<Uuid>(api_key.expose().clone().into())
-            .fetch_optional(&self.pg_pool)
-            .await?;
+    ) -> anyhow::Result<Option<StoredCredentials>> {
+        let res = sqlx::query_as(
+            r#"
+        SELECT * FROM credentials
+        WHERE api_key = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())
+        "#,
+        )
+        .bind::<Uuid>(api_key.expose().clone().into())
+        .fetch_optional(&self.pg_pool)
+        .await?;
 
         Ok(res)
     }
 
     async fn register_user(
         &self,
-        api_key: Masked<ApiKey>,
+        client_id: ClientId,
         encrypted_user: EncryptedUser,
+        default_account: Option<Account>,
     ) -> anyhow::Result<()> {
+        let user_id = encrypted_user.id.clone();
+        let rsa_scheme = encrypted_user.encrypted_signing_key.rsa_scheme;
+        let mut tx = self.pg_pool.begin().await?;
         let result = sqlx::query(
             r#"
-        INSERT INTO users (id, client_id, encrypted_private_key, encrypted_data_key) 
-        SELECT $1, credentials.client_id, $3, $4
-        FROM credentials
-        WHERE api_key = $2"#,
+        INSERT INTO users (id, client_id, key_type, public_key, rsa_padding, rsa_digest, encrypted_private_key, encrypted_data_key, master_key_id) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
         )
         .bind(encrypted_user.id)
-        .bind::<Uuid>(api_key.expose().clone().into())
+        .bind(client_id)
+        .bind(encrypted_user.encrypted_signing_key.key_type)
+        .bind(encrypted_user.public_key)
+        .bind(rsa_scheme.map(|scheme| scheme.padding.to_string()))
+        .bind(rsa_scheme.map(|scheme| scheme.digest.to_string()))
         .bind(encrypted_user.encrypted_signing_key.encrypted_private_key)
         .bind(encrypted_user.encrypted_signing_key.encrypted_data_key)
-        .execute(&self.pg_pool)
+        .bind(encrypted_user.encrypted_signing_key.master_key_id)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             anyhow::bail!("User was not created");
         }
+
+        if let Some(account) = default_account {
+            sqlx::query(
+                "INSERT INTO accounts (user_id, account_index, path, address) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(user_id)
+            .bind(i32::try_from(account.index)?)
+            .bind(account.path)
+            .bind(account.address)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 
-    async fn get_user(&self, user_id: UserId) -> anyhow::Result<Option<EncryptedUser>> {
+    async fn get_user(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<EncryptedUser>> {
         let res = sqlx::query_as(
             r#"
             SELECT 
                 users.id, 
+                users.key_type,
+                users.public_key,
+                users.rsa_padding,
+                users.rsa_digest,
                 users.encrypted_private_key,
-                users.encrypted_data_key
+                users.encrypted_data_key,
+                users.master_key_id
             FROM users 
-            INNER JOIN credentials USING (client_id)
-            WHERE users.id = $1
+            WHERE users.id = $1 AND users.client_id = $2
             "#,
         )
         .bind(user_id)
+        .bind(client_id)
         .fetch_optional(&self.pg_pool)
         .await?;
         Ok(res)
     }
 
-    async fn delete_user(&self, user_id: UserId) -> anyhow::Result<()> {
-        sqlx::query("DELETE FROM users WHERE id = $1")
+    async fn find_users_without_public_key(
+        &self,
+        after: Option<UserId>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<(ClientId, EncryptedUser)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                users.id,
+                users.client_id,
+                users.key_type,
+                users.public_key,
+                users.rsa_padding,
+                users.rsa_digest,
+                users.encrypted_private_key,
+                users.encrypted_data_key,
+                users.master_key_id
+            FROM users
+            WHERE users.public_key IS NULL AND ($1::UUID IS NULL OR users.id > $1)
+            ORDER BY users.id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(i64::from(limit))
+        .fetch_all(&self.pg_pool)
+        .await?;
+
+        rows.iter()
+            .map(|row: &PgRow| Ok((row.try_get("client_id")?, EncryptedUser::from_row(row)?)))
+            .collect()
+    }
+
+    async fn set_public_key(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+        public_key: String,
+    ) -> anyhow::Result<bool> {
+        let result =
+            sqlx::query("UPDATE users SET public_key = $3 WHERE id = $1 AND client_id = $2")
+                .bind(user_id)
+                .bind(client_id)
+                .bind(public_key)
+                .execute(&self.pg_pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_user(&self, client_id: ClientId, user_id: UserId) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM users WHERE id = $1 AND client_id = $2")
             .bind(user_id)
+            .bind(client_id)
             .execute(&self.pg_pool)
             .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_account(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+        account: Account,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO accounts (user_id, account_index, path, address)
+            SELECT users.id, $3, $4, $5
+            FROM users
+            WHERE users.id = $1 AND users.client_id = $2
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .bind(i32::try_from(account.index)?)
+        .bind(account.path)
+        .bind(account.address)
+        .execute(&self.pg_pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_accounts(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+    ) -> anyhow::Result<Vec<Account>> {
+        let res = sqlx::query_as(
+            r#"
+            SELECT
+                accounts.account_index,
+                accounts.path,
+                accounts.address
+            FROM accounts
+            JOIN users ON users.id = accounts.user_id
+            WHERE users.id = $1 AND users.client_id = $2
+            ORDER BY accounts.account_index
+            "#,
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_all(&self.pg_pool)
+        .await?;
+        Ok(res)
     }
 }
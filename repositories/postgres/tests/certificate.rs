@@ -0,0 +1,83 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+use postgres_database::PostgresPool;
+use repositories::{certificate::CertificateRepository, client::ClientRepository};
+use sqlx::PgPool;
+use std::str::FromStr;
+use types::{
+    certificate::CertificateFingerprint,
+    client::Client,
+    encrypt::{master_key::MasterKey, Aes256Key},
+};
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_client_certificates(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let fingerprint = CertificateFingerprint::from_str(&"ab".repeat(32)).expect("fingerprint");
+
+    let mut clients = Vec::new();
+    for name in ["client", "other"] {
+        let client = Client::new(name.to_string());
+        let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+        ClientRepository::create(&db, encrypted_client)
+            .await
+            .expect("create client");
+        clients.push(client.id().clone());
+    }
+    let (client_id, other_id) = (clients[0].clone(), clients[1].clone());
+
+    let created = CertificateRepository::create_certificate(&db, client_id.clone(), &fingerprint)
+        .await
+        .expect("create certificate")
+        .expect("created");
+    assert_eq!(created.fingerprint, fingerprint);
+
+    // A certificate identifies a single client
+    for client_id in [&client_id, &other_id] {
+        assert!(
+            CertificateRepository::create_certificate(&db, client_id.clone(), &fingerprint)
+                .await
+                .expect("create certificate")
+                .is_none()
+        );
+    }
+    let listed = CertificateRepository::list_certificates(&db, client_id.clone())
+        .await
+        .expect("list certificates");
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].fingerprint, fingerprint);
+    assert!(
+        CertificateRepository::list_certificates(&db, other_id.clone())
+            .await
+            .expect("list certificates")
+            .is_empty()
+    );
+
+    assert!(
+        !CertificateRepository::delete_certificate(&db, other_id, &fingerprint)
+            .await
+            .expect("delete certificate")
+    );
+    assert!(
+        CertificateRepository::delete_certificate(&db, client_id.clone(), &fingerprint)
+            .await
+            .expect("delete certificate")
+    );
+
+    // Certificates go with their client
+    CertificateRepository::create_certificate(&db, client_id.clone(), &fingerprint)
+        .await
+        .expect("create certificate");
+    assert!(ClientRepository::delete(&db, client_id.clone())
+        .await
+        .expect("delete client"));
+    assert!(CertificateRepository::list_certificates(&db, client_id)
+        .await
+        .expect("list certificates")
+        .is_empty());
+}
@@ -0,0 +1,399 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+use postgres_database::{KeyStore, PostgresPool};
+use repositories::{
+    client::{ClientRepository, CreateClientResult},
+    dashboard::DashboardRepository,
+    tenant_key::TenantKeyRepository,
+    wallet::WalletRepository,
+};
+use sqlx::PgPool;
+use types::{
+    client::Client,
+    dashboard::DashboardAccount,
+    encrypt::{master_key::MasterKey, Aes256Key},
+    tenant::{TenantKey, TenantKeyRing, TENANT_KEY_ID},
+    user::{KeyType, User},
+};
+
+/// Key store in its own schema of the test database.
+async fn key_store(pg_pool: &PgPool) -> KeyStore {
+    sqlx::query("CREATE SCHEMA key_store")
+        .execute(pg_pool)
+        .await
+        .expect("create schema");
+    let options = (*pg_pool.connect_options())
+        .clone()
+        .options([("search_path", "key_store")]);
+    let key_store = KeyStore {
+        pg_pool: PgPool::connect_with(options)
+            .await
+            .expect("connect key store"),
+    };
+    sqlx::raw_sql(include_str!(
+        "../../../migrations/key_store/20251120120000_tenant_keys.sql"
+    ))
+    .execute(&key_store.pg_pool)
+    .await
+    .expect("migrate key store");
+    key_store
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_delete_client_shreds_tenant_key(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let client = Client::new("client".to_string());
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    assert_eq!(encrypted_client.credentials.master_key_id, TENANT_KEY_ID);
+    let created = ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        None,
+        &key_store,
+    )
+    .await
+    .expect("create client");
+    assert_eq!(created, CreateClientResult::Created);
+
+    let tenant_key = TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+        .await
+        .expect("find tenant key")
+        .expect("tenant key")
+        .decrypt(&master_key)
+        .await
+        .expect("decrypt tenant key");
+    let key_ring = TenantKeyRing::new(&master_key, Some(&tenant_key));
+    ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .expect("client")
+        .decrypt(&key_ring)
+        .await
+        .expect("decrypt client");
+
+    let user = User::new(KeyType::Secp256k1).expect("new user");
+    let user_id = user.id().clone();
+    let encrypted_user = user.encrypt(&key_ring).await.expect("encrypt user");
+    WalletRepository::register_user(&db, client.id().clone(), encrypted_user, None)
+        .await
+        .expect("register user");
+    let encrypted_user = WalletRepository::get_user(&db, client.id().clone(), user_id.clone())
+        .await
+        .expect("get user")
+        .expect("user");
+    assert_eq!(
+        encrypted_user.encrypted_signing_key.master_key_id,
+        TENANT_KEY_ID
+    );
+
+    assert!(
+        TenantKeyRepository::delete_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("delete tenant key")
+    );
+    assert!(ClientRepository::delete(&db, client.id().clone())
+        .await
+        .expect("delete client"));
+    assert!(!ClientRepository::delete(&db, client.id().clone())
+        .await
+        .expect("delete client"));
+    assert!(ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .is_none());
+    assert!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key")
+            .is_none()
+    );
+    assert!(
+        WalletRepository::get_user(&db, client.id().clone(), user_id)
+            .await
+            .expect("get user")
+            .is_none()
+    );
+
+    // A copy of the user kept elsewhere cannot be decrypted without the tenant key.
+    assert!(encrypted_user
+        .decrypt(&TenantKeyRing::new(&master_key, None))
+        .await
+        .is_err());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_create_tenant_key_of_existing_client(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    // Created before tenant keys
+    let client = Client::new("client".to_string());
+    let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+    assert!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key")
+            .is_none()
+    );
+
+    let tenant_key = TenantKey::generate(client.id().clone());
+    let encrypted_tenant_key = tenant_key.encrypt(&master_key).await.expect("encrypt");
+    assert!(
+        TenantKeyRepository::create_tenant_key(&key_store, encrypted_tenant_key)
+            .await
+            .expect("create tenant key")
+    );
+    let other_key = TenantKey::generate(client.id().clone())
+        .encrypt(&master_key)
+        .await
+        .expect("encrypt");
+    assert!(
+        !TenantKeyRepository::create_tenant_key(&key_store, other_key)
+            .await
+            .expect("create tenant key")
+    );
+
+    let stored = ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .expect("client");
+    // Credentials wrapped by the master key still decrypt next to the tenant key
+    stored
+        .decrypt(&TenantKeyRing::new(&master_key, Some(&tenant_key)))
+        .await
+        .expect("decrypt client");
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_create_client_keeps_tenant_key_of_existing_client(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let client = Client::new("client".to_string());
+
+    // Left over by a creation that failed after storing the tenant key
+    let left_over = TenantKey::generate(client.id().clone())
+        .encrypt(&master_key)
+        .await
+        .expect("encrypt");
+    assert!(
+        TenantKeyRepository::create_tenant_key(&key_store, left_over)
+            .await
+            .expect("create tenant key")
+    );
+
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    let created = ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        None,
+        &key_store,
+    )
+    .await
+    .expect("create client");
+    assert_eq!(created, CreateClientResult::Created);
+    let tenant_key = TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+        .await
+        .expect("find tenant key")
+        .expect("tenant key");
+
+    // Creating the client again fails without replacing its tenant key
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    let created = ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        None,
+        &key_store,
+    )
+    .await
+    .expect("create client");
+    assert_eq!(created, CreateClientResult::NameTaken);
+    assert_eq!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key"),
+        Some(tenant_key)
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_move_tenant_keys_to_key_store(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    // Stored in the main database before the key store
+    let client = Client::new("client".to_string());
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+    sqlx::query(
+        "INSERT INTO tenant_keys (client_id, encrypted_key, master_key_id) VALUES ($1, $2, $3)",
+    )
+    .bind(client.id().clone())
+    .bind(encrypted_tenant_key.encrypted_key.clone())
+    .bind(encrypted_tenant_key.master_key_id.clone())
+    .execute(&db.pg_pool)
+    .await
+    .expect("store tenant key");
+
+    assert_eq!(db.move_tenant_keys(&key_store).await.expect("move"), 1);
+    assert_eq!(db.move_tenant_keys(&key_store).await.expect("move"), 0);
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tenant_keys")
+        .fetch_one(&db.pg_pool)
+        .await
+        .expect("count");
+    assert_eq!(count, 0);
+    assert_eq!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key"),
+        Some(encrypted_tenant_key)
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_create_client_with_taken_email(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let owner = Client::new("owner".to_string());
+    let (encrypted_client, encrypted_tenant_key) = owner
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    let account = DashboardAccount::new(
+        owner.id().clone(),
+        "owner@example.com",
+        "long enough password",
+    )
+    .expect("new account");
+    let created = ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        Some(account),
+        &key_store,
+    )
+    .await
+    .expect("create client");
+    assert_eq!(created, CreateClientResult::Created);
+    assert!(DashboardRepository::find_account(&db, "owner@example.com")
+        .await
+        .expect("find account")
+        .is_some());
+
+    // Nothing of the client is stored when the email address is taken
+    let client = Client::new("client".to_string());
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    let account = DashboardAccount::new(
+        client.id().clone(),
+        "owner@example.com",
+        "long enough password",
+    )
+    .expect("new account");
+    let created = ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        Some(account),
+        &key_store,
+    )
+    .await
+    .expect("create client");
+    assert_eq!(created, CreateClientResult::EmailTaken);
+    assert!(ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .is_none());
+    assert!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key")
+            .is_none()
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_failed_client_creation_discards_tenant_key(pg_pool: PgPool) {
+    let key_store = key_store(&pg_pool).await;
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    // Fails the commit, once the tenant key is stored
+    sqlx::raw_sql(
+        r#"
+        CREATE FUNCTION reject_client() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'client rejected';
+        END
+        $$ LANGUAGE plpgsql;
+        CREATE CONSTRAINT TRIGGER reject_client AFTER INSERT ON clients
+        DEFERRABLE INITIALLY DEFERRED FOR EACH ROW EXECUTE FUNCTION reject_client();
+        "#,
+    )
+    .execute(&db.pg_pool)
+    .await
+    .expect("create trigger");
+
+    let client = Client::new("client".to_string());
+    let (encrypted_client, encrypted_tenant_key) = client
+        .encrypt_with_tenant_key(&master_key)
+        .await
+        .expect("encrypt client");
+    assert!(ClientRepository::create_with_tenant_key(
+        &db,
+        encrypted_client,
+        encrypted_tenant_key,
+        None,
+        &key_store,
+    )
+    .await
+    .is_err());
+    assert!(ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .is_none());
+    assert!(
+        TenantKeyRepository::find_tenant_key(&key_store, client.id().clone())
+            .await
+            .expect("find tenant key")
+            .is_none()
+    );
+}
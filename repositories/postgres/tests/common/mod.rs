@@ -0,0 +1,24 @@
+//! Helpers shared by the integration tests, each test crate using only some of them.
+#![allow(dead_code)]
+
+use postgres_database::PostgresPool;
+use repositories::client::ClientRepository;
+use std::time::{SystemTime, UNIX_EPOCH};
+use types::{client::Client, encrypt::master_key::MasterKey};
+
+/// Current Unix time in seconds.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time")
+        .as_secs() as i64
+}
+
+pub async fn create_client(db: &PostgresPool, master_key: &MasterKey, name: &str) -> Client {
+    let client = Client::new(name.to_string());
+    let encrypted_client = client.encrypt(master_key).await.expect("encrypt client");
+    ClientRepository::create(db, encrypted_client)
+        .await
+        .expect("create client");
+    client
+}
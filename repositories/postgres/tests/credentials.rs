@@ -0,0 +1,271 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+mod common;
+
+use common::now;
+use postgres_database::PostgresPool;
+use repositories::{
+    client::ClientRepository, credentials::CredentialsRepository, wallet::WalletRepository,
+};
+use sqlx::PgPool;
+use types::{
+    client::{encrypt::StoredCredentials, Client, Credentials, CredentialsState},
+    client_key::{CredentialType, PublicKeyCredentials},
+    encrypt::{master_key::MasterKey, Aes256Key},
+    scope::Scope,
+};
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_credentials_lifecycle(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let client = Client::new("client".to_string());
+    let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+
+    let issued = Credentials::generate_with_scopes(client.id().clone(), [Scope::UsersSign]);
+    let encrypted_credentials = issued.encrypt(&master_key).await.expect("encrypt");
+    CredentialsRepository::create_credentials(&db, encrypted_credentials, Some(now() + 3600))
+        .await
+        .expect("create credentials");
+
+    // Both the original and the issued credentials authenticate
+    for api_key in [&client.credentials.api_key, &issued.api_key] {
+        assert!(WalletRepository::get_credentials(&db, api_key)
+            .await
+            .expect("get credentials")
+            .is_some());
+    }
+    let listed = CredentialsRepository::list_credentials(&db, client.id().clone())
+        .await
+        .expect("list credentials");
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].scopes, Scope::ALL.to_vec());
+    assert_eq!(listed[1].scopes, vec![Scope::UsersSign]);
+    assert!(listed
+        .iter()
+        .all(|info| info.state(now()) == CredentialsState::Active));
+
+    // The expiry of active credentials is moved
+    let info = CredentialsRepository::set_credentials_expiry(
+        &db,
+        client.id().clone(),
+        &issued.api_key,
+        Some(now() + 7200),
+    )
+    .await
+    .expect("set expiry")
+    .expect("credentials");
+    assert_eq!(info.expires_at, Some(now() + 7200));
+    CredentialsRepository::set_credentials_expiry(&db, client.id().clone(), &issued.api_key, None)
+        .await
+        .expect("set expiry")
+        .expect("credentials");
+
+    // Revoking the original credentials leaves the issued ones working
+    assert!(CredentialsRepository::revoke_credentials(
+        &db,
+        client.id().clone(),
+        &client.credentials.api_key
+    )
+    .await
+    .expect("revoke credentials"));
+    assert!(!CredentialsRepository::revoke_credentials(
+        &db,
+        client.id().clone(),
+        &client.credentials.api_key
+    )
+    .await
+    .expect("revoke credentials"));
+    assert!(
+        WalletRepository::get_credentials(&db, &client.credentials.api_key)
+            .await
+            .expect("get credentials")
+            .is_none()
+    );
+    assert!(CredentialsRepository::set_credentials_expiry(
+        &db,
+        client.id().clone(),
+        &client.credentials.api_key,
+        None
+    )
+    .await
+    .expect("set expiry")
+    .is_none());
+
+    // The client is found with its active credentials
+    let found = ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .expect("client");
+    assert_eq!(found.credentials.api_key, issued.api_key);
+    assert_eq!(found.credentials.scopes, vec![Scope::UsersSign]);
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_expired_credentials_stay_expired(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let client = Client::new("client".to_string());
+    let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+
+    // Expired credentials are rejected
+    let info = CredentialsRepository::set_credentials_expiry(
+        &db,
+        client.id().clone(),
+        &client.credentials.api_key,
+        Some(now() - 1),
+    )
+    .await
+    .expect("set expiry")
+    .expect("credentials");
+    assert_eq!(info.state(now()), CredentialsState::Expired);
+    assert!(
+        WalletRepository::get_credentials(&db, &client.credentials.api_key)
+            .await
+            .expect("get credentials")
+            .is_none()
+    );
+
+    // and cannot be brought back
+    for expires_at in [Some(now() + 3600), None] {
+        assert!(CredentialsRepository::set_credentials_expiry(
+            &db,
+            client.id().clone(),
+            &client.credentials.api_key,
+            expires_at
+        )
+        .await
+        .expect("set expiry")
+        .is_none());
+    }
+    assert!(
+        WalletRepository::get_credentials(&db, &client.credentials.api_key)
+            .await
+            .expect("get credentials")
+            .is_none()
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_credentials_are_scoped_to_client(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let owner = Client::new("owner".to_string());
+    let intruder = Client::new("intruder".to_string());
+    for client in [&owner, &intruder] {
+        let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+        ClientRepository::create(&db, encrypted_client)
+            .await
+            .expect("create client");
+    }
+
+    assert!(!CredentialsRepository::revoke_credentials(
+        &db,
+        intruder.id().clone(),
+        &owner.credentials.api_key
+    )
+    .await
+    .expect("revoke credentials"));
+    assert!(CredentialsRepository::set_credentials_expiry(
+        &db,
+        intruder.id().clone(),
+        &owner.credentials.api_key,
+        Some(now())
+    )
+    .await
+    .expect("set expiry")
+    .is_none());
+    assert!(
+        WalletRepository::get_credentials(&db, &owner.credentials.api_key)
+            .await
+            .expect("get credentials")
+            .is_some()
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_public_key_credentials(pg_pool: PgPool) {
+    use ed25519_dalek::{
+        pkcs8::{spki::der::pem::LineEnding, EncodePublicKey},
+        SigningKey,
+    };
+
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+
+    let client = Client::new("client".to_string());
+    let encrypted_client = client.encrypt(&master_key).await.expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+
+    let public_key = SigningKey::from_bytes(&[7u8; 32])
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .expect("pem");
+    let issued = PublicKeyCredentials::new(client.id().clone(), &public_key, [Scope::UsersRead])
+        .expect("credentials");
+    CredentialsRepository::create_public_key_credentials(&db, &issued, None)
+        .await
+        .expect("create credentials");
+
+    match WalletRepository::get_credentials(&db, &issued.api_key)
+        .await
+        .expect("get credentials")
+    {
+        Some(StoredCredentials::PublicKey(credentials)) => {
+            assert_eq!(credentials.client_id, *client.id());
+            assert_eq!(credentials.credential_type, CredentialType::Ed25519);
+            assert_eq!(credentials.public_key, public_key);
+            assert_eq!(credentials.scopes, vec![Scope::UsersRead]);
+        }
+        other => panic!("unexpected credentials: {other:?}"),
+    }
+    assert!(matches!(
+        WalletRepository::get_credentials(&db, &client.credentials.api_key)
+            .await
+            .expect("get credentials"),
+        Some(StoredCredentials::Hmac(_))
+    ));
+
+    let listed = CredentialsRepository::list_credentials(&db, client.id().clone())
+        .await
+        .expect("list credentials");
+    assert_eq!(
+        listed
+            .iter()
+            .map(|info| info.credential_type)
+            .collect::<Vec<_>>(),
+        vec![CredentialType::Hmac, CredentialType::Ed25519]
+    );
+
+    // The client keeps being found with its HMAC credentials
+    assert!(CredentialsRepository::revoke_credentials(
+        &db,
+        client.id().clone(),
+        &client.credentials.api_key
+    )
+    .await
+    .expect("revoke credentials"));
+    let found = ClientRepository::find(&db, client.id().clone())
+        .await
+        .expect("find client")
+        .expect("client");
+    assert_eq!(found.credentials.api_key, client.credentials.api_key);
+}
@@ -0,0 +1,118 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+mod common;
+
+use common::{create_client, now};
+use postgres_database::PostgresPool;
+use repositories::{
+    client::ClientRepository, dashboard::DashboardRepository, wallet::WalletRepository,
+};
+use sqlx::PgPool;
+use types::{
+    dashboard::{DashboardAccount, ResetToken},
+    encrypt::{master_key::MasterKey, Aes256Key},
+    password,
+    user::{KeyType, User},
+};
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_password_reset(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let client = create_client(&db, &master_key, "client").await;
+
+    let account =
+        DashboardAccount::new(client.id().clone(), "alice@example.com", "initial-password")
+            .expect("account");
+    let account_id = account.id;
+    assert!(DashboardRepository::create_account(&db, account)
+        .await
+        .expect("create account"));
+    let taken = DashboardAccount::new(client.id().clone(), "alice@example.com", "other-password")
+        .expect("account");
+    assert!(!DashboardRepository::create_account(&db, taken)
+        .await
+        .expect("create account"));
+
+    let expired = ResetToken::generate();
+    DashboardRepository::create_password_reset(&db, account_id, &expired.hash, now() - 1)
+        .await
+        .expect("create reset");
+    let first = ResetToken::generate();
+    let second = ResetToken::generate();
+    for token in [&first, &second] {
+        DashboardRepository::create_password_reset(&db, account_id, &token.hash, now() + 3600)
+            .await
+            .expect("create reset");
+    }
+
+    let password_hash = password::hash_password("reset-password").expect("hash");
+    assert!(
+        !DashboardRepository::reset_password(&db, &expired.hash, &password_hash)
+            .await
+            .expect("reset")
+    );
+    assert!(
+        DashboardRepository::reset_password(&db, &first.hash, &password_hash)
+            .await
+            .expect("reset")
+    );
+    // Tokens are used up, including the ones issued along
+    for token in [&first, &second] {
+        assert!(
+            !DashboardRepository::reset_password(&db, &token.hash, &password_hash)
+                .await
+                .expect("reset")
+        );
+    }
+
+    let account = DashboardRepository::find_account(&db, "alice@example.com")
+        .await
+        .expect("find account");
+    DashboardAccount::login(account.as_ref(), "reset-password").expect("login");
+    assert!(DashboardAccount::login(account.as_ref(), "initial-password").is_err());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_list_users_and_delete_accounts(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let client = create_client(&db, &master_key, "client").await;
+    let other = create_client(&db, &master_key, "other").await;
+
+    for client in [&client, &client, &other] {
+        let user = User::new(KeyType::Secp256k1).expect("user");
+        let encrypted_user = user.encrypt(&master_key).await.expect("encrypt user");
+        WalletRepository::register_user(&db, client.id().clone(), encrypted_user, None)
+            .await
+            .expect("register user");
+    }
+    let users = ClientRepository::list_users(&db, client.id().clone(), 10, 0)
+        .await
+        .expect("list users");
+    assert_eq!(users.len(), 2);
+    assert!(users.iter().all(|user| user.key_type == KeyType::Secp256k1));
+    let page = ClientRepository::list_users(&db, client.id().clone(), 1, 1)
+        .await
+        .expect("list users");
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, users[1].id);
+
+    let account = DashboardAccount::new(client.id().clone(), "bob@example.com", "bob-password-1")
+        .expect("account");
+    DashboardRepository::create_account(&db, account)
+        .await
+        .expect("create account");
+    assert!(ClientRepository::delete(&db, client.id().clone())
+        .await
+        .expect("delete client"));
+    assert!(DashboardRepository::find_account(&db, "bob@example.com")
+        .await
+        .expect("find account")
+        .is_none());
+}
@@ -0,0 +1,98 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+use postgres_database::PostgresPool;
+use repositories::{
+    client::ClientRepository, master_key::MasterKeyRepository, wallet::WalletRepository,
+};
+use sqlx::PgPool;
+use types::{
+    client::Client,
+    encrypt::{
+        master_key::MasterKey,
+        provider::{KeyEncryptionProvider, KeyRing, DEFAULT_KEY_ID},
+        rotation::DataKeyOwner,
+        Aes256Key,
+    },
+    user::{KeyType, User},
+};
+
+/// Counts the data keys of credentials and users not wrapped by `key_id`.
+async fn count_data_keys_to_rewrap(db: &PostgresPool, key_id: &str) -> u64 {
+    let mut count = 0;
+    for owner in [DataKeyOwner::Credentials, DataKeyOwner::Users] {
+        count += db
+            .count_data_keys_to_rewrap(owner, key_id)
+            .await
+            .expect("count");
+    }
+    count
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_rewrap_data_keys(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let old_key = MasterKey::from(Aes256Key::generate());
+    let new_key = MasterKey::from(Aes256Key::generate());
+
+    let client = Client::new("client".to_string());
+    let encrypted_client = client.encrypt(&old_key).await.expect("encrypt client");
+    ClientRepository::create(&db, encrypted_client)
+        .await
+        .expect("create client");
+    let user = User::new(KeyType::Secp256k1).expect("new user");
+    let user_id = user.id().clone();
+    let encrypted_user = user.encrypt(&old_key).await.expect("encrypt user");
+    WalletRepository::register_user(&db, client.id().clone(), encrypted_user, None)
+        .await
+        .expect("register user");
+
+    assert_eq!(count_data_keys_to_rewrap(&db, "v2").await, 2);
+    assert_eq!(count_data_keys_to_rewrap(&db, DEFAULT_KEY_ID).await, 0);
+
+    let data_keys = db
+        .find_data_keys_to_rewrap(DataKeyOwner::Users, "v2", None, 10)
+        .await
+        .expect("find data keys");
+    assert_eq!(data_keys.len(), 1);
+    let data_key = &data_keys[0];
+    assert_eq!(data_key.master_key_id, DEFAULT_KEY_ID);
+
+    let key = old_key
+        .unwrap_data_key(DEFAULT_KEY_ID, &data_key.encrypted_data_key)
+        .await
+        .expect("unwrap");
+    let rewrapped = new_key.wrap(&key).await.expect("wrap").with_key_id("v2");
+    assert!(db
+        .replace_data_key(data_key, "v2", rewrapped.clone())
+        .await
+        .expect("replace"));
+    // Already replaced, the stale copy no longer matches.
+    assert!(!db
+        .replace_data_key(data_key, "v2", rewrapped)
+        .await
+        .expect("replace"));
+
+    assert_eq!(count_data_keys_to_rewrap(&db, "v2").await, 1);
+    let after = db
+        .find_data_keys_to_rewrap(DataKeyOwner::Users, "v2", None, 10)
+        .await
+        .expect("find data keys");
+    assert!(after.is_empty());
+
+    let encrypted_user = WalletRepository::get_user(&db, client.id().clone(), user_id)
+        .await
+        .expect("get user")
+        .expect("user");
+    assert_eq!(encrypted_user.encrypted_signing_key.master_key_id, "v2");
+    let wrapped_key = &encrypted_user.encrypted_signing_key.encrypted_data_key;
+    assert_eq!(wrapped_key.key_id.as_deref(), Some("v2"));
+    let unwrapped = new_key
+        .unwrap(&wrapped_key.for_key("v2").expect("wrapped by v2"))
+        .await
+        .expect("unwrap rewrapped");
+    assert_eq!(unwrapped.expose_secret(), key.expose_secret());
+}
@@ -0,0 +1,145 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+mod common;
+
+use common::{create_client, now};
+use postgres_database::PostgresPool;
+use repositories::{
+    client::ClientRepository, lockout::LockoutRepository, network::NetworkRepository,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use types::{
+    encrypt::{master_key::MasterKey, Aes256Key},
+    network::IpNetwork,
+};
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_allowed_networks(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let client = create_client(&db, &master_key, "client").await;
+    let client_id = client.id().clone();
+
+    assert!(
+        NetworkRepository::find_allowed_networks(&db, client_id.clone())
+            .await
+            .expect("find networks")
+            .is_empty()
+    );
+
+    let networks = vec![
+        IpNetwork::from_str("10.0.0.0/8").expect("network"),
+        IpNetwork::from_str("2001:db8::/32").expect("network"),
+    ];
+    for networks in [&networks[..1], &networks[..]] {
+        NetworkRepository::set_allowed_networks(&db, client_id.clone(), networks)
+            .await
+            .expect("set networks");
+        assert_eq!(
+            NetworkRepository::find_allowed_networks(&db, client_id.clone())
+                .await
+                .expect("find networks"),
+            networks
+        );
+    }
+
+    NetworkRepository::set_allowed_networks(&db, client_id.clone(), &[])
+        .await
+        .expect("clear networks");
+    assert!(
+        NetworkRepository::find_allowed_networks(&db, client_id.clone())
+            .await
+            .expect("find networks")
+            .is_empty()
+    );
+
+    NetworkRepository::set_allowed_networks(&db, client_id.clone(), &networks)
+        .await
+        .expect("set networks");
+    assert!(ClientRepository::delete(&db, client_id.clone())
+        .await
+        .expect("delete client"));
+    assert!(NetworkRepository::find_allowed_networks(&db, client_id)
+        .await
+        .expect("find networks")
+        .is_empty());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_lockouts(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let master_key = MasterKey::from(Aes256Key::generate());
+    let client = create_client(&db, &master_key, "client").await;
+    let other = create_client(&db, &master_key, "other").await;
+    let api_key = &client.credentials.api_key;
+
+    assert!(LockoutRepository::find_lockout(&db, api_key)
+        .await
+        .expect("find lockout")
+        .is_none());
+    for expected in 1..=3 {
+        assert_eq!(
+            LockoutRepository::record_failure(&db, api_key)
+                .await
+                .expect("record failure"),
+            expected
+        );
+    }
+    LockoutRepository::lock(&db, api_key, now() + 60)
+        .await
+        .expect("lock");
+    let lockout = LockoutRepository::find_lockout(&db, api_key)
+        .await
+        .expect("find lockout")
+        .expect("lockout");
+    assert_eq!(lockout.failures, 3);
+    assert!(lockout
+        .retry_after(now())
+        .is_some_and(|retry_after| retry_after <= 60));
+
+    let listed = LockoutRepository::list_lockouts(&db, client.id().clone())
+        .await
+        .expect("list lockouts");
+    assert_eq!(listed.len(), 1);
+    assert!(LockoutRepository::list_lockouts(&db, other.id().clone())
+        .await
+        .expect("list lockouts")
+        .is_empty());
+
+    // Only the client owning the credentials unlocks them
+    assert!(!LockoutRepository::unlock(&db, other.id().clone(), api_key)
+        .await
+        .expect("unlock"));
+    assert!(LockoutRepository::unlock(&db, client.id().clone(), api_key)
+        .await
+        .expect("unlock"));
+    assert!(LockoutRepository::find_lockout(&db, api_key)
+        .await
+        .expect("find lockout")
+        .is_none());
+
+    LockoutRepository::record_failure(&db, api_key)
+        .await
+        .expect("record failure");
+    LockoutRepository::clear_failures(&db, api_key)
+        .await
+        .expect("clear failures");
+    assert!(LockoutRepository::find_lockout(&db, api_key)
+        .await
+        .expect("find lockout")
+        .is_none());
+
+    // Failures go with the credentials of a deleted client
+    LockoutRepository::record_failure(&db, api_key)
+        .await
+        .expect("record failure");
+    assert!(ClientRepository::delete(&db, client.id().clone())
+        .await
+        .expect("delete client"));
+}
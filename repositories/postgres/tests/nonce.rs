@@ -0,0 +1,49 @@
+mod common;
+
+use postgres_database::PostgresPool;
+use repositories::nonce::NonceRepository;
+use sqlx::PgPool;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use types::{api_key::ApiKey, secret::mask::Masked};
+use uuid::Uuid;
+
+/// Nonces expire at unsigned Unix times.
+fn now() -> u64 {
+    common::now() as u64
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_nonce_is_rejected_until_expired(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+
+    assert!(db.use_nonce(&api_key, "nonce", now() + 60).await.unwrap());
+    assert!(!db.use_nonce(&api_key, "nonce", now() + 60).await.unwrap());
+
+    let other_api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+    assert!(db
+        .use_nonce(&other_api_key, "nonce", now() + 60)
+        .await
+        .unwrap());
+
+    assert!(db.use_nonce(&api_key, "expired", now() - 1).await.unwrap());
+    assert!(db.use_nonce(&api_key, "expired", now() + 60).await.unwrap());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_nonce_is_rejected_through_its_last_second(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let api_key = Masked::from(ApiKey::from(Uuid::new_v4()));
+
+    // Start of a second, so that both uses fall within it
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    std::thread::sleep(Duration::from_nanos(
+        1_000_000_000 - elapsed.subsec_nanos() as u64,
+    ));
+
+    let expires_at = now();
+    assert!(db.use_nonce(&api_key, "nonce", expires_at).await.unwrap());
+    assert!(!db.use_nonce(&api_key, "nonce", expires_at).await.unwrap());
+}
@@ -0,0 +1,53 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+use postgres_database::PostgresPool;
+use repositories::operator::OperatorRepository;
+use sqlx::PgPool;
+use types::operator::{Operator, Role};
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_operators(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+
+    let operator = Operator::new("root".to_string(), "superadmin-password", Role::Superadmin)
+        .expect("operator");
+    let id = operator.id;
+    assert!(OperatorRepository::create_operator(&db, operator)
+        .await
+        .expect("create operator"));
+
+    // Usernames are unique
+    let duplicate =
+        Operator::new("root".to_string(), "another-password", Role::Viewer).expect("operator");
+    assert!(!OperatorRepository::create_operator(&db, duplicate)
+        .await
+        .expect("create operator"));
+
+    let found = OperatorRepository::find_operator(&db, "root")
+        .await
+        .expect("find operator")
+        .expect("operator exists");
+    assert_eq!(found.id, id);
+    assert_eq!(found.role, Role::Superadmin);
+    Operator::login(Some(&found), "superadmin-password").expect("login");
+    assert!(Operator::login(Some(&found), "another-password").is_err());
+    assert!(OperatorRepository::find_operator(&db, "nobody")
+        .await
+        .expect("find operator")
+        .is_none());
+
+    let viewer =
+        Operator::new("viewer".to_string(), "viewer-password", Role::Viewer).expect("operator");
+    OperatorRepository::create_operator(&db, viewer)
+        .await
+        .expect("create operator");
+    let operators = OperatorRepository::list_operators(&db)
+        .await
+        .expect("list operators");
+    let usernames: Vec<_> = operators.iter().map(|o| o.username.as_str()).collect();
+    assert_eq!(usernames, ["root", "viewer"]);
+}
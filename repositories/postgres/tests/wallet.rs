@@ -0,0 +1,246 @@
+//! Integration tests against a live Postgres instance.
+//!
+//! Run with `DATABASE_URL` pointing at a database the test user may create
+//! schemas in: `cargo test -p postgres_database -- --ignored`.
+
+mod common;
+
+use postgres_database::PostgresPool;
+use repositories::wallet::WalletRepository;
+use sqlx::PgPool;
+use types::{
+    client::ClientId,
+    encrypt::master_key::MasterKey,
+    hd::{self, Account},
+    user::{KeyType, User, UserId},
+};
+
+fn master_key() -> MasterKey {
+    MasterKey::from_file("../../.local/master_key.txt".to_string()).expect("master key")
+}
+
+async fn create_client(db: &PostgresPool, name: &str) -> ClientId {
+    common::create_client(db, &master_key(), name)
+        .await
+        .id()
+        .clone()
+}
+
+async fn register_user(db: &PostgresPool, client_id: &ClientId) -> UserId {
+    let user = User::new(KeyType::default()).expect("new user");
+    let user_id = user.id().clone();
+    let encrypted_user = user.encrypt(&master_key()).await.expect("encrypt user");
+    WalletRepository::register_user(db, client_id.clone(), encrypted_user, None)
+        .await
+        .expect("register user");
+    user_id
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_get_user_is_scoped_to_client(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let intruder = create_client(&db, "intruder").await;
+    let user_id = register_user(&db, &owner).await;
+
+    let own = WalletRepository::get_user(&db, owner, user_id.clone())
+        .await
+        .expect("get user");
+    assert!(own.is_some());
+
+    let foreign = WalletRepository::get_user(&db, intruder, user_id)
+        .await
+        .expect("get user");
+    assert!(foreign.is_none());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_delete_user_is_scoped_to_client(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let intruder = create_client(&db, "intruder").await;
+    let user_id = register_user(&db, &owner).await;
+
+    let deleted = WalletRepository::delete_user(&db, intruder, user_id.clone())
+        .await
+        .expect("delete user");
+    assert!(!deleted);
+    assert!(
+        WalletRepository::get_user(&db, owner.clone(), user_id.clone())
+            .await
+            .expect("get user")
+            .is_some()
+    );
+
+    let deleted = WalletRepository::delete_user(&db, owner.clone(), user_id.clone())
+        .await
+        .expect("delete user");
+    assert!(deleted);
+    assert!(WalletRepository::get_user(&db, owner, user_id)
+        .await
+        .expect("get user")
+        .is_none());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_accounts_are_scoped_to_client(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let intruder = create_client(&db, "intruder").await;
+    let user_id = register_user(&db, &owner).await;
+    let account = Account {
+        index: 1,
+        path: hd::account_path(1).expect("valid index"),
+        address: "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".to_string(),
+    };
+
+    let added =
+        WalletRepository::add_account(&db, intruder.clone(), user_id.clone(), account.clone())
+            .await
+            .expect("add account");
+    assert!(!added);
+
+    let added = WalletRepository::add_account(&db, owner.clone(), user_id.clone(), account.clone())
+        .await
+        .expect("add account");
+    assert!(added);
+
+    let added = WalletRepository::add_account(&db, owner.clone(), user_id.clone(), account.clone())
+        .await
+        .expect("add account");
+    assert!(!added);
+
+    assert_eq!(
+        WalletRepository::list_accounts(&db, owner, user_id.clone())
+            .await
+            .expect("list accounts"),
+        vec![account]
+    );
+    assert!(WalletRepository::list_accounts(&db, intruder, user_id)
+        .await
+        .expect("list accounts")
+        .is_empty());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_register_user_with_default_account(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let account = Account {
+        index: 0,
+        path: hd::DEFAULT_DERIVATION_PATH.to_string(),
+        address: "0x9858EfFD232B4033E47d90003D41EC34EcaEda94".to_string(),
+    };
+
+    let user = User::new(KeyType::Hd).expect("new user");
+    let user_id = user.id().clone();
+    let encrypted_user = user.encrypt(&master_key()).await.expect("encrypt user");
+    WalletRepository::register_user(&db, owner.clone(), encrypted_user, Some(account.clone()))
+        .await
+        .expect("register user");
+    assert_eq!(
+        WalletRepository::list_accounts(&db, owner.clone(), user_id)
+            .await
+            .expect("list accounts"),
+        vec![account.clone()]
+    );
+
+    // The user is not stored when its default account cannot be
+    let user = User::new(KeyType::Hd).expect("new user");
+    let user_id = user.id().clone();
+    let encrypted_user = user.encrypt(&master_key()).await.expect("encrypt user");
+    let invalid = Account {
+        index: u32::MAX,
+        ..account
+    };
+    assert!(
+        WalletRepository::register_user(&db, owner.clone(), encrypted_user, Some(invalid))
+            .await
+            .is_err()
+    );
+    assert!(WalletRepository::get_user(&db, owner, user_id)
+        .await
+        .expect("get user")
+        .is_none());
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_find_users_without_public_key(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let legacy = register_user(&db, &owner).await;
+    register_user(&db, &owner).await;
+
+    // Users registered before public keys were stored
+    sqlx::query("UPDATE users SET public_key = NULL WHERE id = $1")
+        .bind(legacy.clone())
+        .execute(&db.pg_pool)
+        .await
+        .expect("clear public key");
+
+    let users = WalletRepository::find_users_without_public_key(&db, None, 10)
+        .await
+        .expect("find users");
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].0, owner);
+    assert_eq!(users[0].1.id, legacy);
+    assert!(
+        WalletRepository::find_users_without_public_key(&db, Some(legacy.clone()), 10)
+            .await
+            .expect("find users")
+            .is_empty()
+    );
+
+    let stored = WalletRepository::set_public_key(&db, owner, legacy, "public key".to_string())
+        .await
+        .expect("set public key");
+    assert!(stored);
+    assert!(
+        WalletRepository::find_users_without_public_key(&db, None, 10)
+            .await
+            .expect("find users")
+            .is_empty()
+    );
+}
+
+#[ignore = "requires DATABASE_URL"]
+#[sqlx::test(migrations = "../../migrations")]
+async fn test_private_key_cannot_be_moved_between_users(pg_pool: PgPool) {
+    let db = PostgresPool { pg_pool };
+    let owner = create_client(&db, "owner").await;
+    let victim = register_user(&db, &owner).await;
+    let attacker = register_user(&db, &owner).await;
+
+    // Copy the victim's encrypted key and data key onto the attacker's row.
+    sqlx::query(
+        r#"
+        UPDATE users SET
+            encrypted_private_key = victim.encrypted_private_key,
+            encrypted_data_key = victim.encrypted_data_key
+        FROM users AS victim
+        WHERE users.id = $1 AND victim.id = $2
+        "#,
+    )
+    .bind(attacker.clone())
+    .bind(victim.clone())
+    .execute(&db.pg_pool)
+    .await
+    .expect("copy encrypted key");
+
+    let moved = WalletRepository::get_user(&db, owner.clone(), attacker)
+        .await
+        .expect("get user")
+        .expect("user");
+    assert!(moved.decrypt(&master_key()).await.is_err());
+
+    let original = WalletRepository::get_user(&db, owner, victim)
+        .await
+        .expect("get user")
+        .expect("user");
+    assert!(original.decrypt(&master_key()).await.is_ok());
+}
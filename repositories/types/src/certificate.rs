@@ -0,0 +1,26 @@
+use types::{
+    certificate::{CertificateFingerprint, CertificateInfo},
+    client::ClientId,
+};
+
+pub trait CertificateRepository {
+    /// Registers a client certificate by its fingerprint.
+    ///
+    /// Returns `None` if the certificate is already registered, to this client or another.
+    fn create_certificate(
+        &self,
+        client_id: ClientId,
+        fingerprint: &CertificateFingerprint,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<CertificateInfo>>> + Send;
+    /// Lists the certificates registered to a client, oldest first.
+    fn list_certificates(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<CertificateInfo>>> + Send;
+    /// Returns `false` if the certificate is not registered to the client.
+    fn delete_certificate(
+        &self,
+        client_id: ClientId,
+        fingerprint: &CertificateFingerprint,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
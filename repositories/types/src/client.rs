@@ -1,14 +1,61 @@
-use types::client::{encrypt::EncryptedClient, ClientId};
+use crate::tenant_key::TenantKeyRepository;
+use types::{
+    client::{encrypt::EncryptedClient, ClientId},
+    dashboard::DashboardAccount,
+    tenant::encrypt::EncryptedTenantKey,
+    user::UserInfo,
+};
+
+/// Outcome of [`ClientRepository::create_with_tenant_key`], nothing is stored unless created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateClientResult {
+    Created,
+    /// A client with the same name exists.
+    NameTaken,
+    /// The email address of the dashboard account is taken.
+    EmailTaken,
+}
 
 pub trait ClientRepository {
+    /// Creates a client without a tenant key, as clients were created before tenant keys.
     fn create(
         &self,
         client: EncryptedClient,
     ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Creates the client with its dashboard account, if any, and stores its tenant key in the
+    /// key store.
+    ///
+    /// The client and its account are created together or not at all. The key store is another
+    /// database: the tenant key is stored before the client is committed, and deleted again if
+    /// the client cannot be, or by the next creation of the client if that deletion fails.
+    fn create_with_tenant_key(
+        &self,
+        client: EncryptedClient,
+        tenant_key: EncryptedTenantKey,
+        dashboard_account: Option<DashboardAccount>,
+        key_store: &(impl TenantKeyRepository + Sync),
+    ) -> impl std::future::Future<Output = anyhow::Result<CreateClientResult>> + Send;
+    /// Finds the client with its oldest active HMAC credentials, or its oldest HMAC credentials
+    /// if none is active.
     fn find(
         &self,
         client_id: ClientId,
     ) -> impl std::future::Future<Output = anyhow::Result<Option<EncryptedClient>>> + Send;
+    /// Deletes the client with its credentials, users, dashboard accounts, certificates and
+    /// network policy. Its tenant key is deleted from the key store beforehand.
+    ///
+    /// Returns `false` if the client does not exist.
+    fn delete(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    /// Lists the users of the client by id, `limit` of them from `offset` on.
+    fn list_users(
+        &self,
+        client_id: ClientId,
+        limit: i64,
+        offset: i64,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<UserInfo>>> + Send;
     fn find_by_name(
         &self,
         name: &str,
@@ -0,0 +1,42 @@
+use types::{
+    api_key::ApiKey,
+    client::{encrypt::EncryptedCredentials, ClientId, CredentialsInfo},
+    client_key::PublicKeyCredentials,
+    secret::mask::Masked,
+};
+
+pub trait CredentialsRepository {
+    /// Stores additional credentials of a client, valid until `expires_at` if set.
+    fn create_credentials(
+        &self,
+        credentials: EncryptedCredentials,
+        expires_at: Option<i64>,
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Stores credentials of a client signing requests with its own key pair, valid until
+    /// `expires_at` if set.
+    fn create_public_key_credentials(
+        &self,
+        credentials: &PublicKeyCredentials,
+        expires_at: Option<i64>,
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Lists the credentials of a client, revoked and expired ones included, oldest first.
+    fn list_credentials(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<CredentialsInfo>>> + Send;
+    /// Sets or clears the expiry of active credentials, neither revoked nor expired.
+    ///
+    /// Returns `None` if the client owns no such credentials.
+    fn set_credentials_expiry(
+        &self,
+        client_id: ClientId,
+        api_key: &Masked<ApiKey>,
+        expires_at: Option<i64>,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<CredentialsInfo>>> + Send;
+    /// Returns `false` if the client owns no such credentials or they are already revoked.
+    fn revoke_credentials(
+        &self,
+        client_id: ClientId,
+        api_key: &Masked<ApiKey>,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
@@ -0,0 +1,30 @@
+use types::dashboard::DashboardAccount;
+use uuid::Uuid;
+
+pub trait DashboardRepository {
+    /// Returns `false` if the email address is already taken.
+    fn create_account(
+        &self,
+        account: DashboardAccount,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    fn find_account(
+        &self,
+        email: &str,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<DashboardAccount>>> + Send;
+    /// Stores the digest of a password reset token, valid until `expires_at` in Unix seconds.
+    fn create_password_reset(
+        &self,
+        account_id: Uuid,
+        token_hash: &str,
+        expires_at: i64,
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Sets the password of the account the token was issued for, using up every token issued
+    /// for it.
+    ///
+    /// Returns `false` if the token is unknown, used or expired.
+    fn reset_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
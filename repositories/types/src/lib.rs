@@ -1,2 +1,11 @@
+pub mod certificate;
 pub mod client;
+pub mod credentials;
+pub mod dashboard;
+pub mod lockout;
+pub mod master_key;
+pub mod network;
+pub mod nonce;
+pub mod operator;
+pub mod tenant_key;
 pub mod wallet;
@@ -0,0 +1,39 @@
+use types::{api_key::ApiKey, client::ClientId, lockout::Lockout, secret::mask::Masked};
+
+pub trait LockoutRepository {
+    fn find_lockout(
+        &self,
+        api_key: &Masked<ApiKey>,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<Lockout>>> + Send;
+    /// Counts an authentication failure of the credentials.
+    ///
+    /// Returns the number of consecutive failures, this one included.
+    fn record_failure(
+        &self,
+        api_key: &Masked<ApiKey>,
+    ) -> impl std::future::Future<Output = anyhow::Result<u32>> + Send;
+    /// Rejects authentication with the credentials until `locked_until`.
+    fn lock(
+        &self,
+        api_key: &Masked<ApiKey>,
+        locked_until: i64,
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Forgets the failures of the credentials once they authenticate.
+    fn clear_failures(
+        &self,
+        api_key: &Masked<ApiKey>,
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    /// Lists the credentials of a client with authentication failures, locked out or not.
+    fn list_lockouts(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<Lockout>>> + Send;
+    /// Unlocks credentials of a client and forgets their failures.
+    ///
+    /// Returns `false` if the client owns no such credentials with failures.
+    fn unlock(
+        &self,
+        client_id: ClientId,
+        api_key: &Masked<ApiKey>,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
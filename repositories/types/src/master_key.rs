@@ -0,0 +1,32 @@
+use types::encrypt::{
+    rotation::{DataKeyOwner, WrappedDataKey},
+    Encrypted,
+};
+use uuid::Uuid;
+
+/// Wrapped keys to re-wrap, the tenant keys being read from the key store and the data keys
+/// of the other owners from the main database.
+pub trait MasterKeyRepository {
+    /// Counts the data keys of `owner` not wrapped by the given key.
+    fn count_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+    ) -> impl std::future::Future<Output = anyhow::Result<u64>> + Send;
+    /// Returns up to `limit` data keys of `owner` not wrapped by the given key, ordered by id
+    /// and starting after `after`.
+    fn find_data_keys_to_rewrap(
+        &self,
+        owner: DataKeyOwner,
+        key_id: &str,
+        after: Option<Uuid>,
+        limit: u32,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<WrappedDataKey>>> + Send;
+    /// Replaces the data key, unless it changed since it was read.
+    fn replace_data_key(
+        &self,
+        data_key: &WrappedDataKey,
+        key_id: &str,
+        encrypted_data_key: Encrypted,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
@@ -0,0 +1,16 @@
+use types::{client::ClientId, network::IpNetwork};
+
+pub trait NetworkRepository {
+    /// Networks the client may send wallet requests from, empty if it is not restricted.
+    fn find_allowed_networks(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<IpNetwork>>> + Send;
+    /// Replaces the networks the client may send wallet requests from, an empty list lifting
+    /// the restriction.
+    fn set_allowed_networks(
+        &self,
+        client_id: ClientId,
+        networks: &[IpNetwork],
+    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+}
@@ -0,0 +1,12 @@
+use types::{api_key::ApiKey, secret::mask::Masked};
+
+pub trait NonceRepository {
+    /// Records `nonce` as used by `api_key` until `expires_at` (UNIX timestamp in seconds).
+    /// Returns `false` if the nonce was already used and has not expired yet.
+    fn use_nonce(
+        &self,
+        api_key: &Masked<ApiKey>,
+        nonce: &str,
+        expires_at: u64,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
@@ -0,0 +1,17 @@
+use types::operator::Operator;
+
+pub trait OperatorRepository {
+    /// Returns `false` if the username is already taken.
+    fn create_operator(
+        &self,
+        operator: Operator,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    fn find_operator(
+        &self,
+        username: &str,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<Operator>>> + Send;
+    /// Lists operators by username.
+    fn list_operators(
+        &self,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<Operator>>> + Send;
+}
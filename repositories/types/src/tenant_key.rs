@@ -0,0 +1,22 @@
+use types::{client::ClientId, tenant::encrypt::EncryptedTenantKey};
+
+pub trait TenantKeyRepository {
+    fn find_tenant_key(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<EncryptedTenantKey>>> + Send;
+    /// Stores the tenant key of a client created before tenant keys.
+    ///
+    /// Returns `false` if the client already has one.
+    fn create_tenant_key(
+        &self,
+        tenant_key: EncryptedTenantKey,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    /// Deletes the tenant key, making the secrets of the client unrecoverable.
+    ///
+    /// Returns `false` if the client has none.
+    fn delete_tenant_key(
+        &self,
+        client_id: ClientId,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+}
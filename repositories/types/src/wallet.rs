@@ -1,26 +1,61 @@
 use types::{
     api_key::ApiKey,
-    client::encrypt::EncryptedCredentials,
+    client::{encrypt::StoredCredentials, ClientId},
+    hd::Account,
     secret::mask::Masked,
     user::{encrypt::EncryptedUser, UserId},
 };
 
 pub trait WalletRepository {
+    /// Returns active credentials only, neither revoked nor expired.
     fn get_credentials(
         &self,
         api_key: &Masked<ApiKey>,
-    ) -> impl std::future::Future<Output = anyhow::Result<Option<EncryptedCredentials>>> + Send;
+    ) -> impl std::future::Future<Output = anyhow::Result<Option<StoredCredentials>>> + Send;
+    /// Stores a new user of the client, together with the default account of HD wallets.
     fn register_user(
         &self,
-        api_key: Masked<ApiKey>,
+        client_id: ClientId,
         encrypted_user: EncryptedUser,
+        default_account: Option<Account>,
     ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
     fn get_user(
         &self,
+        client_id: ClientId,
         user_id: UserId,
     ) -> impl std::future::Future<Output = anyhow::Result<Option<EncryptedUser>>> + Send;
+    /// Users registered before public keys were kept in clear, with their client, ordered by
+    /// id from `after` on.
+    fn find_users_without_public_key(
+        &self,
+        after: Option<UserId>,
+        limit: u32,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<(ClientId, EncryptedUser)>>> + Send;
+    /// Stores the public key of a user registered before public keys were kept in clear.
+    fn set_public_key(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+        public_key: String,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    /// Returns `false` if no user with the given id is owned by the client.
     fn delete_user(
         &self,
+        client_id: ClientId,
         user_id: UserId,
-    ) -> impl std::future::Future<Output = anyhow::Result<()>> + Send;
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    /// Stores an account derived from the user's HD wallet.
+    ///
+    /// Returns `false` if the user is not owned by the client or the account already exists.
+    fn add_account(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+        account: Account,
+    ) -> impl std::future::Future<Output = anyhow::Result<bool>> + Send;
+    fn list_accounts(
+        &self,
+        client_id: ClientId,
+        user_id: UserId,
+    ) -> impl std::future::Future<Output = anyhow::Result<Vec<Account>>> + Send;
 }
@@ -1,19 +1,43 @@
-use postgres_database::PostgresPool;
-use serde::Deserialize;
-use std::fmt::Debug;
+use kms::{
+    config::{KeyProviderConfig, MasterKeysConfig, UnsealConfig},
+    master_keys::MasterKeys,
+    unseal::UnsealContext,
+};
+use memory_database::MemoryStore;
+use postgres_database::{KeyStore, PostgresPool};
+use repositories::nonce::NonceRepository;
+use secrecy::{ExposeSecret, SecretBox};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{fmt::Debug, str::FromStr};
+use tls::config::TlsConfig;
 use types::{
-    config::ConfigReader,
-    db::postgres::PostgresConnection,
-    encrypt::master_key::{from_file, MasterKey},
+    api_key::ApiKey, config::ConfigReader, db::postgres::PostgresConnection,
+    lockout::LockoutPolicy, network::IpNetwork, secret::mask::Masked,
 };
 
 #[derive(Deserialize)]
 pub struct Config {
     pub rust_log: String,
     pub port: u16,
-    #[serde(deserialize_with = "from_file")]
-    pub master_key: MasterKey,
+    /// Path to the master key file, used when no key provider is configured.
+    pub master_key: Option<String>,
+    pub key_provider: Option<KeyProviderConfig>,
+    /// Several master keys during rotation, taking precedence over `key_provider`.
+    pub master_keys: Option<MasterKeysConfig>,
+    /// Required when a master key is split into shares.
+    pub unseal: Option<UnsealConfig>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Serves HTTPS, verifying client certificates as an additional authentication factor
+    /// when `client_ca` is set.
+    pub tls: Option<TlsConfig>,
     database: PostgresConnection,
+    /// Database of the tenant keys, kept out of the backups of `database`.
+    key_store: PostgresConnection,
 }
 
 impl Debug for Config {
@@ -21,19 +45,184 @@ impl Debug for Config {
         f.debug_struct("Config")
             .field("port", &self.port)
             .field("rust_log", &self.rust_log)
+            .field("key_provider", &self.key_provider)
+            .field("master_keys", &self.master_keys)
+            .field("unseal", &self.unseal)
+            .field("auth", &self.auth)
+            .field("batch", &self.batch)
+            .field("network", &self.network)
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthConfig {
+    /// Maximum allowed difference in seconds between `x-timestamp` and the server clock.
+    #[serde(default = "default_timestamp_window")]
+    pub timestamp_window: u64,
+    #[serde(default)]
+    pub nonce_store: NonceStoreKind,
+    /// HMAC key signing access tokens, at least 32 bytes long. Access tokens are not issued
+    /// when it is not set.
+    pub jwt_secret: Option<SecretBox<String>>,
+    /// Lifetime of access tokens in seconds.
+    #[serde(default = "default_token_ttl")]
+    pub token_ttl: u64,
+    #[serde(default)]
+    pub lockout: LockoutPolicy,
+}
+
+fn default_timestamp_window() -> u64 {
+    300
+}
+
+fn default_token_ttl() -> u64 {
+    300
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            timestamp_window: default_timestamp_window(),
+            nonce_store: NonceStoreKind::default(),
+            jwt_secret: None,
+            token_ttl: default_token_ttl(),
+            lockout: LockoutPolicy::default(),
+        }
+    }
+}
+
+impl Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("timestamp_window", &self.timestamp_window)
+            .field("nonce_store", &self.nonce_store)
+            .field("access_tokens", &self.jwt_secret.is_some())
+            .field("token_ttl", &self.token_ttl)
+            .field("lockout", &self.lockout)
             .finish()
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BatchConfig {
+    /// Maximum number of messages signed by a single batch request.
+    #[serde(default = "default_batch_max_size")]
+    pub max_size: usize,
+    /// Maximum size in bytes of batch request bodies, large enough for full batches.
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+}
+
+fn default_batch_max_size() -> usize {
+    1000
+}
+
+fn default_max_body_size() -> usize {
+    4 * 1024 * 1024
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_batch_max_size(),
+            max_body_size: default_max_body_size(),
+        }
+    }
+}
+
+/// Addresses of wallet clients, e.g. `NETWORK__TRUSTED_PROXIES=10.0.0.0/8,192.168.1.10`.
+#[derive(Debug, Default, Deserialize)]
+pub struct NetworkConfig {
+    /// Proxies whose `X-Forwarded-For` entries are trusted, comma separated. The header is
+    /// ignored when none is set.
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub trusted_proxies: Vec<IpNetwork>,
+}
+
+fn comma_separated<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<IpNetwork>, D::Error> {
+    String::deserialize(deserializer)?
+        .split(',')
+        .map(str::trim)
+        .filter(|network| !network.is_empty())
+        .map(|network| IpNetwork::from_str(network).map_err(D::Error::custom))
+        .collect()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceStoreKind {
+    #[default]
+    Postgres,
+    Memory,
+}
+
+pub enum NonceStore {
+    Postgres(PostgresPool),
+    Memory(MemoryStore),
+}
+
+impl NonceRepository for NonceStore {
+    async fn use_nonce(
+        &self,
+        api_key: &Masked<ApiKey>,
+        nonce: &str,
+        expires_at: u64,
+    ) -> anyhow::Result<bool> {
+        match self {
+            NonceStore::Postgres(store) => store.use_nonce(api_key, nonce, expires_at).await,
+            NonceStore::Memory(store) => store.use_nonce(api_key, nonce, expires_at).await,
+        }
+    }
+}
+
 pub struct Context {
     pub config: Config,
     pub database: PostgresPool,
+    pub key_store: KeyStore,
+    pub nonce_store: NonceStore,
+    pub master_keys: MasterKeys,
 }
 
 impl Context {
     pub async fn build() -> anyhow::Result<Self> {
         let config = Config::read_config()?;
+        if let Some(jwt_secret) = &config.auth.jwt_secret {
+            if jwt_secret.expose_secret().len() < 32 {
+                anyhow::bail!("AUTH__JWT_SECRET must be at least 32 bytes long");
+            }
+        }
+        let master_keys = MasterKeys::from_config(
+            config.master_keys.as_ref(),
+            config.key_provider.as_ref(),
+            config.master_key.as_deref(),
+        )?;
+        if master_keys.is_sealed() && config.unseal.is_none() {
+            anyhow::bail!("Set UNSEAL__TOKEN to unseal the master keys");
+        }
         let database = PostgresPool::new(&config.database).await?;
-        Ok(Self { config, database })
+        let key_store = KeyStore::new(&config.key_store).await?;
+        let nonce_store = match config.auth.nonce_store {
+            NonceStoreKind::Postgres => NonceStore::Postgres(database.clone()),
+            NonceStoreKind::Memory => NonceStore::Memory(MemoryStore::new()),
+        };
+        Ok(Self {
+            config,
+            database,
+            key_store,
+            nonce_store,
+            master_keys,
+        })
+    }
+}
+
+impl UnsealContext for Context {
+    fn master_keys(&self) -> &MasterKeys {
+        &self.master_keys
+    }
+
+    fn unseal_config(&self) -> Option<&UnsealConfig> {
+        self.config.unseal.as_ref()
     }
 }
@@ -0,0 +1,55 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use types::error::Error;
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<&Error> for ErrorResponse {
+    fn from(err: &Error) -> Self {
+        // Details of internal failures are only logged, never returned to the client
+        let message = if err.http_status().is_server_error() {
+            "Internal server error".to_string()
+        } else {
+            err.to_string()
+        };
+        ErrorResponse {
+            code: err.code(),
+            message,
+        }
+    }
+}
+
+/// Domain error returned from route handlers, rendered as an `ErrorResponse`.
+#[derive(Debug)]
+pub struct ApiError(pub Error);
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        ApiError(err)
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.0.http_status().as_u16())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            tracing::error!("Request failed: {}", self.0);
+        }
+        HttpResponse::build(self.status_code()).json(ErrorResponse::from(&self.0))
+    }
+}
@@ -1,5 +1,7 @@
 mod context;
+mod error;
 mod middleware;
+mod oauth;
 mod routes;
 mod server;
 
@@ -10,8 +12,8 @@ use std::str::FromStr;
 async fn main() -> anyhow::Result<()> {
     let ctx = Context::build().await?;
 
-    let tracing_level = tracing::Level::from_str(&ctx.config.rust_log)
-        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    let tracing_level =
+        tracing::Level::from_str(&ctx.config.rust_log).map_err(std::io::Error::other)?;
 
     tracing::subscriber::set_global_default(
         tracing_subscriber::FmtSubscriber::builder()
@@ -19,10 +21,7 @@ async fn main() -> anyhow::Result<()> {
             .finish(),
     )
     .map_err(|err| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to initialize tracing subscriber: {}", err),
-        )
+        std::io::Error::other(format!("Failed to initialize tracing subscriber: {}", err))
     })?;
 
     tracing::info!("Starting service with config: {:?}", ctx.config);
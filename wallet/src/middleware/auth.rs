@@ -1,17 +1,45 @@
-use crate::context::Context;
+use crate::{context::Context, error::ErrorResponse, oauth};
 use actix_http::h1;
 use actix_web::{
-    body::EitherBody,
+    body::{self, BodyStream, EitherBody},
     dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{header, Method},
     web::{Bytes, Data},
-    Error, HttpResponse,
+    Error, HttpMessage, HttpRequest, HttpResponse,
 };
+use anyhow::Context as _;
 use futures_util::future::{ready, LocalBoxFuture, Ready};
-use repositories::wallet::WalletRepository;
-use std::rc::Rc;
-use types::{api_key::ApiKey, secret::mask::Masked};
+use kms::master_keys::MasterKeys;
+use repositories::{
+    certificate::CertificateRepository, lockout::LockoutRepository, network::NetworkRepository,
+    nonce::NonceRepository, tenant_key::TenantKeyRepository, wallet::WalletRepository,
+};
+use std::{
+    net::IpAddr,
+    rc::Rc,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tls::config::ClientAuth;
+use types::{
+    api_key::ApiKey,
+    certificate::CertificateFingerprint,
+    client::{ClientCredentials, ClientId},
+    error,
+    lockout::Lockout,
+    network,
+    request_signature::{self, SignatureVersion},
+    scope::Scope,
+    secret::mask::Masked,
+    tenant::{TenantKey, TenantKeyRing},
+};
 use uuid::Uuid;
 
+const FORWARDED_FOR: &str = "x-forwarded-for";
+/// Default body limit of `web::PayloadConfig`, in bytes.
+const DEFAULT_BODY_LIMIT: usize = 256 * 1024;
+
 pub struct Auth;
 
 impl<S, B> Transform<S, ServiceRequest> for Auth
@@ -53,57 +81,299 @@ where
         Box::pin(async move {
             if let Some(context) = req.app_data::<Data<Context>>() {
                 let ctx = context.clone();
-                match AuthData::from_request(&mut req).await {
-                    Ok(auth_data) => {
-                        tracing::debug!("Extracted authentication data: {:?}", auth_data);
-
-                        match auth_data.check_authentication(&ctx).await {
-                            Ok(_) => {
-                                return svc.call(req).await.map(|res| res.map_into_left_body())
-                            }
-                            Err(err) => {
-                                tracing::error!("Authentication failed: {}", err);
-                                return Ok(req
-                                    .into_response(
-                                        HttpResponse::Unauthorized().body("Unauthorized"),
-                                    )
-                                    .map_into_right_body());
-                            }
-                        }
+                match authenticate(&ctx, &mut req).await {
+                    Ok((client_id, tenant_key)) => {
+                        req.extensions_mut().insert(client_id);
+                        req.extensions_mut().insert(tenant_key);
+                        svc.call(req).await.map(|res| res.map_into_left_body())
                     }
                     Err(err) => {
-                        tracing::error!(
-                            "Failed to extract authentication message from request: {}",
-                            err
-                        );
-                        return Ok(req
-                            .into_response(HttpResponse::Unauthorized().body("Unauthorized"))
-                            .map_into_right_body());
+                        tracing::error!("Authentication failed: {:#}", err);
+                        Ok(req.into_response(unauthorized(&err)).map_into_right_body())
                     }
                 }
             } else {
                 tracing::error!("Failed to extract context");
-                return Ok(req
+                Ok(req
                     .into_response(HttpResponse::InternalServerError().body("No context found"))
-                    .map_into_right_body());
+                    .map_into_right_body())
             }
         })
     }
 }
 
+/// Authenticates the request with an access token or a request signature, then matches the
+/// client certificate of the connection.
+async fn authenticate(
+    ctx: &Context,
+    req: &mut ServiceRequest,
+) -> anyhow::Result<(ClientId, ClientTenantKey)> {
+    let address = client_address(ctx, req.request());
+    let (client_id, tenant_key) = match bearer_token(req) {
+        Some(token) => check_access_token(ctx, &token, route_scope(req)?, address).await?,
+        None => {
+            let body_limit = route_body_limit(ctx, req);
+            let auth_data = AuthData::from_request(req, body_limit)
+                .await
+                .context("Failed to extract authentication message from request")?;
+            tracing::debug!("Extracted authentication data: {:?}", auth_data);
+            auth_data.check_authentication(ctx, address).await?
+        }
+    };
+    check_client_certificate(ctx, req, &client_id).await?;
+    Ok((client_id, tenant_key))
+}
+
+/// Token of the `Authorization: Bearer` header, sent instead of a request signature.
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Largest body the matched wallet route accepts, only batches may exceed the default limit of
+/// `web::PayloadConfig`.
+fn route_body_limit(ctx: &Context, req: &ServiceRequest) -> usize {
+    match req.match_pattern().as_deref() {
+        Some("/wallet/{user_id}/sign/batch") => ctx.config.batch.max_body_size,
+        _ => DEFAULT_BODY_LIMIT,
+    }
+}
+
+/// Scope the matched wallet route requires, `None` for paths without a route.
+fn route_scope(req: &ServiceRequest) -> anyhow::Result<Option<Scope>> {
+    req.match_pattern()
+        .map(|pattern| required_scope(req.method(), &pattern))
+        .transpose()
+}
+
+/// Verifies an access token and that the credentials it was issued for are still active and
+/// not locked out, so that revoking or locking out credentials suspends their tokens.
+async fn check_access_token(
+    ctx: &Context,
+    token: &str,
+    required_scope: Option<Scope>,
+    address: Option<IpAddr>,
+) -> anyhow::Result<(ClientId, ClientTenantKey)> {
+    let claims = oauth::decode_access_token(&ctx.config.auth, token)?;
+    let api_key = Masked::from(ApiKey::from(claims.api_key));
+    match WalletRepository::get_credentials(&ctx.database, &api_key).await? {
+        Some(credentials) if *credentials.client_id() == claims.sub => {}
+        _ => return Err(error::Error::InvalidAccessToken.into()),
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    check_lockout(ctx, &api_key, now).await?;
+    check_network(ctx, &claims.sub, address).await?;
+
+    if let Some(scope) = required_scope {
+        if !claims.scopes.contains(&scope) {
+            return Err(error::Error::MissingScope(scope).into());
+        }
+    }
+
+    let tenant_key = tenant_key(ctx, claims.sub.clone()).await?;
+    Ok((claims.sub, tenant_key))
+}
+
+/// Address the request was sent from, behind the proxies of `NETWORK__TRUSTED_PROXIES`.
+pub(crate) fn client_address(ctx: &Context, req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let forwarded_for: Vec<&str> = req
+        .headers()
+        .get_all(FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    Some(network::client_address(
+        peer,
+        &forwarded_for,
+        &ctx.config.network.trusted_proxies,
+    ))
+}
+
+/// Rejects requests sent from outside the networks the client is restricted to, if any.
+pub(crate) async fn check_network(
+    ctx: &Context,
+    client_id: &ClientId,
+    address: Option<IpAddr>,
+) -> anyhow::Result<()> {
+    let networks =
+        NetworkRepository::find_allowed_networks(&ctx.database, client_id.clone()).await?;
+    if networks.is_empty() {
+        return Ok(());
+    }
+    match address {
+        Some(address) if networks.iter().any(|network| network.contains(address)) => Ok(()),
+        Some(address) => Err(error::Error::AddressNotAllowed(address.to_string()).into()),
+        None => Err(error::Error::AddressNotAllowed("an unknown address".to_string()).into()),
+    }
+}
+
+/// Runs `check` unless the credentials are locked out, counting its failures towards the next
+/// lockout of `AUTH__LOCKOUT`.
+///
+/// Only wrong signatures and secrets count: they are what guessing a secret or forging a
+/// signature produces.
+pub(crate) async fn check_with_lockout(
+    ctx: &Context,
+    api_key: &Masked<ApiKey>,
+    check: impl FnOnce() -> Result<(), error::Error>,
+) -> anyhow::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let lockout = check_lockout(ctx, api_key, now).await?;
+
+    match check() {
+        Ok(()) => {
+            if lockout.is_some() {
+                LockoutRepository::clear_failures(&ctx.database, api_key).await?;
+            }
+            Ok(())
+        }
+        Err(err @ (error::Error::InvalidSignature | error::Error::InvalidClientCredentials)) => {
+            let failures = LockoutRepository::record_failure(&ctx.database, api_key).await?;
+            if let Some(duration) = ctx.config.auth.lockout.lock_duration(failures) {
+                LockoutRepository::lock(&ctx.database, api_key, now + duration as i64).await?;
+                tracing::warn!(
+                    "Credentials {} locked out for {}s after {} consecutive failures",
+                    api_key,
+                    duration,
+                    failures
+                );
+            }
+            Err(err.into())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Rejects credentials locked out at `now`, returning their failures otherwise.
+async fn check_lockout(
+    ctx: &Context,
+    api_key: &Masked<ApiKey>,
+    now: i64,
+) -> anyhow::Result<Option<Lockout>> {
+    let lockout = LockoutRepository::find_lockout(&ctx.database, api_key).await?;
+    if let Some(retry_after) = lockout
+        .as_ref()
+        .and_then(|lockout| lockout.retry_after(now))
+    {
+        return Err(error::Error::CredentialsLocked(retry_after).into());
+    }
+    Ok(lockout)
+}
+
+/// Matches the certificate the client presented against the ones registered to it, when the
+/// wallet verifies client certificates.
+///
+/// Certificates are required of every client unless `TLS__CLIENT_AUTH=optional`, in which case
+/// only clients that registered certificates must present one of them.
+async fn check_client_certificate(
+    ctx: &Context,
+    req: &ServiceRequest,
+    client_id: &ClientId,
+) -> anyhow::Result<()> {
+    let Some(tls) = ctx
+        .config
+        .tls
+        .as_ref()
+        .filter(|tls| tls.client_ca.is_some())
+    else {
+        return Ok(());
+    };
+    let certificates =
+        CertificateRepository::list_certificates(&ctx.database, client_id.clone()).await?;
+    if certificates.is_empty() && tls.client_auth == ClientAuth::Optional {
+        return Ok(());
+    }
+    match req.request().conn_data::<CertificateFingerprint>() {
+        Some(fingerprint)
+            if certificates
+                .iter()
+                .any(|certificate| certificate.fingerprint == *fingerprint) =>
+        {
+            Ok(())
+        }
+        _ => Err(error::Error::UnknownClientCertificate.into()),
+    }
+}
+
+/// Active credentials of `api_key`, decrypted, with the tenant key of their client.
+pub(crate) async fn client_credentials(
+    ctx: &Context,
+    api_key: &Masked<ApiKey>,
+) -> anyhow::Result<(ClientCredentials, ClientTenantKey)> {
+    let stored_credentials = WalletRepository::get_credentials(&ctx.database, api_key)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Invalid API key"))?;
+
+    // Public-key credentials hold no secret, yet the tenant key is needed by the handlers
+    let tenant_key = tenant_key(ctx, stored_credentials.client_id().clone()).await?;
+    let credentials = stored_credentials
+        .decrypt(&tenant_key.key_ring(&ctx.master_keys))
+        .await?;
+    Ok((credentials, tenant_key))
+}
+
+async fn tenant_key(ctx: &Context, client_id: ClientId) -> anyhow::Result<ClientTenantKey> {
+    let tenant_key = match TenantKeyRepository::find_tenant_key(&ctx.key_store, client_id).await? {
+        Some(tenant_key) => Some(Arc::new(tenant_key.decrypt(&ctx.master_keys).await?)),
+        None => None,
+    };
+    Ok(ClientTenantKey(tenant_key))
+}
+
+/// Tenant key of the authenticated client, `None` for clients created before tenant keys.
+#[derive(Clone)]
+pub struct ClientTenantKey(Option<Arc<TenantKey>>);
+
+impl ClientTenantKey {
+    /// Key ring encrypting the client's data keys.
+    pub fn key_ring<'a>(&'a self, master_keys: &'a MasterKeys) -> TenantKeyRing<'a, MasterKeys> {
+        TenantKeyRing::new(master_keys, self.0.as_deref())
+    }
+}
+
 #[derive(Debug)]
 pub struct AuthData {
     pub api_key: Masked<ApiKey>,
     pub signature: String,
     pub timestamp: u64,
+    pub nonce: Option<String>,
     pub http_method: String,
     pub request_path: String,
     pub request_query: String,
-    pub request_body: Option<String>,
+    pub request_body: Bytes,
+    pub signature_version: SignatureVersion,
+    /// Signed headers with their values, for v2 signatures only.
+    pub signed_headers: Vec<(String, String)>,
+    /// `None` for paths without a route.
+    pub required_scope: Option<Scope>,
+}
+
+/// Scope the wallet route matching `pattern` requires.
+fn required_scope(method: &Method, pattern: &str) -> anyhow::Result<Scope> {
+    let scope = match pattern {
+        "/wallet/register" => Scope::UsersRegister,
+        "/wallet/{user_id}/sign"
+        | "/wallet/{user_id}/sign/batch"
+        | "/wallet/{user_id}/sign/personal"
+        | "/wallet/{user_id}/sign/typed-data"
+        | "/wallet/{user_id}/sign/transaction" => Scope::UsersSign,
+        "/wallet/{user_id}/verify" => Scope::UsersRead,
+        "/wallet/{user_id}/accounts" if method == Method::GET => Scope::UsersRead,
+        // Deriving an account registers a new address for the user
+        "/wallet/{user_id}/accounts" => Scope::UsersRegister,
+        "/wallet/{user_id}/revoke" => Scope::UsersRevoke,
+        // Routes without a scope are refused rather than left open
+        _ => anyhow::bail!("No scope defined for route {}", pattern),
+    };
+    Ok(scope)
 }
 
 impl AuthData {
-    pub async fn from_request(req: &mut ServiceRequest) -> anyhow::Result<Self> {
+    /// Reads the request body of up to `body_limit` bytes, which is then given back to the request.
+    pub async fn from_request(req: &mut ServiceRequest, body_limit: usize) -> anyhow::Result<Self> {
         let timestamp = req
             .headers()
             .get("x-timestamp")
@@ -118,56 +388,165 @@ impl AuthData {
             .map(ApiKey::from)
             .map(Masked::from)
             .ok_or_else(|| anyhow::anyhow!("Missing or invalid x-api-key header"))?;
+        let nonce = req
+            .headers()
+            .get("x-nonce")
+            .map(|value| value.to_str().map(|s| s.to_string()))
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("Invalid x-nonce header"))?;
         let signature = req
             .headers()
             .get("x-signature")
             .and_then(|value| value.to_str().map(|s| s.to_string()).ok())
             .ok_or_else(|| anyhow::anyhow!("Missing or invalid x-signature header"))?;
+        let signature_version = req
+            .headers()
+            .get(request_signature::SIGNATURE_VERSION_HEADER)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| anyhow::anyhow!("Invalid x-signature-version header"))
+                    .and_then(|str| Ok(str.parse::<SignatureVersion>()?))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let signed_headers = match signature_version {
+            SignatureVersion::V1 => Vec::new(),
+            SignatureVersion::V2 => {
+                let list = req
+                    .headers()
+                    .get(request_signature::SIGNED_HEADERS_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| anyhow::anyhow!("Missing or invalid x-signed-headers header"))?;
+                request_signature::signed_headers(list, nonce.is_some())?
+                    .into_iter()
+                    .map(|name| {
+                        let value = req
+                            .headers()
+                            .get(&name)
+                            .and_then(|value| value.to_str().ok())
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("Missing or invalid signed header {}", name)
+                            })?
+                            .trim()
+                            .to_string();
+                        Ok((name, value))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+        };
+        let required_scope = route_scope(req)?;
         let http_method = req.method().to_string();
         let request_path = req.path().to_string();
         let request_query = req.query_string().to_string();
-        let request_bytes = req
-            .extract::<Bytes>()
+        let request_bytes = body::to_bytes_limited(BodyStream::new(req.take_payload()), body_limit)
             .await
+            .map_err(|_| PayloadError::Overflow)?
             .map_err(|_| anyhow::anyhow!("Failed to extract request body as bytes"))?;
-        let request_body = String::from_utf8(request_bytes.to_vec()).ok();
 
-        req.set_payload(bytes_to_payload(request_bytes));
+        req.set_payload(bytes_to_payload(request_bytes.clone()));
 
         Ok(AuthData {
             api_key,
             signature,
             timestamp,
+            nonce,
             http_method,
             request_path,
             request_query,
-            request_body,
+            request_body: request_bytes,
+            signature_version,
+            signed_headers,
+            required_scope,
+        })
+    }
+
+    /// Verifies the address of the request, its signature and the scopes of the credentials,
+    /// then returns the client owning them, with its tenant key.
+    pub async fn check_authentication(
+        &self,
+        ctx: &Context,
+        address: Option<IpAddr>,
+    ) -> anyhow::Result<(ClientId, ClientTenantKey)> {
+        let (credentials, tenant_key) = client_credentials(ctx, &self.api_key).await?;
+
+        check_network(ctx, credentials.client_id(), address).await?;
+
+        let message = self.message()?;
+
+        check_with_lockout(ctx, &self.api_key, || {
+            credentials.check_authentication(&message, &self.signature)
         })
+        .await?;
+
+        self.check_replay(ctx).await?;
+
+        if let Some(scope) = self.required_scope {
+            credentials.check_scope(scope)?;
+        }
+
+        Ok((credentials.client_id().clone(), tenant_key))
     }
 
-    pub async fn check_authentication(&self, ctx: &Context) -> anyhow::Result<()> {
-        let encrypted_credentials = WalletRepository::get_credentials(&ctx.database, &self.api_key)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Invalid API key"))?;
+    /// Message the client signed, as selected by `x-signature-version`.
+    fn message(&self) -> Result<String, error::Error> {
+        match self.signature_version {
+            SignatureVersion::V1 => Ok(request_signature::message_v1(
+                self.timestamp,
+                self.nonce.as_deref(),
+                &self.http_method,
+                &self.request_path,
+                &self.request_query,
+                &self.request_body,
+            )),
+            SignatureVersion::V2 => request_signature::canonical_request(
+                &self.http_method,
+                &self.request_path,
+                &self.request_query,
+                &self.signed_headers,
+                &self.request_body,
+            ),
+        }
+    }
 
-        let credentials = encrypted_credentials.decrypt(&ctx.config.master_key)?;
+    /// Rejects requests signed outside of the configured time window and reused nonces.
+    async fn check_replay(&self, ctx: &Context) -> anyhow::Result<()> {
+        let window = ctx.config.auth.timestamp_window;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
-        // Create the message to sign
-        let message = format!(
-            "{}{}{}{}{}",
-            self.timestamp,
-            self.http_method,
-            self.request_path,
-            self.request_query,
-            self.request_body.as_ref().unwrap_or(&String::new())
-        );
+        if now.abs_diff(self.timestamp) > window {
+            return Err(error::Error::TimestampOutOfWindow.into());
+        }
 
-        credentials.check_authentication(&message, &self.signature)?;
+        if let Some(nonce) = &self.nonce {
+            let expires_at = self.timestamp.saturating_add(window);
+            if !NonceRepository::use_nonce(&ctx.nonce_store, &self.api_key, nonce, expires_at)
+                .await?
+            {
+                return Err(error::Error::NonceReused.into());
+            }
+        }
 
         Ok(())
     }
 }
 
+fn unauthorized(err: &anyhow::Error) -> HttpResponse {
+    if let Some(err @ PayloadError::Overflow) = err.downcast_ref::<PayloadError>() {
+        return HttpResponse::PayloadTooLarge().body(err.to_string());
+    }
+    match err.downcast_ref::<error::Error>() {
+        Some(err @ (error::Error::MissingScope(_) | error::Error::AddressNotAllowed(_))) => {
+            HttpResponse::Forbidden().json(ErrorResponse::from(err))
+        }
+        Some(err @ error::Error::CredentialsLocked(retry_after)) => HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(ErrorResponse::from(err)),
+        Some(err) => HttpResponse::Unauthorized().json(ErrorResponse::from(err)),
+        None => HttpResponse::Unauthorized().body("Unauthorized"),
+    }
+}
+
 fn bytes_to_payload(buf: Bytes) -> Payload {
     let (_, mut pl) = h1::Payload::create(true);
     pl.unread_data(buf);
@@ -0,0 +1,336 @@
+use crate::{
+    context::{AuthConfig, Context},
+    middleware::auth,
+};
+use actix_web::{
+    http::{header, StatusCode},
+    web::{Data, Form},
+    HttpRequest, HttpResponse,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use repositories::nonce::NonceRepository;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use types::{
+    api_key::ApiKey,
+    client::{ClientCredentials, ClientId},
+    error::Error,
+    scope::Scope,
+    secret::{mask::Masked, redact::Redacted},
+};
+use uuid::Uuid;
+
+/// Issuer of access tokens, and audience of the assertions exchanged for them.
+pub const ISSUER: &str = "pontoon-wallet";
+
+const CLIENT_CREDENTIALS: &str = "client_credentials";
+const JWT_BEARER_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Claims of access tokens, standing in for request signatures until they expire.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    pub sub: ClientId,
+    /// Credentials the token was issued for, revoking them revokes the token.
+    pub api_key: Uuid,
+    pub scopes: Vec<Scope>,
+    pub iss: String,
+    pub iat: u64,
+    pub exp: u64,
+    pub jti: Uuid,
+}
+
+/// Validates an access token, failing with [`Error::InvalidAccessToken`].
+pub fn decode_access_token(config: &AuthConfig, token: &str) -> Result<AccessTokenClaims, Error> {
+    let Some(jwt_secret) = &config.jwt_secret else {
+        return Err(Error::InvalidAccessToken);
+    };
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[ISSUER]);
+    validation.leeway = 0;
+    let key = DecodingKey::from_secret(jwt_secret.expose_secret().as_bytes());
+    jsonwebtoken::decode::<AccessTokenClaims>(token, &key, &validation)
+        .map(|token| token.claims)
+        .map_err(|err| {
+            tracing::debug!("Invalid access token: {}", err);
+            Error::InvalidAccessToken
+        })
+}
+
+/// Client credentials grant of RFC 6749, with the client authentication methods of
+/// RFC 7523 for assertions.
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Redacted<String>>,
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+    /// Space separated scopes, every scope of the credentials when not set.
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub scope: String,
+}
+
+/// Error of the token endpoint, in the format of RFC 6749 rather than an `ErrorResponse`, as
+/// OAuth client libraries expect.
+#[derive(Debug, Serialize)]
+struct OAuthError {
+    error: &'static str,
+    error_description: String,
+}
+
+fn oauth_error(status: StatusCode, error: &'static str, description: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(OAuthError {
+            error,
+            error_description: description.to_string(),
+        })
+}
+
+/// Exchanges client credentials, a secret or a signed assertion, for an access token.
+pub(crate) async fn token(
+    ctx: Data<Context>,
+    req: HttpRequest,
+    body: Form<TokenRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(jwt_secret) = &ctx.config.auth.jwt_secret else {
+        tracing::debug!("Access tokens are disabled, AUTH__JWT_SECRET is not set");
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let request = body.into_inner();
+    if request.grant_type != CLIENT_CREDENTIALS {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "only the client_credentials grant is supported",
+        ));
+    }
+
+    let credentials = match authenticate_client(&ctx, &req, &request).await {
+        Ok(credentials) => credentials,
+        Err(err) => {
+            tracing::error!("Client authentication failed: {}", err);
+            return Ok(oauth_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication failed",
+            ));
+        }
+    };
+
+    let scopes = match &request.scope {
+        None => credentials.scopes().to_vec(),
+        Some(scope) => match requested_scopes(scope, &credentials) {
+            Ok(scopes) => scopes,
+            Err(err) => {
+                return Ok(oauth_error(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_scope",
+                    &err.to_string(),
+                ))
+            }
+        },
+    };
+
+    let iat = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let claims = AccessTokenClaims {
+        sub: credentials.client_id().clone(),
+        api_key: credentials.api_key().expose().to_uuid(),
+        scopes,
+        iss: ISSUER.to_string(),
+        iat,
+        exp: iat + ctx.config.auth.token_ttl,
+        jti: Uuid::new_v4(),
+    };
+    let key = EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes());
+    match jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &key) {
+        Ok(access_token) => {
+            tracing::info!(
+                "Issued access token for credentials {}",
+                credentials.api_key()
+            );
+            Ok(HttpResponse::Ok()
+                .insert_header((header::CACHE_CONTROL, "no-store"))
+                .json(TokenResponse {
+                    access_token,
+                    token_type: "Bearer",
+                    expires_in: ctx.config.auth.token_ttl,
+                    scope: claims
+                        .scopes
+                        .iter()
+                        .map(Scope::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                }))
+        }
+        Err(err) => {
+            tracing::error!("Failed to issue access token: {}", err);
+            Ok(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+/// Parses the requested scopes, all of which the credentials must be granted.
+fn requested_scopes(scope: &str, credentials: &ClientCredentials) -> Result<Vec<Scope>, Error> {
+    let mut scopes = scope
+        .split_whitespace()
+        .map(str::parse::<Scope>)
+        .collect::<Result<Vec<_>, _>>()?;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(Error::UnknownScope(scope.to_string()));
+    }
+    for scope in &scopes {
+        credentials.check_scope(*scope)?;
+    }
+    Ok(scopes)
+}
+
+/// Authenticates the client with its secret, sent with HTTP Basic authentication or in the
+/// form, or with an assertion signed with its credentials.
+async fn authenticate_client(
+    ctx: &Context,
+    req: &HttpRequest,
+    request: &TokenRequest,
+) -> anyhow::Result<ClientCredentials> {
+    let address = auth::client_address(ctx, req);
+    if let Some(assertion) = &request.client_assertion {
+        if request.client_assertion_type.as_deref() != Some(JWT_BEARER_ASSERTION) {
+            anyhow::bail!("Unsupported client assertion type");
+        }
+        return authenticate_assertion(ctx, address, assertion, request.client_id.as_deref()).await;
+    }
+
+    let (client_id, client_secret) = match basic_credentials(req)? {
+        Some(credentials) => credentials,
+        None => match (&request.client_id, &request.client_secret) {
+            (Some(client_id), Some(client_secret)) => {
+                (client_id.clone(), client_secret.expose().clone())
+            }
+            _ => anyhow::bail!("Missing client credentials"),
+        },
+    };
+    let api_key = parse_api_key(&client_id)?;
+    let (credentials, _) = auth::client_credentials(ctx, &api_key).await?;
+    auth::check_network(ctx, credentials.client_id(), address).await?;
+    auth::check_with_lockout(ctx, &api_key, || credentials.check_secret(&client_secret)).await?;
+    Ok(credentials)
+}
+
+/// API key and secret of the `Authorization: Basic` header.
+fn basic_credentials(req: &HttpRequest) -> anyhow::Result<Option<(String, String)>> {
+    let Some(encoded) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+    else {
+        return Ok(None);
+    };
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim())?)?;
+    let (client_id, client_secret) = decoded
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Malformed basic credentials"))?;
+    Ok(Some((client_id.to_string(), client_secret.to_string())))
+}
+
+fn parse_api_key(client_id: &str) -> anyhow::Result<Masked<ApiKey>> {
+    Ok(Masked::from(ApiKey::from(Uuid::parse_str(client_id)?)))
+}
+
+#[derive(Debug, Deserialize)]
+struct AssertionHeader {
+    alg: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Claims of client assertions, issued by the client for itself: `iss` and `sub` are the API
+/// key of its credentials.
+#[derive(Debug, Deserialize)]
+struct AssertionClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: u64,
+    jti: String,
+}
+
+/// Verifies a JWS compact assertion with the credentials its `sub` names.
+///
+/// Assertions are signed with the secret of HMAC credentials (`HS256`) or the private key of
+/// public-key credentials (`EdDSA`, `ES256`), expire within the timestamp window and are
+/// accepted once.
+async fn authenticate_assertion(
+    ctx: &Context,
+    address: Option<IpAddr>,
+    assertion: &str,
+    client_id: Option<&str>,
+) -> anyhow::Result<ClientCredentials> {
+    let mut parts = assertion.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("Malformed client assertion");
+    };
+    let header: AssertionHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+    let claims: AssertionClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+    if claims.iss != claims.sub || client_id.is_some_and(|client_id| client_id != claims.sub) {
+        anyhow::bail!("Client assertion issued for another client");
+    }
+
+    let api_key = parse_api_key(&claims.sub)?;
+    let (credentials, _) = auth::client_credentials(ctx, &api_key).await?;
+    auth::check_network(ctx, credentials.client_id(), address).await?;
+    if header.alg != credentials.credential_type().jws_algorithm() {
+        anyhow::bail!(
+            "Client assertion algorithm {} does not match {} credentials",
+            header.alg,
+            credentials.credential_type()
+        );
+    }
+    let signing_input = &assertion[..assertion.len() - signature.len() - 1];
+    auth::check_with_lockout(ctx, &api_key, || {
+        credentials.check_authentication(signing_input, signature)
+    })
+    .await?;
+
+    let audience = match &claims.aud {
+        Audience::One(audience) => audience == ISSUER,
+        Audience::Many(audiences) => audiences.iter().any(|audience| audience == ISSUER),
+    };
+    if !audience {
+        anyhow::bail!("Client assertion is not intended for {}", ISSUER);
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    if claims.exp <= now || claims.exp > now + ctx.config.auth.timestamp_window {
+        return Err(Error::TimestampOutOfWindow.into());
+    }
+    if !NonceRepository::use_nonce(&ctx.nonce_store, &api_key, &claims.jti, claims.exp).await? {
+        return Err(Error::NonceReused.into());
+    }
+    Ok(credentials)
+}
@@ -1,64 +1,206 @@
-use crate::context::Context;
+use crate::{
+    context::Context,
+    error::{ApiError, ErrorResponse},
+    middleware::auth::ClientTenantKey,
+};
 use actix_web::{
-    web::{Data, Json, Path},
-    HttpRequest, HttpResponse,
+    web::{self, Bytes, Data, Json, Path, Query, ReqData},
+    HttpResponse,
 };
 use repositories::wallet::WalletRepository;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use types::{
-    api_key::ApiKey,
-    secret::mask::Masked,
-    user::{User, UserId},
+    client::ClientId,
+    encoding::{MessageEncoding, SignatureEncoding},
+    error::Error,
+    ethereum::{
+        eip712::TypedData,
+        transaction::{Transaction, TransactionRequest},
+    },
+    hd::{self, Account},
+    user::{KeyType, RsaOptions, SigningKey, User, UserId},
 };
-use uuid::Uuid;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RegisterUserRequest {
+    #[serde(default)]
+    pub key_type: KeyType,
+    /// Key size and signature scheme, RSA users only.
+    #[serde(default)]
+    pub rsa: Option<RsaOptions>,
+}
 
 #[derive(Debug, Clone, Serialize)]
 pub struct RegisterUserResponse {
     pub user_id: UserId,
+    pub key_type: KeyType,
     pub pub_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsa: Option<RsaOptions>,
 }
 
 pub(crate) async fn register_user(
     ctx: Data<Context>,
-    req: HttpRequest,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    body: Bytes,
 ) -> actix_web::Result<HttpResponse> {
-    tracing::debug!("Registering new user");
-
-    let api_key = req
-        .headers()
-        .get("x-api-key")
-        .and_then(|value| value.to_str().ok())
-        .and_then(|str| Uuid::parse_str(str).ok())
-        .map(ApiKey::from)
-        .map(Masked::from)
-        .ok_or_else(|| {
-            actix_web::error::ErrorUnauthorized("Missing or invalid x-api-key header")
-        })?;
-
-    let user = User::new()
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to create user"))?;
+    let client_id = client_id.into_inner();
+    tracing::debug!("Registering new user for client: {:?}", client_id);
+
+    // Request body is optional, RSA keys are generated by default
+    let request: RegisterUserRequest = if body.is_empty() {
+        RegisterUserRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(actix_web::error::ErrorBadRequest)?
+    };
+
+    if request.rsa.is_some() && request.key_type != KeyType::Rsa {
+        return Err(ApiError(Error::InvalidKeyOptions(
+            "rsa options are only supported by rsa keys".to_string(),
+        ))
+        .into());
+    }
+
+    // Key generation is CPU bound, large RSA keys take a while
+    let user = web::block(move || {
+        let signing_key = match request.rsa {
+            Some(options) => SigningKey::generate_rsa(options)?,
+            None => SigningKey::generate(request.key_type)?,
+        };
+        User::with_signing_key(signing_key)
+    })
+    .await?
+    .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to create user"))?;
 
     let response = RegisterUserResponse {
         user_id: user.id().clone(),
+        key_type: user.signing_key.key_type(),
         pub_key: user.signing_key.public_key_pem().map_err(|_| {
             actix_web::error::ErrorInternalServerError("Failed to get public key PEM")
         })?,
+        address: user.signing_key.address(),
+        rsa: user.signing_key.rsa_options(),
     };
 
-    let encrypted_user = user.encrypt(&ctx.config.master_key).map_err(|err| {
+    let key_ring = tenant_key.key_ring(&ctx.master_keys);
+    let encrypted_user = user.encrypt(&key_ring).await.map_err(|err| {
         tracing::error!("Failed to encrypt user: {}", err);
         actix_web::error::ErrorInternalServerError("Failed to encrypt user")
     })?;
 
-    match WalletRepository::register_user(&ctx.database, api_key, encrypted_user).await {
-        Ok(_) => Ok(HttpResponse::Created().json(response)),
-        Err(err) => {
-            tracing::error!("Failed to register user: {}", err);
-            Err(actix_web::error::ErrorInternalServerError(
-                "Failed to register user",
-            ))
+    // The default account of HD wallets is tracked like any other derived account
+    let default_account = match (response.key_type, response.address.clone()) {
+        (KeyType::Hd, Some(address)) => Some(Account {
+            index: 0,
+            path: hd::DEFAULT_DERIVATION_PATH.to_string(),
+            address,
+        }),
+        _ => None,
+    };
+
+    if let Err(err) =
+        WalletRepository::register_user(&ctx.database, client_id, encrypted_user, default_account)
+            .await
+    {
+        tracing::error!("Failed to register user: {}", err);
+        return Err(actix_web::error::ErrorInternalServerError(
+            "Failed to register user",
+        ));
+    }
+
+    Ok(HttpResponse::Created().json(response))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeriveAccountRequest {
+    /// Address index of the account, defaults to the next unused index.
+    pub index: Option<u32>,
+}
+
+pub(crate) async fn derive_account(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    path: Path<UserId>,
+    body: Bytes,
+) -> actix_web::Result<HttpResponse> {
+    let client_id = client_id.into_inner();
+    let user_id = path.into_inner();
+    tracing::debug!("Deriving account for user: {:?}", user_id);
+
+    // Request body is optional, the next account index is used by default
+    let request: DeriveAccountRequest = if body.is_empty() {
+        DeriveAccountRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(actix_web::error::ErrorBadRequest)?
+    };
+
+    let user = get_user(&ctx, &tenant_key, client_id.clone(), user_id.clone()).await?;
+
+    let index = match request.index {
+        Some(index) => index,
+        None => list_accounts(&ctx, client_id.clone(), user_id.clone())
+            .await?
+            .iter()
+            .map(|account| account.index + 1)
+            .max()
+            .unwrap_or_default(),
+    };
+    let derivation_path = hd::account_path(index).map_err(ApiError::from)?;
+    let address = user
+        .signing_key
+        .derive(&derivation_path)
+        .map_err(ApiError::from)?
+        .address()
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Failed to derive address"))?;
+
+    let account = Account {
+        index,
+        path: derivation_path,
+        address,
+    };
+    if !add_account(&ctx, client_id, user_id, account.clone()).await? {
+        return Err(actix_web::error::ErrorConflict("Account already exists"));
+    }
+
+    Ok(HttpResponse::Created().json(account))
+}
+
+pub(crate) async fn get_accounts(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    path: Path<UserId>,
+) -> actix_web::Result<HttpResponse> {
+    let client_id = client_id.into_inner();
+    let user_id = path.into_inner();
+    tracing::debug!("Listing accounts of user: {:?}", user_id);
+
+    let accounts = list_accounts(&ctx, client_id.clone(), user_id.clone()).await?;
+
+    // Users without HD wallets have no accounts, unknown users are reported as such
+    if accounts.is_empty() {
+        let user = WalletRepository::get_user(&ctx.database, client_id, user_id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Failed to get user: {}", err);
+                actix_web::error::ErrorInternalServerError("Failed to get user")
+            })?;
+        if user.is_none() {
+            return Err(actix_web::error::ErrorNotFound("User not found"));
         }
     }
+
+    Ok(HttpResponse::Ok().json(accounts))
+}
+
+/// Optional signing parameters shared by the sign endpoints.
+#[derive(Debug, Deserialize)]
+pub struct SignQuery {
+    /// BIP-32 derivation path of the key to sign with, HD wallets only.
+    pub path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -67,17 +209,320 @@ pub struct SignMessageResponse {
     pub signature: String,
 }
 
+/// Message to sign, either a plain UTF-8 string or a payload with explicit encodings.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SignMessageRequest {
+    Text(String),
+    Payload(SignPayload),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignPayload {
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// The message is a digest computed with the hash function of the user's key.
+    #[serde(default)]
+    pub prehashed: bool,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+}
+
+impl SignPayload {
+    pub fn decode(&self) -> Result<Vec<u8>, Error> {
+        self.encoding.decode(&self.message)
+    }
+
+    /// Signs the decoded message and encodes the signature as requested.
+    pub fn sign(&self, signing_key: &SigningKey, message: &[u8]) -> Result<String, Error> {
+        let signature = if self.prehashed {
+            signing_key.sign_digest(message)?
+        } else {
+            signing_key.sign_bytes(message)?
+        };
+        signing_key.encode_signature(&signature, self.signature_encoding)
+    }
+}
+
 pub(crate) async fn sign_message(
     ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
     path: Path<UserId>,
-    body: Json<String>,
+    query: Query<SignQuery>,
+    body: Json<SignMessageRequest>,
 ) -> actix_web::Result<HttpResponse> {
     let user_id = path.into_inner();
-    let message = body.into_inner();
     tracing::debug!("Signing message on behalf of user: {:?}", user_id);
 
-    // Get user
-    let encrypted_user = WalletRepository::get_user(&ctx.database, user_id)
+    // Decode the payload before the private key gets decrypted
+    let request = body.into_inner();
+    let message = request.decode().map_err(ApiError::from)?;
+
+    let signing_key = get_signing_key(
+        &ctx,
+        &tenant_key,
+        client_id.into_inner(),
+        user_id,
+        query.into_inner(),
+    )
+    .await?;
+
+    let signed = request
+        .sign(&signing_key, &message)
+        .map_err(ApiError::from)?;
+    Ok(HttpResponse::Ok().json(signed))
+}
+
+impl SignMessageRequest {
+    fn decode(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            SignMessageRequest::Text(message) => Ok(message.as_bytes().to_vec()),
+            SignMessageRequest::Payload(payload) => payload.decode(),
+        }
+    }
+
+    /// Signs the message returned by [`SignMessageRequest::decode`].
+    fn sign(self, signing_key: &SigningKey, message: &[u8]) -> Result<SignMessageResponse, Error> {
+        match self {
+            // Text messages are their own encoding
+            SignMessageRequest::Text(text) => {
+                let signature = signing_key.sign_message(&text)?;
+                Ok(SignMessageResponse {
+                    message: text,
+                    signature,
+                })
+            }
+            SignMessageRequest::Payload(payload) => {
+                let signature = payload.sign(signing_key, message)?;
+                Ok(SignMessageResponse {
+                    message: payload.message,
+                    signature,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignBatchRequest {
+    pub messages: Vec<SignMessageRequest>,
+}
+
+/// Outcome of a single batch item, failures don't affect the other items.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SignBatchResult {
+    Signed(SignMessageResponse),
+    Failed { error: ErrorResponse },
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignBatchResponse {
+    pub results: Vec<SignBatchResult>,
+}
+
+pub(crate) async fn sign_batch(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    path: Path<UserId>,
+    query: Query<SignQuery>,
+    body: Json<SignBatchRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let user_id = path.into_inner();
+    let messages = body.into_inner().messages;
+    tracing::debug!(
+        "Signing batch of {} messages on behalf of user: {:?}",
+        messages.len(),
+        user_id
+    );
+
+    let max_size = ctx.config.batch.max_size;
+    if messages.len() > max_size {
+        return Err(ApiError(Error::BatchTooLarge(max_size)).into());
+    }
+
+    // The signing key is decrypted once for the whole batch
+    let signing_key = get_signing_key(
+        &ctx,
+        &tenant_key,
+        client_id.into_inner(),
+        user_id,
+        query.into_inner(),
+    )
+    .await?;
+
+    let results = web::block(move || {
+        messages
+            .into_iter()
+            .map(|request| {
+                request
+                    .decode()
+                    .and_then(|message| request.sign(&signing_key, &message))
+            })
+            .map(|signed| match signed {
+                Ok(signed) => SignBatchResult::Signed(signed),
+                Err(err) => SignBatchResult::Failed {
+                    error: ErrorResponse::from(&err),
+                },
+            })
+            .collect()
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(SignBatchResponse { results }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignPersonalMessageRequest {
+    pub message: String,
+}
+
+pub(crate) async fn sign_personal_message(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    path: Path<UserId>,
+    query: Query<SignQuery>,
+    body: Json<SignPersonalMessageRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let user_id = path.into_inner();
+    let message = body.into_inner().message;
+    tracing::debug!("Signing personal message on behalf of user: {:?}", user_id);
+
+    let signing_key = get_signing_key(
+        &ctx,
+        &tenant_key,
+        client_id.into_inner(),
+        user_id,
+        query.into_inner(),
+    )
+    .await?;
+
+    let signature = signing_key
+        .sign_personal_message(message.as_bytes())
+        .map_err(ApiError::from)?;
+
+    Ok(HttpResponse::Ok().json(SignMessageResponse {
+        message,
+        signature: format!("0x{}", hex::encode(signature)),
+    }))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SignTypedDataResponse {
+    pub hash: String,
+    pub signature: String,
+}
+
+pub(crate) async fn sign_typed_data(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    path: Path<UserId>,
+    query: Query<SignQuery>,
+    body: Json<TypedData>,
+) -> actix_web::Result<HttpResponse> {
+    let user_id = path.into_inner();
+    tracing::debug!("Signing typed data on behalf of user: {:?}", user_id);
+
+    let hash = body.signing_hash().map_err(ApiError::from)?;
+
+    let signing_key = get_signing_key(
+        &ctx,
+        &tenant_key,
+        client_id.into_inner(),
+        user_id,
+        query.into_inner(),
+    )
+    .await?;
+
+    let signature = signing_key
+        .sign_ethereum_hash(&hash)
+        .map_err(ApiError::from)?;
+
+    Ok(HttpResponse::Ok().json(SignTypedDataResponse {
+        hash: format!("0x{}", hex::encode(hash)),
+        signature: format!("0x{}", hex::encode(signature)),
+    }))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SignTransactionResponse {
+    pub raw_transaction: String,
+    pub hash: String,
+}
+
+pub(crate) async fn sign_transaction(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    tenant_key: ReqData<ClientTenantKey>,
+    path: Path<UserId>,
+    query: Query<SignQuery>,
+    body: Json<TransactionRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let user_id = path.into_inner();
+    tracing::debug!("Signing transaction on behalf of user: {:?}", user_id);
+
+    // Validate transaction before the private key gets decrypted
+    let transaction = Transaction::try_from(body.into_inner()).map_err(ApiError::from)?;
+
+    let signing_key = get_signing_key(
+        &ctx,
+        &tenant_key,
+        client_id.into_inner(),
+        user_id,
+        query.into_inner(),
+    )
+    .await?;
+
+    let signed = transaction.sign(&signing_key).map_err(ApiError::from)?;
+
+    Ok(HttpResponse::Ok().json(SignTransactionResponse {
+        raw_transaction: format!("0x{}", hex::encode(signed.raw)),
+        hash: format!("0x{}", hex::encode(signed.hash)),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifySignatureRequest {
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    pub signature: String,
+    /// Encoding the signature was requested in, as accepted by the sign endpoint.
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifySignatureResponse {
+    pub valid: bool,
+}
+
+pub(crate) async fn verify_signature(
+    ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
+    path: Path<UserId>,
+    body: Json<VerifySignatureRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let client_id = client_id.into_inner();
+    let user_id = path.into_inner();
+    let request = body.into_inner();
+    tracing::debug!("Verifying signature on behalf of user: {:?}", user_id);
+
+    let message = request
+        .encoding
+        .decode(&request.message)
+        .map_err(ApiError::from)?;
+    let signature = request
+        .signature_encoding
+        .decode(&request.signature)
+        .map_err(ApiError::from)?;
+
+    let encrypted_user = WalletRepository::get_user(&ctx.database, client_id, user_id)
         .await
         .map_err(|err| {
             tracing::error!("Failed to get user: {}", err);
@@ -85,32 +530,39 @@ pub(crate) async fn sign_message(
         })?
         .ok_or_else(|| actix_web::error::ErrorNotFound("User not found"))?;
 
-    // Decrypt private key
-    let user = encrypted_user
-        .decrypt(&ctx.config.master_key)
-        .map_err(|err| {
-            tracing::error!("Failed to decrypt user: {}", err);
-            actix_web::error::ErrorInternalServerError("Failed to decrypt user")
-        })?;
+    let rsa_scheme = encrypted_user
+        .encrypted_signing_key
+        .rsa_scheme
+        .unwrap_or_default();
+    // Public keys of users registered before they were stored are filled in by the re-wrap
+    // job of the admin service, verification never decrypts the private key
+    let Some(public_key) = encrypted_user.public_key else {
+        return Err(actix_web::error::ErrorConflict(
+            "Public key of the user is not stored yet",
+        ));
+    };
 
-    // Sign message
-    let signature = user.signing_key.sign_message(message.as_str());
+    let valid = verifier::verify_with_scheme(&public_key, rsa_scheme, &message, &signature)
+        .map_err(|_| ApiError(Error::InvalidSignature))?;
 
-    Ok(HttpResponse::Ok().json(SignMessageResponse { message, signature }))
+    Ok(HttpResponse::Ok().json(VerifySignatureResponse { valid }))
 }
 
 pub(crate) async fn revoke_user(
     ctx: Data<Context>,
+    client_id: ReqData<ClientId>,
     path: Path<UserId>,
 ) -> actix_web::Result<HttpResponse> {
+    let client_id = client_id.into_inner();
     let user_id = path.into_inner();
     tracing::debug!("Revoking user: {:?}", user_id);
 
-    match WalletRepository::delete_user(&ctx.database, user_id.clone()).await {
-        Ok(_) => {
+    match WalletRepository::delete_user(&ctx.database, client_id, user_id.clone()).await {
+        Ok(true) => {
             tracing::debug!("User {:?} revoked successfully", user_id);
             Ok(HttpResponse::NoContent().finish())
         }
+        Ok(false) => Err(actix_web::error::ErrorNotFound("User not found")),
         Err(err) => {
             tracing::error!("Failed to revoke user: {}", err);
             Err(actix_web::error::ErrorInternalServerError(
@@ -119,3 +571,69 @@ pub(crate) async fn revoke_user(
         }
     }
 }
+
+/// Loads the user owned by the client and decrypts its signing key.
+async fn get_user(
+    ctx: &Context,
+    tenant_key: &ClientTenantKey,
+    client_id: ClientId,
+    user_id: UserId,
+) -> actix_web::Result<User> {
+    let encrypted_user = WalletRepository::get_user(&ctx.database, client_id, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get user: {}", err);
+            actix_web::error::ErrorInternalServerError("Failed to get user")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("User not found"))?;
+
+    encrypted_user
+        .decrypt(&tenant_key.key_ring(&ctx.master_keys))
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to decrypt user: {}", err);
+            actix_web::error::ErrorInternalServerError("Failed to decrypt user")
+        })
+}
+
+/// Loads the user's signing key, derived at the requested path if any.
+async fn get_signing_key(
+    ctx: &Context,
+    tenant_key: &ClientTenantKey,
+    client_id: ClientId,
+    user_id: UserId,
+    query: SignQuery,
+) -> actix_web::Result<SigningKey> {
+    let user = get_user(ctx, tenant_key, client_id, user_id).await?;
+    match query.path {
+        Some(path) => Ok(user.signing_key.derive(&path).map_err(ApiError::from)?),
+        None => Ok(user.signing_key),
+    }
+}
+
+async fn add_account(
+    ctx: &Context,
+    client_id: ClientId,
+    user_id: UserId,
+    account: Account,
+) -> actix_web::Result<bool> {
+    WalletRepository::add_account(&ctx.database, client_id, user_id, account)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to add account: {}", err);
+            actix_web::error::ErrorInternalServerError("Failed to add account")
+        })
+}
+
+async fn list_accounts(
+    ctx: &Context,
+    client_id: ClientId,
+    user_id: UserId,
+) -> actix_web::Result<Vec<Account>> {
+    WalletRepository::list_accounts(&ctx.database, client_id, user_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to list accounts: {}", err);
+            actix_web::error::ErrorInternalServerError("Failed to list accounts")
+        })
+}
@@ -1,37 +1,319 @@
-use crate::{context::Context, middleware, routes};
+use crate::{context::Context, middleware, oauth, routes};
 use actix_web::{
     dev::Server,
     web::{self, Data},
     App, HttpResponse, HttpServer,
 };
+use kms::unseal;
 use std::net::Ipv4Addr;
 use tracing_actix_web::TracingLogger;
+use types::certificate::CertificateFingerprint;
 
 pub fn make_server(ctx: Context) -> anyhow::Result<Server> {
     let port = ctx.config.port;
+    let max_body_size = ctx.config.batch.max_body_size;
+    let tls_config = ctx
+        .config
+        .tls
+        .as_ref()
+        .map(tls::server::server_config)
+        .transpose()?;
     let data = Data::new(ctx);
 
     let server = HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
-            .wrap(middleware::auth::Auth)
+            .wrap(actix_web::middleware::from_fn(
+                unseal::reject_sealed::<Context>,
+            ))
             .wrap(middleware::request::RequestId)
             .wrap(TracingLogger::default())
-            .service(web::resource("/wallet/register").route(web::post().to(routes::register_user)))
-            .service(
-                web::resource("/wallet/{user_id}/sign").route(web::post().to(routes::sign_message)),
-            )
-            .service(
-                web::resource("/wallet/{user_id}/revoke")
-                    .route(web::delete().to(routes::revoke_user)),
-            )
+            .configure(|config| services(config, max_body_size))
             .default_service(web::to(|| {
                 tracing::error!("Route not found");
                 HttpResponse::NotFound()
             }))
     })
-    .bind((Ipv4Addr::UNSPECIFIED, port))?
+    // The middleware matches the client certificate against the authenticated client
+    .on_connect(|connection, data| {
+        if let Some(fingerprint) = tls::server::peer_fingerprint(connection) {
+            data.insert::<CertificateFingerprint>(fingerprint);
+        }
+    });
+
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((Ipv4Addr::UNSPECIFIED, port), tls_config)?,
+        None => server.bind((Ipv4Addr::UNSPECIFIED, port))?,
+    }
     .run();
 
     Ok(server)
 }
+
+/// Routes of the service with their authentication middlewares, batches accepting bodies of up
+/// to `max_body_size` bytes.
+pub(crate) fn services(config: &mut web::ServiceConfig, max_body_size: usize) {
+    config
+        .service(
+            web::resource("/unseal")
+                .route(web::get().to(unseal::get_unseal::<Context>))
+                .route(web::post().to(unseal::unseal::<Context>)),
+        )
+        .service(web::resource("/oauth/token").route(web::post().to(oauth::token)))
+        .service(
+            web::scope("/wallet")
+                .wrap(middleware::auth::Auth)
+                .service(web::resource("/register").route(web::post().to(routes::register_user)))
+                .service(
+                    web::resource("/{user_id}/sign").route(web::post().to(routes::sign_message)),
+                )
+                .service(
+                    web::resource("/{user_id}/sign/batch")
+                        // Other routes keep the default body limits
+                        .app_data(web::PayloadConfig::new(max_body_size))
+                        .app_data(web::JsonConfig::default().limit(max_body_size))
+                        .route(web::post().to(routes::sign_batch)),
+                )
+                .service(
+                    web::resource("/{user_id}/sign/personal")
+                        .route(web::post().to(routes::sign_personal_message)),
+                )
+                .service(
+                    web::resource("/{user_id}/sign/typed-data")
+                        .route(web::post().to(routes::sign_typed_data)),
+                )
+                .service(
+                    web::resource("/{user_id}/sign/transaction")
+                        .route(web::post().to(routes::sign_transaction)),
+                )
+                .service(
+                    web::resource("/{user_id}/verify")
+                        .route(web::post().to(routes::verify_signature)),
+                )
+                .service(
+                    web::resource("/{user_id}/accounts")
+                        .route(web::post().to(routes::derive_account))
+                        .route(web::get().to(routes::get_accounts)),
+                )
+                .service(
+                    web::resource("/{user_id}/revoke").route(web::delete().to(routes::revoke_user)),
+                ),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{Config, NonceStore};
+    use actix_http::Request;
+    use actix_web::{
+        body::MessageBody,
+        dev::{Service, ServiceResponse},
+        http::{Method, StatusCode},
+        test,
+    };
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hmac::{Hmac, Mac};
+    use kms::master_keys::MasterKeys;
+    use memory_database::MemoryStore;
+    use postgres_database::{KeyStore, PostgresPool};
+    use repositories::{client::ClientRepository, credentials::CredentialsRepository};
+    use sha2::Sha256;
+    use sqlx::PgPool;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use types::{
+        client::{Client, Credentials},
+        request_signature,
+        scope::Scope,
+    };
+    use uuid::Uuid;
+
+    /// Context on the test database, with nonces kept in memory.
+    ///
+    /// Clients are created without tenant keys, the key store is never queried for one.
+    fn context(pg_pool: PgPool) -> Context {
+        let connection = serde_json::json!({
+            "user": "admin",
+            "dbname": "pontoon",
+            "port": 5432,
+            "password": "",
+            "host": "localhost",
+        });
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "rust_log": "info",
+            "port": 8000,
+            "master_key": "../.local/master_key.txt",
+            "auth": { "nonce_store": "memory" },
+            "database": connection,
+            "key_store": connection,
+        }))
+        .expect("config");
+        Context {
+            master_keys: MasterKeys::from_config(None, None, config.master_key.as_deref())
+                .expect("master keys"),
+            config,
+            database: PostgresPool {
+                pg_pool: pg_pool.clone(),
+            },
+            key_store: KeyStore { pg_pool },
+            nonce_store: NonceStore::Memory(MemoryStore::new()),
+        }
+    }
+
+    /// Stores a client with credentials granted `scopes`, returning their API key and secret.
+    async fn credentials(
+        ctx: &Context,
+        scopes: impl IntoIterator<Item = Scope>,
+    ) -> (String, String) {
+        let client = Client::new("client".to_string());
+        let encrypted_client = client
+            .encrypt(&ctx.master_keys)
+            .await
+            .expect("encrypt client");
+        ClientRepository::create(&ctx.database, encrypted_client)
+            .await
+            .expect("create client");
+        let credentials = Credentials::generate_with_scopes(client.id().clone(), scopes);
+        let encrypted_credentials = credentials
+            .encrypt(&ctx.master_keys)
+            .await
+            .expect("encrypt credentials");
+        CredentialsRepository::create_credentials(&ctx.database, encrypted_credentials, None)
+            .await
+            .expect("create credentials");
+
+        let credentials = serde_json::to_value(&credentials).expect("serialize credentials");
+        (
+            credentials["api_key"]
+                .as_str()
+                .expect("api key")
+                .to_string(),
+            credentials["secret"].as_str().expect("secret").to_string(),
+        )
+    }
+
+    async fn app(
+        ctx: Context,
+    ) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error>
+    {
+        let max_body_size = ctx.config.batch.max_body_size;
+        test::init_service(
+            App::new()
+                .app_data(Data::new(ctx))
+                .configure(|config| services(config, max_body_size)),
+        )
+        .await
+    }
+
+    /// Request signed with `secret`, with a nonce of its own.
+    fn signed_request(
+        method: Method,
+        path: &str,
+        body: &str,
+        api_key: &str,
+        secret: &str,
+    ) -> test::TestRequest {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("now")
+            .as_secs();
+        let nonce = Uuid::new_v4().to_string();
+        let message = request_signature::message_v1(
+            timestamp,
+            Some(&nonce),
+            method.as_str(),
+            path,
+            "",
+            body.as_bytes(),
+        );
+        let mut hmac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac key");
+        hmac.update(message.as_bytes());
+        let signature = STANDARD.encode(hmac.finalize().into_bytes());
+
+        test::TestRequest::default()
+            .method(method)
+            .uri(path)
+            .insert_header(("x-api-key", api_key))
+            .insert_header(("x-timestamp", timestamp.to_string()))
+            .insert_header(("x-nonce", nonce))
+            .insert_header(("x-signature", signature))
+            .insert_header(("content-type", "application/json"))
+            .set_payload(body.to_string())
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_sign_only_credentials_cannot_register_or_revoke(pg_pool: PgPool) {
+        let ctx = context(pg_pool);
+        let (api_key, secret) = credentials(&ctx, [Scope::UsersSign]).await;
+        let app = app(ctx).await;
+        let user_id = Uuid::new_v4();
+
+        let request = signed_request(Method::POST, "/wallet/register", "{}", &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let path = format!("/wallet/{user_id}/revoke");
+        let request = signed_request(Method::DELETE, &path, "", &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The credentials authenticate on the routes of their scope, for an unknown user here
+        let path = format!("/wallet/{user_id}/sign");
+        let body = r#"{"message":"hello"}"#;
+        let request = signed_request(Method::POST, &path, body, &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_failed_signatures_lock_credentials_out(pg_pool: PgPool) {
+        let ctx = context(pg_pool);
+        let threshold = ctx.config.auth.lockout.threshold;
+        let (api_key, secret) = credentials(&ctx, Scope::ALL).await;
+        let app = app(ctx).await;
+
+        for _ in 0..threshold {
+            let request = signed_request(
+                Method::POST,
+                "/wallet/register",
+                "{}",
+                &api_key,
+                "wrong secret",
+            );
+            let response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Locked out even with the right secret
+        let request = signed_request(Method::POST, "/wallet/register", "{}", &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("retry-after"));
+    }
+
+    #[ignore = "requires DATABASE_URL"]
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_only_batches_accept_large_bodies(pg_pool: PgPool) {
+        let ctx = context(pg_pool);
+        let (api_key, secret) = credentials(&ctx, [Scope::UsersSign]).await;
+        let app = app(ctx).await;
+        let user_id = Uuid::new_v4();
+        // Over the default body limit, well within the batch one
+        let message = "a".repeat(300 * 1024);
+
+        let path = format!("/wallet/{user_id}/sign");
+        let body = serde_json::json!({ "message": message }).to_string();
+        let request = signed_request(Method::POST, &path, &body, &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Read in full, then refused for the unknown user
+        let path = format!("/wallet/{user_id}/sign/batch");
+        let body = serde_json::json!({ "messages": [{ "message": message }] }).to_string();
+        let request = signed_request(Method::POST, &path, &body, &api_key, &secret);
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}